@@ -0,0 +1,155 @@
+//! Attribute the storage of pallets to the crates that implement them.
+//!
+//! The crates are either parsed from the runtime source or given as JSON manifest that maps pallet
+//! names to crates, eg. `{"Balances": "pallet-balances"}`. A manifest can also map to team or
+//! repository names instead.
+
+use crate::{fmt_bytes, fmt_percent, PalletInfo, Units, View, UNKNOWN};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use std::{collections::BTreeMap as Map, fs, path::Path};
+
+/// The crate of each pallet by its name in the runtime.
+pub type Crates = Map<String, String>;
+
+/// Load the crates from a JSON manifest or from the Rust sources in a file or directory.
+pub fn load(path: &str) -> Result<Crates> {
+	let mut crates = Crates::new();
+
+	if path.ends_with(".json") {
+		let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+		let manifest = manifest
+			.as_object()
+			.ok_or_else(|| anyhow!("{} must map pallet names to crates", path))?;
+		for (pallet, krate) in manifest {
+			let krate = krate.as_str().ok_or_else(|| anyhow!("Crate of {} is no string", pallet))?;
+			crates.insert(pallet.clone(), krate.into());
+		}
+	} else {
+		parse_sources(Path::new(path), &mut crates)?;
+	}
+
+	if crates.is_empty() {
+		return Err(anyhow!("No pallets found in {}", path))
+	}
+	log::info!("Found the crates of {} pallets", crates.len());
+	Ok(crates)
+}
+
+/// Print the storage size per crate.
+pub fn print(found_by_pallet: &Map<String, PalletInfo>, crates: &Crates, view: View, units: Units) {
+	let mut by_crate = Map::<&str, (usize, Vec<&str>)>::new();
+	for pallet in found_by_pallet.values() {
+		let size = pallet.items.values().map(|i| view.size(i.key_len, i.value_len)).sum::<usize>();
+		let krate = crates.get(&pallet.name).map_or(UNKNOWN, |c| c.as_str());
+
+		let (total, pallets) = by_crate.entry(krate).or_default();
+		*total += size;
+		pallets.push(&pallet.name);
+	}
+	let total = by_crate.values().map(|(size, _)| size).sum::<usize>();
+
+	println!("Storage by crate:");
+	for (krate, (size, pallets)) in by_crate.iter().sorted_by_key(|(_, (size, _))| size).rev() {
+		println!(
+			"  {}: {} {} ({})",
+			krate,
+			fmt_bytes(*size, false, units),
+			fmt_percent(*size, total),
+			pallets.join(", ")
+		);
+	}
+}
+
+/// Find the runtime declaration in all Rust files below a path.
+fn parse_sources(path: &Path, crates: &mut Crates) -> Result<()> {
+	if path.is_dir() {
+		for entry in fs::read_dir(path)? {
+			let entry = entry?.path();
+			// Build artifacts can contain the sources of dependencies.
+			if entry.file_name().is_some_and(|name| name != "target") {
+				parse_sources(&entry, crates)?;
+			}
+		}
+	} else if path.extension().is_some_and(|ext| ext == "rs") {
+		// Drop comments, since they can appear between the pallets.
+		let source = fs::read_to_string(path)?
+			.lines()
+			.map(|line| line.split_once("//").map_or(line, |(code, _)| code))
+			.join("\n");
+		parse_construct_runtime(&source, crates);
+		parse_runtime_attribute(&source, crates);
+	}
+
+	Ok(())
+}
+
+/// Parse the pallets of `construct_runtime!`, eg. `Balances: pallet_balances = 10,`.
+fn parse_construct_runtime(source: &str, crates: &mut Crates) {
+	for (start, _) in source.match_indices("construct_runtime!") {
+		// The pallets are declared in the body of the runtime enum.
+		let Some(open) = source[start..].find('{') else { continue };
+		let body = &source[start + open + 1..];
+
+		let mut depth = 0;
+		let mut entry = String::new();
+		for c in body.chars() {
+			match c {
+				'{' | '(' | '<' => depth += 1,
+				'}' | ')' | '>' if depth > 0 => depth -= 1,
+				'}' => break,
+				',' if depth == 0 => {
+					parse_entry(&entry, crates);
+					entry.clear();
+					continue
+				},
+				_ => (),
+			}
+			entry.push(c);
+		}
+		parse_entry(&entry, crates);
+	}
+}
+
+/// Parse a single pallet of `construct_runtime!`, eg. `System: frame_system::{Pallet} = 0`.
+fn parse_entry(entry: &str, crates: &mut Crates) {
+	// Skip attributes like `#[cfg(feature = "std")]`.
+	let entry = entry.trim().rsplit_once(']').map_or(entry, |(_, entry)| entry);
+	let Some((name, path)) = entry.split_once(':') else { return };
+	let name = name.trim();
+
+	if name.chars().all(|c| c.is_alphanumeric() || c == '_') && !path.starts_with(':') {
+		if let Some(krate) = crate_of(path) {
+			crates.insert(name.into(), krate);
+		}
+	}
+}
+
+/// Parse the pallets of `#[frame_support::runtime]`, eg. `pub type Balances = pallet_balances;`.
+fn parse_runtime_attribute(source: &str, crates: &mut Crates) {
+	for (start, _) in source.match_indices("pallet_index(") {
+		let rest = &source[start..];
+		let Some(decl) = rest.find("pub type ").map(|i| &rest[i + "pub type ".len()..]) else {
+			continue
+		};
+		let Some((name, path)) = decl.split_once(';').and_then(|(d, _)| d.split_once('=')) else {
+			continue
+		};
+		if let Some(krate) = crate_of(path) {
+			crates.insert(name.trim().into(), krate);
+		}
+	}
+}
+
+/// The crate of a path like `pallet_balances::Pallet<Runtime>`.
+///
+/// Pallets that are defined in the runtime itself are attributed to the runtime crate.
+fn crate_of(path: &str) -> Option<String> {
+	let krate = path.trim().split(|c: char| !(c.is_alphanumeric() || c == '_')).next()?;
+
+	match krate {
+		"" => None,
+		"crate" | "self" | "super" => Some("runtime".into()),
+		krate => Some(krate.into()),
+	}
+}
@@ -0,0 +1,101 @@
+//! Size of the extrinsics and events of a range of blocks.
+//!
+//! Complements the analysis of a snapshot with how much data the chain activity produces. Blocks
+//! are fetched one by one over RPC and decoded with the latest metadata. Extrinsics that it cannot
+//! decode are counted as `Unknown`.
+
+use crate::{fmt_bytes, Units, UNKNOWN};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use polkadot_du::rpc::RpcOptions;
+use std::collections::BTreeMap as Map;
+use subxt::{backend::legacy::LegacyRpcMethods, OnlineClient, SubstrateConfig};
+
+/// Number and size of the extrinsics or events of a pallet.
+#[derive(Default)]
+struct Usage {
+	count: usize,
+	size: usize,
+}
+
+/// Print the size of the extrinsics and events of the blocks `from..=to`.
+pub async fn print(url: &str, rpc: &RpcOptions, from: u32, to: u32, units: Units) -> Result<()> {
+	if to < from {
+		return Err(anyhow!("Block range #{}..=#{} is empty", from, to))
+	}
+	let rpc = rpc.connect(url).await?;
+	let legacy = LegacyRpcMethods::<SubstrateConfig>::new(rpc.clone());
+	let client = OnlineClient::<SubstrateConfig>::from_rpc_client(rpc).await?;
+
+	let mut extrinsics_by_pallet = Map::<String, Usage>::new();
+	let mut events_by_pallet = Map::<String, Usage>::new();
+
+	println!("Blocks #{}..=#{}:", from, to);
+	for number in from..=to {
+		let hash = legacy
+			.chain_get_block_hash(Some(number.into()))
+			.await?
+			.ok_or_else(|| anyhow!("Block #{} is not known to {}", number, url))?;
+		let block = client.blocks().at(hash).await?;
+
+		let extrinsics = block.extrinsics().await?;
+		let mut extrinsics_size = 0;
+		let mut decoded = 0;
+		for extrinsic in extrinsics.iter() {
+			let Ok(extrinsic) = extrinsic else {
+				log::warn!("Could not decode all extrinsics of block #{}", number);
+				break
+			};
+			decoded += 1;
+			// Older blocks can contain calls that the latest metadata does not know anymore.
+			let pallet = extrinsic.pallet_name().unwrap_or(UNKNOWN);
+			let usage = extrinsics_by_pallet.entry(pallet.into()).or_default();
+			usage.count += 1;
+			usage.size += extrinsic.bytes().len();
+			extrinsics_size += extrinsic.bytes().len();
+		}
+		// Decoding stops at the first extrinsic that fails, so the rest is counted by its raw size.
+		if decoded < extrinsics.len() {
+			let body = legacy
+				.chain_get_block(Some(hash))
+				.await?
+				.ok_or_else(|| anyhow!("Block #{} is not known to {}", number, url))?;
+			for raw in body.block.extrinsics.iter().skip(decoded) {
+				let usage = extrinsics_by_pallet.entry(UNKNOWN.into()).or_default();
+				usage.count += 1;
+				usage.size += raw.0.len();
+				extrinsics_size += raw.0.len();
+			}
+		}
+
+		// The events are the value of `System::Events`.
+		let events = block.events().await?;
+		for event in events.iter() {
+			let Ok(event) = event else {
+				log::warn!("Could not decode all events of block #{}", number);
+				break
+			};
+			let usage = events_by_pallet.entry(event.pallet_name().into()).or_default();
+			usage.count += 1;
+			usage.size += event.bytes().len();
+		}
+
+		println!(
+			"  #{}: {} extrinsics of {}, {} events of {}",
+			number,
+			extrinsics.len(),
+			fmt_bytes(extrinsics_size, false, units),
+			events.len(),
+			fmt_bytes(events.bytes().len(), false, units)
+		);
+	}
+
+	for (title, by_pallet) in [("Extrinsics", extrinsics_by_pallet), ("Events", events_by_pallet)] {
+		println!("{} by pallet:", title);
+		for (pallet, usage) in by_pallet.iter().sorted_by_key(|(_, u)| u.size).rev() {
+			println!("  {}: {} of {}", pallet, usage.count, fmt_bytes(usage.size, false, units));
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,207 @@
+//! Check a snapshot against the storage invariants of an assertions file, eg. in the runbooks of
+//! chain operations.
+//!
+//! The file is a list of assertions in a subset of YAML, one mapping per storage item or pallet:
+//!
+//! ```yaml
+//! # Exposures are paged and should be pruned after the history depth.
+//! - item: Staking::ErasStakersPaged
+//!   max_entries: 100000
+//! - pallet: Staking
+//!   max_size: 2GiB
+//! - item: Democracy::PublicProps
+//!   absent: true
+//! ```
+//!
+//! Sizes count the bytes of the keys and values, given like `2GiB` or `500MB`.
+//!
+//! A pallet or item that is not in the metadata fails its limits, since it is likely a typo. It
+//! only passes when it must be absent.
+
+use crate::{fmt_bytes, migration::parse_size, Analysis, Units};
+use anyhow::{anyhow, Result};
+use std::fs;
+
+/// What an assertion is about.
+#[derive(Clone)]
+enum Subject {
+	Pallet(String),
+	Item(String, String),
+}
+
+/// What must hold for the subject of an assertion.
+enum Limit {
+	MaxEntries(usize),
+	MaxSize(usize),
+	Absent,
+}
+
+pub struct Assertion {
+	/// Line of the assertion in the file.
+	line: usize,
+	subject: Subject,
+	limit: Limit,
+}
+
+/// Load the assertions of a file.
+pub fn load(path: &str) -> Result<Vec<Assertion>> {
+	let text = fs::read_to_string(path)
+		.map_err(|e| anyhow!("Failed to read assertions from {}: {}", path, e))?;
+
+	// The `key: value` pairs of each list item, with their line.
+	let mut mappings: Vec<Vec<(usize, &str, &str)>> = Vec::new();
+	for (i, line) in text.lines().enumerate() {
+		let number = i + 1;
+		let not_a_list = || anyhow!("Line {} of {}: expected a list of assertions", number, path);
+		let line = line.split('#').next().unwrap_or_default().trim_end();
+		if line.trim().is_empty() {
+			continue
+		}
+
+		let pair = match line.trim_start().strip_prefix('-') {
+			Some(rest) => {
+				mappings.push(Vec::new());
+				rest.trim()
+			},
+			None if line.starts_with(' ') || line.starts_with('\t') => line.trim(),
+			None => return Err(not_a_list()),
+		};
+		if pair.is_empty() {
+			continue
+		}
+		let (key, value) = pair
+			.split_once(':')
+			.ok_or_else(|| anyhow!("Line {} of {}: expected key: value", number, path))?;
+		let mapping = mappings.last_mut().ok_or_else(not_a_list)?;
+		mapping.push((number, key.trim(), value.trim().trim_matches(['"', '\''])));
+	}
+
+	let mut assertions = Vec::new();
+	for mapping in mappings {
+		let line = mapping.first().map_or(0, |(line, _, _)| *line);
+		let invalid =
+			|line: usize, message: String| anyhow!("Line {} of {}: {}", line, path, message);
+
+		let mut subject = None;
+		let mut limits = Vec::new();
+		for (line, key, value) in mapping {
+			match key {
+				"pallet" => subject = Some(Subject::Pallet(value.into())),
+				"item" => {
+					let (pallet, item) = value.split_once("::").ok_or_else(|| {
+						invalid(line, format!("{} is not of the form Pallet::Item", value))
+					})?;
+					subject = Some(Subject::Item(pallet.into(), item.into()));
+				},
+				"max_entries" => {
+					let max = value.parse().map_err(|_| {
+						invalid(line, format!("{} is not a number of entries", value))
+					})?;
+					limits.push(Limit::MaxEntries(max));
+				},
+				"max_size" => {
+					let max = parse_size(value).map_err(|e| invalid(line, e))?;
+					limits.push(Limit::MaxSize(max));
+				},
+				"absent" => match value {
+					"true" => limits.push(Limit::Absent),
+					"false" => (),
+					_ => {
+						let message = format!("absent must be true or false, not {}", value);
+						return Err(invalid(line, message))
+					},
+				},
+				_ => return Err(invalid(line, format!("Unknown key {}", key))),
+			}
+		}
+
+		let Some(subject) = subject else {
+			return Err(invalid(line, "An assertion needs a pallet or an item".into()))
+		};
+		if limits.is_empty() {
+			return Err(invalid(line, "An assertion needs max_entries, max_size or absent".into()))
+		}
+		for limit in limits {
+			assertions.push(Assertion { line, subject: subject.clone(), limit });
+		}
+	}
+
+	if assertions.is_empty() {
+		return Err(anyhow!("No assertions found in {}", path))
+	}
+	Ok(assertions)
+}
+
+/// Print whether each assertion holds and fail if any does not.
+pub fn run(
+	assertions: &[Assertion],
+	analysis: &Analysis,
+	network: &str,
+	units: Units,
+) -> Result<()> {
+	if let Some(stopped) = analysis.stopped {
+		let reason = stopped.describe();
+		return Err(anyhow!("Cannot check partial results, the analysis was {}", reason))
+	}
+	let meta = analysis.decoder.meta();
+	let block = analysis.provenance.block;
+	let block = block.map_or("unknown block".into(), |b| format!("block {}", b));
+	println!("Checking {} assertions against {} at {}:", assertions.len(), network, block);
+
+	let mut failed = 0;
+	for assertion in assertions {
+		// Entries and bytes of keys and values of the subject.
+		let (name, known, entries, size) = match &assertion.subject {
+			Subject::Pallet(pallet) => {
+				let items = analysis.found_by_pallet.get(pallet).map(|p| p.items.values());
+				let (entries, size) = items.into_iter().flatten().fold((0, 0), |(e, s), item| {
+					(e + item.num_entries, s + item.key_len + item.value_len)
+				});
+				(pallet.clone(), meta.pallet_by_name(pallet).is_some(), entries, size)
+			},
+			Subject::Item(pallet, item) => {
+				let info = analysis.found_by_pallet.get(pallet).and_then(|p| p.items.get(item));
+				let known = meta
+					.pallet_by_name(pallet)
+					.and_then(|p| p.storage())
+					.is_some_and(|s| s.entry_by_name(item).is_some());
+				let entries = info.map_or(0, |i| i.num_entries);
+				let size = info.map_or(0, |i| i.key_len + i.value_len);
+				(format!("{}::{}", pallet, item), known, entries, size)
+			},
+		};
+		let (passed, outcome) = match assertion.limit {
+			// Likely a typo, which must not pass as a subject without entries.
+			Limit::MaxEntries(_) | Limit::MaxSize(_) if !known =>
+				(false, "is not in the metadata".into()),
+			Limit::MaxEntries(max) =>
+				(entries <= max, format!("has {} entries, at most {} allowed", entries, max)),
+			Limit::MaxSize(max) => (
+				size <= max,
+				format!(
+					"has {}, at most {} allowed",
+					fmt_bytes(size, false, units),
+					fmt_bytes(max, false, units)
+				),
+			),
+			Limit::Absent => match entries {
+				0 => (true, "does not exist".into()),
+				_ => (false, format!("exists with {} entries", entries)),
+			},
+		};
+
+		let status = if passed {
+			ansi_term::Color::Green.paint("PASS")
+		} else {
+			failed += 1;
+			ansi_term::Color::Red.paint("FAIL")
+		};
+		println!("  {} {} {} (line {})", status, name, outcome, assertion.line);
+	}
+
+	println!("{} passed, {} failed", assertions.len() - failed, failed);
+	if failed > 0 {
+		return Err(anyhow!("{} of {} assertions failed", failed, assertions.len()))
+	}
+	Ok(())
+}
@@ -0,0 +1,38 @@
+//! Keys of the default child tries, in which pallets like Crowdloan and Contracts keep unbounded
+//! data under a single root in the main trie.
+//!
+//! The main trie stores the root of each child trie under [`PREFIX`] followed by the id of the
+//! trie. Sources that include the child tries for an analysis, like the `--live` one, list the
+//! entries of a child trie right after its root, under the key of the root followed by the key
+//! within the child trie. That is no key of the main trie, so snapshots only hold the roots.
+
+/// Prefix of the keys of the roots of the default child tries.
+pub const PREFIX: &[u8] = b":child_storage:default:";
+
+/// Name under which the child tries are reported next to the pallets.
+pub const CHILD_TRIES: &str = "Child Tries";
+
+/// Length of the ids of child tries, which all pallets derive by hashing, eg. the fund index.
+const ID_LEN: usize = 32;
+
+/// Pallets that keep data in child tries.
+const OWNERS: &[&str] = &["Crowdloan", "Contracts", "Revive"];
+
+/// Split the key of a child trie entry into the id of the trie and the key within the trie.
+///
+/// The key within the trie is empty for the root of the trie.
+pub fn split_key(key: &[u8]) -> Option<(&[u8], &[u8])> {
+	let rest = key.strip_prefix(PREFIX)?;
+	Some(rest.split_at(rest.len().min(ID_LEN)))
+}
+
+/// The pallet that owns the child tries, if only one pallet that uses child tries `is_present`.
+///
+/// The ids are hashes, so they cannot tell apart the tries of multiple such pallets.
+pub fn owner(is_present: impl Fn(&str) -> bool) -> Option<&'static str> {
+	let mut present = OWNERS.iter().filter(|pallet| is_present(pallet));
+	match (present.next(), present.next()) {
+		(Some(owner), None) => Some(owner),
+		_ => None,
+	}
+}
@@ -0,0 +1,68 @@
+//! Check how many keys of a chain the metadata can categorize, without a snapshot.
+//!
+//! Fetches the keys that follow random positions in the key space over RPC. This is quick, so
+//! outdated metadata shows up before a long analysis of a full snapshot. Keys that follow sparse
+//! regions of the key space, like `:code`, are sampled more often than others.
+
+use crate::{build_prefix_lookup, categorize_prefix, fmt_percent, CategorizedKey};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use polkadot_du::rpc::RpcOptions;
+use sp_crypto_hashing::blake2_256;
+use std::collections::BTreeMap as Map;
+use subxt::{backend::legacy::LegacyRpcMethods, Metadata, SubstrateConfig};
+
+/// Number of uncategorized prefixes to show.
+const TOP_UNKNOWN: usize = 10;
+
+/// Sample `samples` keys of the chain at `url` and print the share that `meta` can categorize.
+pub async fn print(url: &str, rpc: &RpcOptions, meta: &Metadata, samples: u32) -> Result<()> {
+	let rpc = rpc.connect(url).await?;
+	let legacy = LegacyRpcMethods::<SubstrateConfig>::new(rpc);
+	let at = legacy.chain_get_finalized_head().await?;
+
+	let pallets = meta.pallets().collect::<Vec<_>>();
+	let lookup = build_prefix_lookup(&pallets);
+	let (mut items, mut pallets) = (0, 0);
+	let mut unknown = Map::<Vec<u8>, usize>::new();
+	let mut sampled = 0;
+
+	for i in 0..samples {
+		// Derive the positions from a hash to keep runs reproducible.
+		let start = blake2_256(&i.to_le_bytes());
+		let keys = legacy.state_get_keys_paged(&[], 1, Some(&start), Some(at)).await?;
+		// Positions after the last key have no key to sample.
+		let Some(key) = keys.first() else { continue };
+
+		match categorize_prefix(key, &lookup) {
+			// Roots of child tries are known like storage items.
+			CategorizedKey::Item(..) | CategorizedKey::Child { .. } => items += 1,
+			CategorizedKey::Pallet(_) => pallets += 1,
+			CategorizedKey::Unknown => {
+				*unknown.entry(key[..key.len().min(16)].to_vec()).or_default() += 1;
+			},
+		}
+		sampled += 1;
+	}
+	if sampled == 0 {
+		return Err(anyhow!("{} returned no keys", url))
+	}
+
+	let num_unknown = unknown.values().sum::<usize>();
+	println!("Sampled {} keys of the finalized block {:?}:", sampled, at);
+	println!("  {} in known storage items", fmt_share(items, sampled));
+	println!("  {} in known pallets but unknown storage items", fmt_share(pallets, sampled));
+	println!("  {} unknown", fmt_share(num_unknown, sampled));
+	for (prefix, count) in unknown.iter().sorted_by_key(|(_, c)| **c).rev().take(TOP_UNKNOWN) {
+		println!("    0x{}: {} keys", hex::encode(prefix), count);
+	}
+
+	Ok(())
+}
+
+/// Format a sampled share with the half-width of its 95% confidence interval.
+fn fmt_share(part: usize, total: usize) -> String {
+	let share = part as f64 / total as f64;
+	let ci = 1.96 * (share * (1.0 - share) / total as f64).sqrt();
+	format!("{} ±{:.1}%", fmt_percent(part, total), ci * 100.0)
+}
@@ -0,0 +1,116 @@
+//! Key-Value dumps as CSV, eg. exported from another tool or a database query.
+//!
+//! Every line is `hex_key,hex_value`, optionally with `0x` prefixes and quotes. A header line is
+//! skipped. Reference counts are not part of a dump, so every entry has a count of one.
+
+use crate::{
+	error::{PduError, Result},
+	snapshot::SnapshotEntry,
+};
+use std::{
+	fs::File,
+	io::{BufRead, BufReader, Lines},
+};
+
+/// Reads the entries of a CSV dump one by one.
+pub struct CsvReader {
+	path: String,
+	lines: Lines<BufReader<File>>,
+	/// Number of the last read line, starting at one.
+	line: usize,
+	num_keys: usize,
+	/// Entries with keys before this are skipped.
+	start_key: Option<Vec<u8>>,
+	/// Entries with keys from this on are skipped.
+	end_key: Option<Vec<u8>>,
+}
+
+impl CsvReader {
+	/// Open a CSV dump and count its entries.
+	pub fn open(path: &str) -> Result<Self> {
+		let open = || {
+			File::open(path)
+				.map(|f| BufReader::new(f).lines())
+				.map_err(|e| PduError::io(format!("Failed to open CSV dump {}", path), e))
+		};
+
+		// The number of entries precedes them in a snapshot, so the analysis needs it upfront.
+		let mut num_keys = 0;
+		for (i, line) in open()?.enumerate() {
+			let line = line.map_err(|e| PduError::io(format!("Failed to read {}", path), e))?;
+			if line.trim().is_empty() || (i == 0 && parse_line(&line).is_err()) {
+				continue
+			}
+			num_keys += 1;
+		}
+		log::info!("Loading {} entries from CSV dump", num_keys);
+
+		Ok(Self {
+			path: path.into(),
+			lines: open()?,
+			line: 0,
+			num_keys,
+			start_key: None,
+			end_key: None,
+		})
+	}
+
+	/// Only return the entries from `start` on and before `end`.
+	///
+	/// Dumps need not be sorted, so the keys are filtered instead of ending the dump early.
+	pub fn range(mut self, start: Option<Vec<u8>>, end: Option<Vec<u8>>) -> Self {
+		self.start_key = start;
+		self.end_key = end;
+		self
+	}
+
+	pub fn num_keys(&self) -> usize {
+		self.num_keys
+	}
+
+	fn in_range(&self, key: &[u8]) -> bool {
+		self.start_key.as_deref().is_none_or(|start| key >= start) &&
+			self.end_key.as_deref().is_none_or(|end| key < end)
+	}
+}
+
+impl Iterator for CsvReader {
+	type Item = Result<SnapshotEntry>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let line = match self.lines.next()? {
+				Ok(line) => line,
+				Err(e) => {
+					let context = format!("Failed to read line {} of {}", self.line + 1, self.path);
+					return Some(Err(PduError::io(context, e)))
+				},
+			};
+			self.line += 1;
+			if line.trim().is_empty() {
+				continue
+			}
+
+			match parse_line(&line) {
+				Ok((key, _)) if !self.in_range(&key) => continue,
+				Ok((key, value)) => return Some(Ok((key, (value, 1)))),
+				Err(_) if self.line == 1 => continue,
+				Err(e) => {
+					let e = format!("Line {} of {}: {}", self.line, self.path, e);
+					return Some(Err(PduError::SnapshotFormat(e)))
+				},
+			}
+		}
+	}
+}
+
+fn parse_line(line: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+	let (key, value) = line.split_once(',').ok_or("Expected hex_key,hex_value")?;
+	let parse = |field: &str, name: &str| {
+		let field = field.trim().trim_matches('"');
+		hex::decode(field.strip_prefix("0x").unwrap_or(field))
+			.map_err(|e| format!("Invalid hex {}: {}", name, e))
+	};
+
+	Ok((parse(key, "key")?, parse(value, "value")?))
+}
@@ -0,0 +1,66 @@
+//! Machine-readable description of the storage layout of a chain.
+//!
+//! Types are referenced by their id in the metadata, which is also what Polkadot-API uses in its
+//! lookup. Their paths are listed separately so that the descriptor is readable on its own.
+
+use serde_json::{json, Map as JsonMap, Value};
+use sp_crypto_hashing::twox_128;
+use std::collections::BTreeSet;
+use subxt::Metadata;
+use subxt_metadata::{StorageEntryModifier, StorageEntryType};
+
+/// Describe the prefixes, hashers and types of all storage items.
+pub fn describe(network: &str, meta: &Metadata) -> Value {
+	let mut types = BTreeSet::new();
+	let mut pallets = Vec::new();
+
+	for pallet in meta.pallets() {
+		let pallet_prefix = twox_128(pallet.name().as_bytes());
+		let mut items = Vec::new();
+
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			let prefix = [pallet_prefix, twox_128(entry.name().as_bytes())].concat();
+			let (hashers, key) = match entry.entry_type() {
+				StorageEntryType::Plain(_) => (Vec::new(), None),
+				StorageEntryType::Map { hashers, key_ty, .. } =>
+					(hashers.iter().map(|h| format!("{:?}", h)).collect(), Some(*key_ty)),
+			};
+			let value = entry.entry_type().value_ty();
+			types.extend(key.into_iter().chain([value]));
+
+			items.push(json!({
+				"name": entry.name(),
+				"prefix": format!("0x{}", hex::encode(prefix)),
+				"modifier": match entry.modifier() {
+					StorageEntryModifier::Optional => "Optional",
+					StorageEntryModifier::Default => "Default",
+				},
+				"hashers": hashers,
+				"key": key,
+				"value": value,
+			}));
+		}
+
+		pallets.push(json!({
+			"name": pallet.name(),
+			"index": pallet.index(),
+			"prefix": format!("0x{}", hex::encode(pallet_prefix)),
+			"storage": items,
+		}));
+	}
+
+	// Types without a path, like tuples and primitives, can only be looked up by their id.
+	let types = types
+		.into_iter()
+		.filter_map(|id| {
+			let path = &meta.types().resolve(id)?.path.segments;
+			(!path.is_empty()).then(|| (id.to_string(), path.join("::").into()))
+		})
+		.collect::<JsonMap<_, _>>();
+
+	json!({
+		"network": network,
+		"pallets": pallets,
+		"types": types,
+	})
+}
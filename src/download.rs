@@ -0,0 +1,53 @@
+//! Create a snapshot from the state of a live node, like `try-runtime create-snapshot` does.
+
+use crate::pallet_range;
+use anyhow::Result;
+use futures::StreamExt;
+use itertools::Itertools;
+use polkadot_du::{live::LiveReader, rpc::RpcOptions, snapshot::SnapshotWriter, source::KvSource};
+use subxt::utils::H256;
+
+/// Download the state of block `at` from the node at `url` and write it as snapshot to `out`.
+///
+/// Only the keys of `pallets` are downloaded, unless it is empty.
+pub async fn run(
+	url: &str,
+	rpc: &RpcOptions,
+	at: Option<H256>,
+	pallets: &[String],
+	out: &str,
+) -> Result<()> {
+	let reader = LiveReader::connect_with(url, at, rpc).await?;
+	let state_version = reader.state_version().await?;
+	let trailer = reader.trailer().await?;
+
+	// A snapshot is sorted by key, so the pallets are downloaded in the order of their keys.
+	let mut pallets = pallets.iter().unique().map(|p| Some(p.as_str())).collect::<Vec<_>>();
+	pallets.sort_by_key(|pallet| pallet.map(pallet_range));
+	if pallets.is_empty() {
+		// All keys.
+		pallets.push(None);
+	}
+
+	let mut snapshot = SnapshotWriter::create(out, state_version)?;
+	for pallet in pallets {
+		let (start, end) = pallet.map_or((None, None), |pallet| {
+			let (start, end) = pallet_range(pallet);
+			(Some(start), end)
+		});
+		let mut entries = std::pin::pin!(reader.clone().range(start, end).stream());
+		let mut num_keys = 0;
+		while let Some(entry) = entries.next().await {
+			snapshot.write(&entry?)?;
+			num_keys += 1;
+		}
+
+		match pallet {
+			Some(pallet) if num_keys == 0 => log::warn!("Pallet {} has no keys", pallet),
+			Some(pallet) => log::info!("Downloaded {} keys of pallet {}", num_keys, pallet),
+			None => log::info!("Downloaded {} keys", num_keys),
+		}
+	}
+
+	Ok(snapshot.finish(&trailer)?)
+}
@@ -0,0 +1,70 @@
+//! Compare the snapshots of multiple chains side by side.
+
+use crate::{
+	analyze, fmt_bytes, fmt_percent, progress_bar, unknown_size, Cli, PalletInfo, Units, UNKNOWN,
+};
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use itertools::Itertools;
+use std::{collections::BTreeMap as Map, fs, path::Path};
+
+/// Analyze every `*.snap` file in a directory and print how the chains compare.
+///
+/// The network name is taken from the file name, so the metadata is loaded from `<network>.meta`
+/// like for a single snapshot.
+pub async fn print(dir: &str, top: usize, units: Units) -> Result<()> {
+	let snapshots = fs::read_dir(dir)?
+		.map(|entry| entry.map(|e| e.path()))
+		.filter_ok(|path| path.extension().is_some_and(|ext| ext == "snap"))
+		.collect::<Result<Vec<_>, _>>()?;
+	if snapshots.is_empty() {
+		return Err(anyhow!("No snapshots found in {}", dir))
+	}
+
+	let mut chains = Vec::new();
+	for path in snapshots.iter().sorted() {
+		let network = network_name(path)?;
+		// Parse the arguments like for a single snapshot to get the same defaults.
+		let snapshot = path.to_string_lossy();
+		let cli = Cli::parse_from(["pdu", "--network", &network, "--snapshot", &snapshot]);
+		let args = cli.args.expect("Parsed without a subcommand; qed");
+
+		let analysis = analyze(&args, progress_bar(args.units)).await?;
+		chains.push((network, analysis.found_by_pallet));
+	}
+
+	println!("Ecosystem of {} chains:", chains.len());
+	for (network, found_by_pallet) in chains.iter().sorted_by_key(|(_, f)| total_size(f)).rev() {
+		let total = total_size(found_by_pallet);
+		let (unknown_pallets, unknown_items) = unknown_size(found_by_pallet);
+		let top_pallets = found_by_pallet
+			.values()
+			.filter(|p| p.name != UNKNOWN)
+			.sorted_by_key(|p| p.size)
+			.rev()
+			.take(top)
+			.map(|p| format!("{} {}", p.name, fmt_percent(p.size, total)))
+			.join(", ");
+
+		println!(
+			"  {}: {}, {} {}, top pallets: {}",
+			network,
+			fmt_bytes(total, false, units),
+			fmt_percent(unknown_pallets + unknown_items, total),
+			UNKNOWN,
+			top_pallets
+		);
+	}
+
+	Ok(())
+}
+
+fn network_name(path: &Path) -> Result<String> {
+	path.file_stem()
+		.map(|stem| stem.to_string_lossy().into_owned())
+		.ok_or_else(|| anyhow!("Snapshot {} has no file name", path.display()))
+}
+
+fn total_size(found_by_pallet: &Map<String, PalletInfo>) -> usize {
+	found_by_pallet.values().map(|p| p.size).sum()
+}
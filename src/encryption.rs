@@ -0,0 +1,142 @@
+//! Encryption of snapshots at rest with the `age` or `gpg` command line tools.
+//!
+//! Keys never pass through PDU: the tools run as child processes, and decrypted snapshots are
+//! streamed from them without touching the disk.
+
+use crate::process::{self, Output};
+use crate::error::{PduError, Result};
+use std::{path::Path, process::Command};
+
+/// A tool to encrypt snapshots with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Tool {
+	/// `age`, with recipients given as public keys or files with public keys.
+	Age,
+	/// `gpg`, with recipients given as key ids or user ids of public keys.
+	Gpg,
+}
+
+impl Tool {
+	/// The tool that a file was encrypted with, by its extension.
+	pub fn of(path: &str) -> Option<Self> {
+		match Path::new(path).extension()?.to_str()? {
+			"age" => Some(Tool::Age),
+			"gpg" => Some(Tool::Gpg),
+			_ => None,
+		}
+	}
+
+	pub fn extension(self) -> &'static str {
+		match self {
+			Tool::Age => "age",
+			Tool::Gpg => "gpg",
+		}
+	}
+
+	fn command(self) -> Command {
+		let mut command = Command::new(self.extension());
+		if self == Tool::Gpg {
+			command.args(["--batch", "--quiet", "--yes"]);
+		}
+		command
+	}
+
+	/// Decrypt the file at `path` to `out`, using stdin and stdout for what is `None`.
+	fn decrypt_command(
+		self,
+		path: Option<&str>,
+		identity: Option<&str>,
+		out: Option<&str>,
+	) -> Command {
+		let mut command = self.command();
+		command.arg("--decrypt");
+		if let (Tool::Age, Some(identity)) = (self, identity) {
+			command.args(["--identity", identity]);
+		}
+		if let Some(out) = out {
+			command.args(["--output", out]);
+		}
+		command.args(path);
+		command
+	}
+}
+
+/// Encrypt a snapshot for the given recipients.
+///
+/// Writes to `<snapshot>.age` or `<snapshot>.gpg` unless `out` is given.
+pub fn encrypt(tool: Tool, snapshot: &str, out: Option<&str>, recipients: &[String]) -> Result<()> {
+	let out = out.map_or_else(|| format!("{}.{}", snapshot, tool.extension()), Into::into);
+	let mut command = tool.command();
+	command.arg("--encrypt");
+	for recipient in recipients {
+		// `age` takes files with public keys separately.
+		let flag = match tool {
+			Tool::Age if Path::new(recipient).is_file() => "--recipients-file",
+			_ => "--recipient",
+		};
+		command.args([flag, recipient]);
+	}
+	command.args(["--output", &out, snapshot]);
+
+	process::run(tool.extension(), &mut command)?;
+	log::info!("Wrote the encrypted snapshot to {}", out);
+	Ok(())
+}
+
+/// Decrypt a `.age` or `.gpg` snapshot.
+///
+/// Writes to the path without the extension unless `out` is given.
+pub fn decrypt(snapshot: &str, out: Option<&str>, identity: Option<&str>) -> Result<()> {
+	let tool = Tool::of(snapshot).ok_or_else(|| {
+		PduError::SnapshotFormat(format!(
+			"Encrypted snapshots must end with .age or .gpg: {}",
+			snapshot
+		))
+	})?;
+	let out = match out {
+		Some(out) => out.to_string(),
+		None => snapshot.strip_suffix(&format!(".{}", tool.extension())).unwrap_or(snapshot).into(),
+	};
+
+	let mut command = tool.decrypt_command(Some(snapshot), identity, Some(&out));
+	process::run(tool.extension(), &mut command)?;
+	log::info!("Wrote the decrypted snapshot to {}", out);
+	Ok(())
+}
+
+/// Stream the decrypted content of a `.age` or `.gpg` file.
+///
+/// The tools check the integrity of a file only at its end, so the content is not known to be
+/// authentic before [`Output::finish`] succeeded.
+pub fn decrypted(path: &str, identity: Option<&str>) -> Result<Output> {
+	let tool = Tool::of(path).ok_or_else(|| not_encrypted(path))?;
+	check_identity(tool, path, identity)?;
+	log::info!("Decrypting snapshot with {}", tool.extension());
+
+	Output::spawn(tool.extension(), &mut tool.decrypt_command(Some(path), identity, None))
+}
+
+/// Decrypt the output of another tool, eg. the download of a `.age` or `.gpg` file.
+///
+/// Like [`decrypted`], the content must be checked with [`Output::finish`].
+pub fn decrypt_output(path: &str, identity: Option<&str>, input: Output) -> Result<Output> {
+	let tool = Tool::of(path).ok_or_else(|| not_encrypted(path))?;
+	check_identity(tool, path, identity)?;
+	log::info!("Decrypting snapshot with {}", tool.extension());
+
+	input.pipe(tool.extension(), &mut tool.decrypt_command(None, identity, None))
+}
+
+fn check_identity(tool: Tool, path: &str, identity: Option<&str>) -> Result<()> {
+	if tool == Tool::Age && identity.is_none() {
+		return Err(PduError::SnapshotFormat(format!(
+			"Decrypting {} needs the identity file of a recipient",
+			path
+		)))
+	}
+	Ok(())
+}
+
+fn not_encrypted(path: &str) -> PduError {
+	PduError::SnapshotFormat(format!("{} is not encrypted", path))
+}
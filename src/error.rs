@@ -0,0 +1,31 @@
+//! Errors of the library, by what failed.
+
+use std::io;
+
+/// An error of the library, so that embedders can tell what failed.
+#[derive(Debug, thiserror::Error)]
+pub enum PduError {
+	/// Reading or writing a snapshot failed, including the tools that it is streamed through.
+	#[error("{context}")]
+	SnapshotIo { context: String, source: io::Error },
+	/// A snapshot or dump is malformed, or cannot be read as it is.
+	#[error("{0}")]
+	SnapshotFormat(String),
+	/// The metadata does not allow what was asked of a storage item.
+	#[error("{0}")]
+	Metadata(String),
+	/// A node or an object storage could not be reached.
+	#[error("{0}")]
+	Rpc(String),
+	/// A key or value does not decode as its type.
+	#[error("{context}")]
+	Decode { context: String, source: parity_scale_codec::Error },
+}
+
+impl PduError {
+	pub(crate) fn io(context: impl Into<String>, source: io::Error) -> Self {
+		Self::SnapshotIo { context: context.into(), source }
+	}
+}
+
+pub type Result<T, E = PduError> = std::result::Result<T, E>;
@@ -0,0 +1,323 @@
+//! A tiny synthetic chain to test PDU and the tools that build on it without a node.
+//!
+//! `pdu fixture` writes its metadata and snapshot, which are then analyzed offline like any
+//! network:
+//!
+//! ```sh
+//! pdu fixture && pdu --network fixture
+//! ```
+//!
+//! The entries cover plain values, maps with one and with multiple keys, empty and zeroed values,
+//! unknown items, keys that belong to no pallet and the root of a child trie. They never change
+//! between versions, so that the results can be compared against golden files. The entries of the
+//! child trie are not in the snapshot, see [`child_entries`].
+
+use crate::{
+	child,
+	error::{PduError, Result},
+	snapshot::{SnapshotEntry, SnapshotWriter},
+};
+use frame_metadata::{v15::*, RuntimeMetadataPrefixed};
+use parity_scale_codec::{Decode, Encode};
+use scale_info::{meta_type, MetaType, TypeInfo};
+use sp_crypto_hashing::{blake2_128, blake2_256, twox_128, twox_64};
+use std::{fs, path::Path};
+use subxt::Metadata;
+
+/// Network name under which `pdu fixture` writes the fixture by default.
+pub const NETWORK: &str = "fixture";
+
+/// Block number of the snapshot, as stored in `System::Number`.
+pub const BLOCK: u32 = 1000;
+
+#[derive(TypeInfo, Encode)]
+struct AccountId32([u8; 32]);
+
+#[derive(TypeInfo, Encode, Default)]
+struct AccountData {
+	free: u128,
+	reserved: u128,
+	frozen: u128,
+	flags: u128,
+}
+
+#[derive(TypeInfo, Encode, Default)]
+struct AccountInfo {
+	nonce: u32,
+	consumers: u32,
+	providers: u32,
+	sufficients: u32,
+	data: AccountData,
+}
+
+#[derive(TypeInfo, Encode)]
+struct BalanceLock {
+	id: [u8; 8],
+	amount: u128,
+	reasons: u8,
+}
+
+#[derive(TypeInfo, Encode)]
+struct IndividualExposure {
+	who: AccountId32,
+	value: u128,
+}
+
+#[derive(TypeInfo, Encode)]
+struct ExposurePage {
+	page_total: u128,
+	others: Vec<IndividualExposure>,
+}
+
+#[derive(TypeInfo, Encode)]
+struct FundInfo {
+	depositor: AccountId32,
+	raised: u128,
+	fund_index: u32,
+}
+
+#[derive(TypeInfo)]
+struct Runtime;
+
+#[derive(TypeInfo)]
+enum RuntimeCall {}
+
+/// The metadata of the fixture chain.
+pub fn metadata() -> Metadata {
+	Metadata::decode(&mut runtime_metadata().encode().as_slice())
+		.expect("The fixture metadata is valid; qed")
+}
+
+/// The entries of the fixture snapshot, sorted by key.
+pub fn entries() -> Vec<SnapshotEntry> {
+	let account = |i: u8| [i; 32];
+	let mut entries = Vec::new();
+	let mut push = |key: Vec<u8>, value: Vec<u8>| entries.push((key, (value, 1)));
+
+	for i in 0..20u8 {
+		// Every fifth account is dust that was never reaped.
+		let free = if i % 5 == 0 { 0 } else { 1_000_000_000_000 * i as u128 };
+		let info = AccountInfo {
+			nonce: i as u32,
+			providers: 1,
+			data: AccountData { free, ..Default::default() },
+			..Default::default()
+		};
+		push(blake2_128_concat(prefix("System", "Account"), &account(i)), info.encode());
+	}
+	push(prefix("System", "Number"), BLOCK.encode());
+	for n in BLOCK - 4..=BLOCK {
+		push(twox_64_concat(prefix("System", "BlockHash"), &n.encode()), [n as u8; 32].encode());
+	}
+
+	push(prefix("Balances", "TotalIssuance"), 95_000_000_000_000u128.encode());
+	for i in 0..5u8 {
+		let locks = vec![BalanceLock { id: *b"staking ", amount: 100 * i as u128, reasons: 2 }];
+		push(blake2_128_concat(prefix("Balances", "Locks"), &account(i)), locks.encode());
+	}
+	// An item that was removed from the metadata, but not from storage.
+	push(prefix("Balances", "Gone"), vec![1, 2, 3]);
+
+	push(prefix("Staking", "CurrentEra"), 3u32.encode());
+	for era in 1..=3u32 {
+		for validator in 0..era as u8 {
+			let others = (0..era as u8)
+				.map(|i| IndividualExposure { who: AccountId32(account(100 + i)), value: 10 })
+				.collect::<Vec<_>>();
+			let page = ExposurePage { page_total: 10 * era as u128, others };
+			let key = twox_64_concat(prefix("Staking", "ErasStakersPaged"), &era.encode());
+			let key = twox_64_concat(key, &account(validator));
+			push(twox_64_concat(key, &0u32.encode()), page.encode());
+		}
+	}
+	// Entries that are not needed anymore, but were not removed.
+	push(twox_64_concat(prefix("Staking", "Ledger"), &account(0)), vec![]);
+	push(twox_64_concat(prefix("Staking", "Ledger"), &account(1)), vec![0; 16]);
+
+	// A crowdloan with its contributions in a child trie.
+	let fund = FundInfo { depositor: AccountId32(account(0)), raised: 30, fund_index: 0 };
+	push(twox_64_concat(prefix("Crowdloan", "Funds"), &2000u32.encode()), fund.encode());
+	push(crowdloan_root(), [0xcc; 32].to_vec());
+
+	push(b":code".to_vec(), vec![0; 2000]);
+	push(b":heappages".to_vec(), 8u64.encode());
+
+	entries.sort();
+	entries
+}
+
+/// The contributions to the crowdloan of the fixture, which are in its child trie.
+///
+/// They are keyed like the `--live` analysis reads them, by the key of the root followed by the key
+/// within the trie, which is no key of the main trie. So they are only analyzed in memory, together
+/// with [`entries`].
+pub fn child_entries() -> Vec<SnapshotEntry> {
+	let root = crowdloan_root();
+	(0..3u8)
+		.map(|i| ([&root[..], &[i; 32]].concat(), ((10u128, Vec::<u8>::new()).encode(), 1)))
+		.collect()
+}
+
+/// Key of the root of the child trie of the crowdloan in the main trie.
+fn crowdloan_root() -> Vec<u8> {
+	[child::PREFIX, &blake2_256(&(*b"crowdloan", 0u32).encode())].concat()
+}
+
+/// Write the metadata and snapshot of the fixture to `<network>.meta` and `<network>.snap` in
+/// `dir`, where an analysis of the network finds them.
+pub fn write(dir: &Path, network: &str) -> Result<()> {
+	fs::create_dir_all(dir)
+		.map_err(|e| PduError::io(format!("Failed to create {}", dir.display()), e))?;
+	let meta_path = dir.join(format!("{}.meta", network));
+	fs::write(&meta_path, runtime_metadata().encode())
+		.map_err(|e| PduError::io(format!("Failed to write {}", meta_path.display()), e))?;
+
+	// State version 1, like all current chains.
+	let mut snapshot = SnapshotWriter::create(dir.join(format!("{}.snap", network)), 1)?;
+	for entry in entries() {
+		snapshot.write(&entry)?;
+	}
+	snapshot.finish(&[])
+}
+
+fn runtime_metadata() -> RuntimeMetadataPrefixed {
+	use StorageHasher::*;
+
+	// Spec name, impl name, authoring version and spec version, see `Provenance`.
+	let version = (NETWORK, NETWORK, 1u32, 1u32).encode();
+	let pallets = vec![
+		pallet(
+			"System",
+			0,
+			vec![
+				map(
+					"Account",
+					vec![Blake2_128Concat],
+					meta_type::<AccountId32>(),
+					meta_type::<AccountInfo>(),
+				),
+				plain("Number", meta_type::<u32>()),
+				map("BlockHash", vec![Twox64Concat], meta_type::<u32>(), meta_type::<[u8; 32]>()),
+			],
+			vec![PalletConstantMetadata {
+				name: "Version",
+				ty: meta_type::<Vec<u8>>(),
+				value: version,
+				docs: vec![],
+			}],
+		),
+		pallet(
+			"Balances",
+			10,
+			vec![
+				plain("TotalIssuance", meta_type::<u128>()),
+				map(
+					"Locks",
+					vec![Blake2_128Concat],
+					meta_type::<AccountId32>(),
+					meta_type::<Vec<BalanceLock>>(),
+				),
+			],
+			vec![],
+		),
+		pallet(
+			"Staking",
+			20,
+			vec![
+				plain("CurrentEra", meta_type::<u32>()),
+				map(
+					"ErasStakersPaged",
+					vec![Twox64Concat, Twox64Concat, Twox64Concat],
+					meta_type::<(u32, AccountId32, u32)>(),
+					meta_type::<ExposurePage>(),
+				),
+				map(
+					"Ledger",
+					vec![Twox64Concat],
+					meta_type::<AccountId32>(),
+					meta_type::<Vec<u8>>(),
+				),
+			],
+			vec![],
+		),
+		pallet(
+			"Crowdloan",
+			30,
+			vec![map("Funds", vec![Twox64Concat], meta_type::<u32>(), meta_type::<FundInfo>())],
+			vec![],
+		),
+	];
+
+	let extrinsic = ExtrinsicMetadata {
+		version: 4,
+		address_ty: meta_type::<()>(),
+		call_ty: meta_type::<RuntimeCall>(),
+		signature_ty: meta_type::<()>(),
+		extra_ty: meta_type::<()>(),
+		signed_extensions: vec![],
+	};
+	let outer_enums = OuterEnums {
+		call_enum_ty: meta_type::<RuntimeCall>(),
+		event_enum_ty: meta_type::<RuntimeCall>(),
+		error_enum_ty: meta_type::<RuntimeCall>(),
+	};
+	let custom = CustomMetadata { map: Default::default() };
+
+	RuntimeMetadataV15::new(pallets, extrinsic, meta_type::<Runtime>(), vec![], outer_enums, custom)
+		.into()
+}
+
+fn pallet(
+	name: &'static str,
+	index: u8,
+	entries: Vec<StorageEntryMetadata>,
+	constants: Vec<PalletConstantMetadata>,
+) -> PalletMetadata {
+	PalletMetadata {
+		name,
+		storage: Some(PalletStorageMetadata { prefix: name, entries }),
+		calls: None,
+		event: None,
+		constants,
+		error: None,
+		index,
+		docs: vec![],
+	}
+}
+
+fn plain(name: &'static str, value: MetaType) -> StorageEntryMetadata {
+	StorageEntryMetadata {
+		name,
+		modifier: StorageEntryModifier::Optional,
+		ty: StorageEntryType::Plain(value),
+		default: vec![0],
+		docs: vec![],
+	}
+}
+
+fn map(
+	name: &'static str,
+	hashers: Vec<StorageHasher>,
+	key: MetaType,
+	value: MetaType,
+) -> StorageEntryMetadata {
+	StorageEntryMetadata {
+		name,
+		modifier: StorageEntryModifier::Optional,
+		ty: StorageEntryType::Map { hashers, key, value },
+		default: vec![0],
+		docs: vec![],
+	}
+}
+
+fn prefix(pallet: &str, item: &str) -> Vec<u8> {
+	[twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+}
+
+fn blake2_128_concat(prefix: Vec<u8>, key: &[u8]) -> Vec<u8> {
+	[prefix, blake2_128(key).to_vec(), key.to_vec()].concat()
+}
+
+fn twox_64_concat(prefix: Vec<u8>, key: &[u8]) -> Vec<u8> {
+	[prefix, twox_64(key).to_vec(), key.to_vec()].concat()
+}
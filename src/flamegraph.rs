@@ -0,0 +1,26 @@
+//! Print the results as folded stacks, one `network;pallet;item size` line per storage item.
+//!
+//! Flame graph tools like `inferno-flamegraph` or speedscope render them as an interactive view of
+//! the same hierarchy as the tree.
+
+use crate::{Args, PalletInfo};
+use std::collections::BTreeMap as Map;
+
+pub fn print(found_by_pallet: &Map<String, PalletInfo>, args: &Args) {
+	// Semicolons separate the frames and the last space separates the size.
+	let frame = |name: &str| name.replace([';', ' '], "_");
+	let network = frame(&args.network);
+
+	for pallet in found_by_pallet.values() {
+		for item in pallet.items.values() {
+			let size = if args.counts_only {
+				item.num_entries
+			} else {
+				args.view.size(item.key_len, item.value_len)
+			};
+			if size > 0 {
+				println!("{};{};{} {}", network, frame(&pallet.name), frame(&item.name), size);
+			}
+		}
+	}
+}
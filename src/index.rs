@@ -0,0 +1,208 @@
+//! Index file that caches the analysis of a snapshot and the offsets of its keys.
+//!
+//! It is stored next to the snapshot as `<snapshot>.idx` and only used as long as the size and
+//! modification time of the snapshot still match. The cached analysis is only used with the same
+//! metadata, types and view, while the offsets of the keys do not depend on them. With the offsets
+//! and the sizes of the pallets, an analysis reads the largest pallets first.
+
+use crate::{ItemInfo, PalletInfo, View};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use parity_scale_codec::{Decode, Encode};
+use polkadot_du::{
+	snapshot::{Input, KeyOffsets, SnapshotReader},
+	ValueSizes,
+};
+use sp_crypto_hashing::blake2_256;
+use std::{cmp::Reverse, collections::BTreeMap as Map, fs, time::UNIX_EPOCH};
+use subxt::Metadata;
+
+const MAGIC: &[u8; 7] = b"pdu-idx";
+const VERSION: u8 = 1;
+
+/// Size, modification time in seconds and its nanoseconds of the snapshot that an index was built
+/// from.
+type Fingerprint = (u64, u64, u32);
+
+/// An encoded `ItemInfo`: name, key length, value length, entries, sum of squared sizes, empty and
+/// zeroed values and the value sizes.
+type IndexedItem = (String, u64, u64, u64, u128, u64, u64, IndexedSizes);
+
+/// An encoded `ValueSizes`: minimum, maximum, sum and the count per bucket.
+type IndexedSizes = (u64, u64, u64, Vec<(u32, u64)>);
+
+/// The cached analysis of a snapshot and its block number, if known.
+pub type Indexed = (Map<String, PalletInfo>, Option<u128>);
+
+/// The index of a snapshot.
+pub struct Index {
+	/// Offsets of the keys of the snapshot.
+	pub offsets: KeyOffsets,
+	/// Hash of what the analysis depends on besides the snapshot, see [`inputs`].
+	inputs: [u8; 32],
+	analysis: Indexed,
+}
+
+impl Index {
+	/// The cached analysis, if it was done with the same `inputs`.
+	pub fn analysis(&self, inputs: [u8; 32]) -> Option<Indexed> {
+		if self.inputs != inputs {
+			log::info!("Ignoring the analysis in the index, which used other metadata or options");
+			return None
+		}
+		Some(self.analysis.clone())
+	}
+
+	/// The pallets of the cached analysis, largest first.
+	///
+	/// Their order hardly depends on the metadata or options, so it is used even if they changed.
+	pub fn largest_first(&self) -> Vec<String> {
+		let pallets = self.analysis.0.values().sorted_by_key(|pallet| Reverse(pallet.size));
+		pallets.map(|pallet| pallet.name.clone()).collect()
+	}
+}
+
+/// Hash of the metadata, the source of the `--types` and the view of an analysis.
+pub fn inputs(meta: &Metadata, types: Option<&str>, view: View) -> Result<[u8; 32]> {
+	let types = types.map(fs::read).transpose()?;
+	Ok(blake2_256(&(meta.encode(), types, view as u8).encode()))
+}
+
+/// Path of the index that belongs to a snapshot.
+pub fn path(snapshot: &str) -> String {
+	format!("{}.idx", snapshot)
+}
+
+/// Load the index of a snapshot.
+///
+/// Returns `None` if there is no index or it is outdated.
+pub fn load(snapshot: &str) -> Result<Option<Index>> {
+	let Ok(bytes) = fs::read(path(snapshot)) else { return Ok(None) };
+	let input = &mut &bytes[..];
+
+	if <[u8; 7]>::decode(input)? != *MAGIC || u8::decode(input)? != VERSION {
+		return Err(anyhow!("{} is not a PDU index of a supported version", path(snapshot)))
+	}
+	if Fingerprint::decode(input)? != fingerprint(snapshot)? {
+		log::info!("Ignoring outdated index {}", path(snapshot));
+		return Ok(None)
+	}
+
+	let offsets = KeyOffsets::decode(input)?;
+	let inputs = <[u8; 32]>::decode(input)?;
+	let block = Option::<u128>::decode(input)?;
+	let pallets = Vec::<(String, Vec<IndexedItem>)>::decode(input)?;
+	let found_by_pallet = pallets
+		.into_iter()
+		.map(|(name, items)| {
+			let items = items
+				.into_iter()
+				.map(|(name, key_len, value_len, num_entries, size_sq, empty, zero, sizes)| {
+					let (min, max, sum, buckets) = sizes;
+					let buckets = buckets
+						.into_iter()
+						.map(|(bucket, count)| (bucket, count as usize))
+						.collect::<Map<_, _>>();
+					let value_sizes = ValueSizes {
+						min: min as usize,
+						max: max as usize,
+						sum: sum as usize,
+						count: buckets.values().sum(),
+						buckets,
+					};
+					let item = ItemInfo {
+						name: name.clone(),
+						key_len: key_len as usize,
+						value_len: value_len as usize,
+						num_entries: num_entries as usize,
+						size_sq,
+						empty_values: empty as usize,
+						zero_values: zero as usize,
+						value_sizes,
+					};
+					(name, item)
+				})
+				.collect::<Map<_, _>>();
+			let size = items.values().map(|i| i.key_len + i.value_len).sum();
+
+			(name.clone(), PalletInfo { name, size, items })
+		})
+		.collect();
+
+	log::info!("Loaded index {}", path(snapshot));
+	Ok(Some(Index { offsets, inputs, analysis: (found_by_pallet, block) }))
+}
+
+/// Open a snapshot to read the entries from `start` on and before `end`, decrypting it with the
+/// `identity` if needed.
+///
+/// Seeks to the start if the snapshot has an index, instead of decoding the entries before it.
+pub fn open_range(
+	snapshot: &str,
+	identity: Option<&str>,
+	start: Vec<u8>,
+	end: Option<Vec<u8>>,
+) -> Result<SnapshotReader<Input>> {
+	let reader = SnapshotReader::open_encrypted(snapshot, identity)?;
+	let reader = match load(snapshot)? {
+		Some(index) => reader.seek(&index.offsets, &start)?,
+		None => reader,
+	};
+	Ok(reader.range(Some(start), end))
+}
+
+/// Write the analysis of a snapshot and the offsets of its keys to its index.
+pub fn store(
+	snapshot: &str,
+	offsets: &KeyOffsets,
+	inputs: [u8; 32],
+	found_by_pallet: &Map<String, PalletInfo>,
+	block: Option<u128>,
+) -> Result<()> {
+	let pallets = found_by_pallet
+		.values()
+		.map(|pallet| {
+			let items = pallet
+				.items
+				.values()
+				.map(|i| {
+					(
+						i.name.clone(),
+						i.key_len as u64,
+						i.value_len as u64,
+						i.num_entries as u64,
+						i.size_sq,
+						i.empty_values as u64,
+						i.zero_values as u64,
+						(
+							i.value_sizes.min as u64,
+							i.value_sizes.max as u64,
+							i.value_sizes.sum as u64,
+							i.value_sizes.buckets.iter().map(|(b, c)| (*b, *c as u64)).collect(),
+						),
+					)
+				})
+				.collect::<Vec<IndexedItem>>();
+			(pallet.name.clone(), items)
+		})
+		.collect::<Vec<_>>();
+
+	let mut bytes = MAGIC.to_vec();
+	VERSION.encode_to(&mut bytes);
+	fingerprint(snapshot)?.encode_to(&mut bytes);
+	offsets.encode_to(&mut bytes);
+	inputs.encode_to(&mut bytes);
+	block.encode_to(&mut bytes);
+	pallets.encode_to(&mut bytes);
+	fs::write(path(snapshot), bytes)?;
+
+	log::info!("Index written to {}", path(snapshot));
+	Ok(())
+}
+
+fn fingerprint(snapshot: &str) -> Result<Fingerprint> {
+	let meta = fs::metadata(snapshot)?;
+	let modified = meta.modified()?.duration_since(UNIX_EPOCH)?;
+
+	Ok((meta.len(), modified.as_secs(), modified.subsec_nanos()))
+}
@@ -0,0 +1,304 @@
+//! Storage sizes of pallets and their items, by the prefixes of the keys.
+//!
+//! ```no_run
+//! # async fn example(metadata: subxt::Metadata) -> Result<(), polkadot_du::PduError> {
+//! let found_by_pallet = polkadot_du::info::analyze_snapshot("polkadot.snap", &metadata).await?;
+//! for pallet in found_by_pallet.values() {
+//!     println!("{}: {} bytes", pallet.name, pallet.size);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+	child::{self, CHILD_TRIES},
+	error::Result,
+	snapshot::SnapshotReader,
+	source::KvSource,
+};
+use clap::ValueEnum;
+use futures::StreamExt;
+use sp_crypto_hashing::twox_128;
+use std::collections::BTreeMap as Map;
+use subxt::Metadata;
+use subxt_metadata::{PalletMetadata, StorageEntryMetadata};
+
+/// Name of the pallet or storage item of keys that are not in the metadata.
+pub const UNKNOWN: &str = "Unknown";
+
+/// Pallets and storage items by the `twox_128` hashes of their names.
+///
+/// Pallets have a 16 byte prefix and no storage metadata, items a 32 byte prefix.
+pub type PrefixMap = Map<Vec<u8>, (String, Option<StorageEntryMetadata>)>;
+
+/// Which part of the storage entries the reported sizes refer to.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum View {
+	/// Only the key bytes, eg. to investigate the overhead of hashers.
+	Keys,
+	/// Only the value bytes.
+	Values,
+	/// Key and value bytes together.
+	Both,
+}
+
+impl View {
+	pub fn size(self, key_len: usize, value_len: usize) -> usize {
+		match self {
+			View::Keys => key_len,
+			View::Values => value_len,
+			View::Both => key_len + value_len,
+		}
+	}
+}
+
+/// Storage size information of a pallet.
+#[derive(Clone)]
+pub struct PalletInfo {
+	/// Name of the pallet.
+	pub name: String,
+	pub size: usize,
+	/// The storage items of the pallet.
+	pub items: Map<String, ItemInfo>,
+}
+
+/// Storage size information of a storage item inside a pallet.
+#[derive(Clone, Default)]
+pub struct ItemInfo {
+	pub name: String,
+	pub key_len: usize,
+	pub value_len: usize,
+	pub num_entries: usize,
+	/// Sum of the squared entry sizes as selected by the [`View`].
+	///
+	/// Used to estimate the error of sampled runs.
+	pub size_sq: u128,
+	/// Number of entries with an empty value.
+	pub empty_values: usize,
+	/// Number of entries with a non-empty value that consists only of zero bytes.
+	///
+	/// These are often default values that should have been removed instead.
+	pub zero_values: usize,
+	/// Distribution of the value sizes, which averages hide.
+	pub value_sizes: ValueSizes,
+}
+
+/// Distribution of the value sizes of a storage item, to spot outliers like unbounded vectors.
+///
+/// Sizes are counted in buckets that are exact below 64 bytes and within 1/32 of the size above,
+/// so that the buckets of an item stay few no matter how many entries it has.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct ValueSizes {
+	pub min: usize,
+	pub max: usize,
+	pub sum: usize,
+	pub count: usize,
+	/// Number of values by their bucket, see [`ValueSizes::bucket`].
+	pub buckets: Map<u32, usize>,
+}
+
+impl ValueSizes {
+	pub fn record(&mut self, size: usize) {
+		self.min = if self.count == 0 { size } else { self.min.min(size) };
+		self.max = self.max.max(size);
+		self.sum += size;
+		self.count += 1;
+		*self.buckets.entry(Self::bucket(size)).or_default() += 1;
+	}
+
+	pub fn merge(&mut self, other: ValueSizes) {
+		if other.count == 0 {
+			return
+		}
+		self.min = if self.count == 0 { other.min } else { self.min.min(other.min) };
+		self.max = self.max.max(other.max);
+		self.sum += other.sum;
+		self.count += other.count;
+		for (bucket, count) in other.buckets {
+			*self.buckets.entry(bucket).or_default() += count;
+		}
+	}
+
+	pub fn mean(&self) -> Option<usize> {
+		(self.count > 0).then(|| self.sum / self.count)
+	}
+
+	/// The size that the share `q` of the values does not exceed, eg. `0.99` for the p99.
+	///
+	/// Rounded up to the end of its bucket, but never above the largest value.
+	pub fn quantile(&self, q: f64) -> Option<usize> {
+		let rank = ((q * self.count as f64).ceil() as usize).max(1);
+		let mut seen = 0;
+		self.buckets.iter().find_map(|(bucket, count)| {
+			seen += count;
+			(seen >= rank).then(|| Self::bounds(*bucket).1.min(self.max))
+		})
+	}
+
+	/// The bucket of a size, as the shift and the six leading bits of the size.
+	pub fn bucket(size: usize) -> u32 {
+		let shift = (usize::BITS - size.leading_zeros()).saturating_sub(6);
+		(shift << 6) | (size >> shift) as u32
+	}
+
+	/// The smallest and largest size of a bucket.
+	pub fn bounds(bucket: u32) -> (usize, usize) {
+		let (shift, leading) = (bucket >> 6, (bucket & 63) as usize);
+		(leading << shift, ((leading + 1) << shift) - 1)
+	}
+}
+
+pub enum CategorizedKey {
+	/// A key that belongs to a storage item inside a pallet.
+	Item(String, StorageEntryMetadata),
+	/// A key that belongs to a pallet but an unknown storage item.
+	Pallet(String),
+	/// A key that does not belong to any known pallet.
+	Unknown,
+	/// A key of a child trie or of its root, with the pallet that owns the trie if it is known.
+	Child { pallet: String, trie: String },
+}
+
+impl From<(String, Option<StorageEntryMetadata>)> for CategorizedKey {
+	fn from((pallet, storage): (String, Option<StorageEntryMetadata>)) -> Self {
+		if let Some(storage) = storage {
+			CategorizedKey::Item(pallet, storage)
+		} else {
+			CategorizedKey::Pallet(pallet)
+		}
+	}
+}
+
+/// Analyze the storage sizes of a snapshot file, see [`SnapshotReader::open`] for the paths.
+pub async fn analyze_snapshot(path: &str, metadata: &Metadata) -> Result<Map<String, PalletInfo>> {
+	analyze(SnapshotReader::open(path)?, metadata).await
+}
+
+/// Analyze the storage sizes of the entries of a source by their pallet and item.
+///
+/// Keys that are not in the metadata are accounted to [`UNKNOWN`], the entries of child tries to
+/// one item per trie in [`CHILD_TRIES`].
+pub async fn analyze(
+	source: impl KvSource,
+	metadata: &Metadata,
+) -> Result<Map<String, PalletInfo>> {
+	let pallets = metadata.pallets().collect::<Vec<_>>();
+	let lookup = build_prefix_lookup(&pallets);
+	let mut found_by_pallet = Map::new();
+
+	let mut entries = std::pin::pin!(source.stream());
+	while let Some(entry) = entries.next().await {
+		let (key, (value, _)) = entry?;
+		let (pallet, item) = match categorize_prefix(&key, &lookup) {
+			CategorizedKey::Item(pallet, item) => (pallet, item.name().to_string()),
+			CategorizedKey::Pallet(pallet) => (pallet, UNKNOWN.into()),
+			CategorizedKey::Unknown => (UNKNOWN.into(), UNKNOWN.into()),
+			CategorizedKey::Child { trie, .. } => (CHILD_TRIES.into(), trie),
+		};
+		record_entry(&mut found_by_pallet, pallet, item, &key, &value, View::Both);
+	}
+
+	Ok(found_by_pallet)
+}
+
+pub fn build_prefix_lookup(pallets: &[PalletMetadata]) -> PrefixMap {
+	let mut prefix_lookup = PrefixMap::new();
+
+	for pallet in pallets {
+		let pallet_hash = twox_128(pallet.name().as_bytes());
+		prefix_lookup.insert(pallet_hash.into(), (pallet.name().into(), None));
+
+		if let Some(storage) = pallet.storage() {
+			for entry in storage.entries() {
+				let entry_hash = twox_128(entry.name().as_bytes());
+				let full_hash = [pallet_hash, entry_hash].concat();
+				prefix_lookup.insert(full_hash, (pallet.name().into(), Some(entry.clone())));
+			}
+		}
+	}
+
+	prefix_lookup
+}
+
+pub fn categorize_prefix(key: &[u8], lookup: &PrefixMap) -> CategorizedKey {
+	if let Some((id, _)) = child::split_key(key) {
+		let is_present = |pallet: &str| lookup.contains_key(&twox_128(pallet.as_bytes())[..]);
+		let pallet = child::owner(is_present).unwrap_or(UNKNOWN);
+		let trie = format!("{} 0x{}", pallet, hex::encode(id));
+		return CategorizedKey::Child { pallet: pallet.into(), trie }
+	}
+	if key.len() >= 32 {
+		let prefix = &key[0..32];
+
+		if let Some((pallet, storage)) = lookup.get(prefix) {
+			return (pallet.clone(), storage.clone()).into();
+		}
+	}
+	if key.len() >= 16 {
+		let prefix = &key[0..16];
+
+		if let Some((pallet, storage)) = lookup.get(prefix) {
+			return (pallet.clone(), storage.clone()).into();
+		}
+	}
+	CategorizedKey::Unknown
+}
+
+/// Account a single Key-Value pair to its pallet and storage item.
+pub fn record_entry(
+	found_by_pallet: &mut Map<String, PalletInfo>,
+	pallet: String,
+	item: String,
+	key: &[u8],
+	value: &[u8],
+	view: View,
+) {
+	let pallet_info = found_by_pallet.entry(pallet.clone()).or_insert_with(|| PalletInfo {
+		name: pallet,
+		size: 0,
+		items: Map::new(),
+	});
+	let item_info = pallet_info
+		.items
+		.entry(item.clone())
+		.or_insert_with(|| ItemInfo { name: item, ..Default::default() });
+
+	let size = key.len() + value.len();
+	item_info.key_len += key.len();
+	item_info.value_len += value.len();
+	item_info.num_entries += 1;
+	item_info.value_sizes.record(value.len());
+	item_info.size_sq += (view.size(key.len(), value.len()) as u128).pow(2);
+	if value.is_empty() {
+		item_info.empty_values += 1;
+	} else if value.iter().all(|b| *b == 0) {
+		item_info.zero_values += 1;
+	}
+
+	pallet_info.size += size;
+}
+
+/// Add the sizes that were found in another part of the snapshot.
+pub fn merge_found(found_by_pallet: &mut Map<String, PalletInfo>, other: Map<String, PalletInfo>) {
+	for (pallet, pallet_info) in other {
+		let Some(existing) = found_by_pallet.get_mut(&pallet) else {
+			found_by_pallet.insert(pallet, pallet_info);
+			continue
+		};
+		existing.size += pallet_info.size;
+
+		for (item_name, item_info) in pallet_info.items {
+			let Some(existing_item) = existing.items.get_mut(&item_name) else {
+				existing.items.insert(item_name, item_info);
+				continue
+			};
+			existing_item.key_len += item_info.key_len;
+			existing_item.value_len += item_info.value_len;
+			existing_item.num_entries += item_info.num_entries;
+			existing_item.size_sq += item_info.size_sq;
+			existing_item.empty_values += item_info.empty_values;
+			existing_item.zero_values += item_info.zero_values;
+			existing_item.value_sizes.merge(item_info.value_sizes);
+		}
+	}
+}
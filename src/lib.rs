@@ -0,0 +1,19 @@
+//! Library part of PDU to process snapshots of Substrate chains from Rust code.
+
+pub mod child;
+pub mod csv;
+pub mod encryption;
+pub mod error;
+pub mod fixture;
+pub mod info;
+pub mod live;
+pub mod process;
+pub mod progress;
+pub mod remote;
+pub mod rpc;
+pub mod snapshot;
+pub mod source;
+
+pub use error::PduError;
+pub use info::{analyze_snapshot, ItemInfo, PalletInfo, PrefixMap, ValueSizes};
+pub use snapshot::SnapshotReader;
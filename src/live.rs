@@ -0,0 +1,230 @@
+//! The state of a block read from an archive node over RPC, for chains that are small enough to
+//! skip creating a snapshot.
+//!
+//! Keys are listed with `state_getKeysPaged` and their values fetched page by page with
+//! `state_queryStorageAt`. Reference counts are not available over RPC, so every entry has a count
+//! of one. The entries of child tries can be fetched with the `childstate` methods for an analysis,
+//! see [`LiveReader::child_tries`].
+
+use crate::{
+	child,
+	error::{PduError, Result},
+	rpc::RpcOptions,
+	snapshot::SnapshotEntry,
+	source::KvSource,
+};
+use futures::{stream, Stream};
+use parity_scale_codec::Encode;
+use subxt::{
+	backend::{
+		legacy::{rpc_methods::Bytes, LegacyRpcMethods},
+		rpc::{rpc_params, RpcClient},
+	},
+	utils::H256,
+	SubstrateConfig,
+};
+use tokio::sync::mpsc::channel;
+
+/// Number of keys per request, which is the most that nodes allow for `state_getKeysPaged`.
+const PAGE_SIZE: u32 = 1000;
+
+/// Reads the entries of a block page by page.
+#[derive(Clone)]
+pub struct LiveReader {
+	/// For the `childstate` methods, which have no typed wrappers.
+	client: RpcClient,
+	rpc: LegacyRpcMethods<SubstrateConfig>,
+	url: String,
+	at: H256,
+	/// Entries with keys before this are skipped.
+	start_key: Option<Vec<u8>>,
+	/// Entries with keys from this on are skipped.
+	end_key: Option<Vec<u8>>,
+	/// Whether to fetch the entries of the child tries.
+	child_tries: bool,
+}
+
+impl LiveReader {
+	/// Connect to a node to read the state of block `at`, by default of its finalized head.
+	pub async fn connect(url: &str, at: Option<H256>) -> Result<Self> {
+		Self::connect_with(url, at, &RpcOptions::default()).await
+	}
+
+	/// Like [`Self::connect`], but eg. through a proxy.
+	pub async fn connect_with(url: &str, at: Option<H256>, options: &RpcOptions) -> Result<Self> {
+		let client = options.connect(url).await?;
+		let rpc = LegacyRpcMethods::new(client.clone());
+		let at = match at {
+			Some(at) => at,
+			None => rpc.chain_get_finalized_head().await.map_err(|e| rpc_error(url, e))?,
+		};
+		log::info!("Reading the state of block {:?} from {}", at, url);
+
+		Ok(Self {
+			client,
+			rpc,
+			url: url.into(),
+			at,
+			start_key: None,
+			end_key: None,
+			child_tries: false,
+		})
+	}
+
+	/// Only read the entries with keys from `start` on and before `end`.
+	pub fn range(mut self, start: Option<Vec<u8>>, end: Option<Vec<u8>>) -> Self {
+		self.start_key = start;
+		self.end_key = end;
+		self
+	}
+
+	/// Also fetch the entries of the child tries, right after their root, see [`child`].
+	///
+	/// Their keys are the key of the root followed by the key within the trie, which is no key of
+	/// the main trie. So they are only for analysis and must not be written to snapshots.
+	pub fn child_tries(mut self, fetch: bool) -> Self {
+		self.child_tries = fetch;
+		self
+	}
+
+	/// Hash of the block whose state is read.
+	pub fn at(&self) -> H256 {
+		self.at
+	}
+
+	/// State version of the runtime of the block, as needed for a snapshot.
+	pub async fn state_version(&self) -> Result<u8> {
+		let version = self
+			.rpc
+			.state_get_runtime_version(Some(self.at))
+			.await
+			.map_err(|e| rpc_error(&self.url, e))?;
+		// Runtimes from before state version 1 do not report one.
+		Ok(version.other.get("stateVersion").and_then(|v| v.as_u64()).unwrap_or(0) as u8)
+	}
+
+	/// The storage root and header of the block, which follow the entries in a snapshot.
+	pub async fn trailer(&self) -> Result<Vec<u8>> {
+		let header = self
+			.rpc
+			.chain_get_header(Some(self.at))
+			.await
+			.map_err(|e| rpc_error(&self.url, e))?
+			.ok_or_else(|| PduError::Rpc(format!("{} has no block {:?}", self.url, self.at)))?;
+
+		Ok((header.state_root, header).encode())
+	}
+
+	/// Fetch the entries of the page of keys after `after`, or of the first page.
+	///
+	/// Returns them sorted by key, together with the key to continue after if there are more.
+	async fn page(&self, after: Option<&[u8]>) -> Result<(Vec<SnapshotEntry>, Option<Vec<u8>>)> {
+		let start = after.or(self.start_key.as_deref());
+		let mut keys = self
+			.rpc
+			.state_get_keys_paged(&[], PAGE_SIZE, start, Some(self.at))
+			.await
+			.map_err(|e| rpc_error(&self.url, e))?;
+
+		let in_range = |key: &Vec<u8>| self.end_key.as_ref().is_none_or(|end| key < end);
+		let next = keys.last().filter(|_| keys.len() == PAGE_SIZE as usize).cloned();
+		let next = next.filter(in_range);
+		keys.retain(in_range);
+		// Keys are listed after the start key, so the start key itself is missing.
+		if let (None, Some(start)) = (after, &self.start_key) {
+			keys.insert(0, start.clone());
+		}
+		if keys.is_empty() {
+			return Ok((Vec::new(), next))
+		}
+
+		let changes = self
+			.rpc
+			.state_query_storage_at(keys.iter().map(Vec::as_slice), Some(self.at))
+			.await
+			.map_err(|e| rpc_error(&self.url, e))?;
+		let mut entries = changes
+			.into_iter()
+			.flat_map(|set| set.changes)
+			.filter_map(|(key, value)| Some((key.0, (value?.0, 1))))
+			.collect::<Vec<_>>();
+		let roots = keys.iter().filter(|key| self.child_tries && key.starts_with(child::PREFIX));
+		for root in roots {
+			entries.extend(self.child_entries(root).await?);
+		}
+		entries.sort();
+
+		Ok((entries, next))
+	}
+
+	/// Fetch the entries of the child trie whose root is stored under the key `root`.
+	async fn child_entries(&self, root: &[u8]) -> Result<Vec<SnapshotEntry>> {
+		let (child_key, prefix) = (Bytes(root.into()), Bytes(Vec::new()));
+		let mut entries = Vec::new();
+		let mut start = None;
+		loop {
+			let params = rpc_params![&child_key, &prefix, PAGE_SIZE, start, self.at];
+			let keys: Vec<Bytes> = self
+				.client
+				.request("childstate_getKeysPaged", params)
+				.await
+				.map_err(|e| rpc_error(&self.url, e))?;
+			let params = rpc_params![&child_key, &keys, self.at];
+			let values: Vec<Option<Bytes>> = self
+				.client
+				.request("childstate_getStorageEntries", params)
+				.await
+				.map_err(|e| rpc_error(&self.url, e))?;
+
+			let more = keys.len() == PAGE_SIZE as usize;
+			start = keys.last().cloned();
+			for (key, value) in keys.into_iter().zip(values) {
+				if let Some(value) = value {
+					entries.push(([root, &key].concat(), (value.0, 1)));
+				}
+			}
+			if !more {
+				return Ok(entries)
+			}
+		}
+	}
+}
+
+impl KvSource for LiveReader {
+	/// The number of keys is only known after listing all of them.
+	fn len_hint(&self) -> Option<usize> {
+		None
+	}
+
+	fn stream(self) -> impl Stream<Item = Result<SnapshotEntry>> + Send + 'static {
+		// Fetch the next page while the current one is processed.
+		let (tx, mut rx) = channel(PAGE_SIZE as usize);
+		tokio::spawn(async move {
+			let mut after = None;
+			loop {
+				let (entries, next) = match self.page(after.as_deref()).await {
+					Ok(page) => page,
+					Err(e) => {
+						let _ = tx.send(Err(e)).await;
+						return
+					},
+				};
+				for entry in entries {
+					if tx.send(Ok(entry)).await.is_err() {
+						return
+					}
+				}
+				match next {
+					Some(next) => after = Some(next),
+					None => return,
+				}
+			}
+		});
+
+		stream::poll_fn(move |cx| rx.poll_recv(cx))
+	}
+}
+
+fn rpc_error(url: &str, e: subxt::Error) -> PduError {
+	PduError::Rpc(format!("Failed to read the state from {}: {}", url, e))
+}
@@ -0,0 +1,62 @@
+//! Memory usage of a run, to estimate whether a bigger snapshot fits into memory.
+//!
+//! The peak resident set size comes from the OS. Allocation stats need the `jemalloc` feature,
+//! which makes jemalloc the global allocator.
+
+use crate::{fmt_bytes, Units};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static ALLOCATOR: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+pub fn print(units: Units) {
+	println!("Memory:");
+	match peak_rss() {
+		Some(peak) => println!("  peak RSS:  {}", fmt_bytes(peak, false, units)),
+		None => println!("  peak RSS:  unknown on this platform"),
+	}
+
+	print_allocations(units);
+}
+
+/// Peak resident set size of the process in bytes.
+#[cfg(unix)]
+fn peak_rss() -> Option<usize> {
+	let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+	// SAFETY: `getrusage` initializes the struct if it succeeds.
+	if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+		return None
+	}
+	let max_rss = unsafe { usage.assume_init() }.ru_maxrss as usize;
+
+	// macOS reports bytes, everything else kilobytes.
+	Some(if cfg!(target_os = "macos") { max_rss } else { max_rss * 1024 })
+}
+
+#[cfg(not(unix))]
+fn peak_rss() -> Option<usize> {
+	None
+}
+
+#[cfg(feature = "jemalloc")]
+fn print_allocations(units: Units) {
+	use tikv_jemalloc_ctl::{epoch, stats};
+
+	// The stats are cached until the epoch advances.
+	let stats = epoch::advance().and_then(|_| {
+		Ok((stats::allocated::read()?, stats::active::read()?, stats::resident::read()?))
+	});
+	match stats {
+		Ok((allocated, active, resident)) => {
+			println!("  allocated: {}", fmt_bytes(allocated, false, units));
+			println!("  active:    {}", fmt_bytes(active, false, units));
+			println!("  resident:  {}", fmt_bytes(resident, false, units));
+		},
+		Err(e) => log::warn!("Failed to read the jemalloc stats: {}", e),
+	}
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn print_allocations(_units: Units) {
+	println!("  allocation stats need the `jemalloc` feature");
+}
@@ -0,0 +1,124 @@
+//! Merge multiple snapshots into one, eg. per-pallet extracts into the state of a test network.
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use itertools::Itertools;
+use parity_scale_codec::Decode;
+use polkadot_du::snapshot::{SnapshotReader, SnapshotWriter};
+use sp_crypto_hashing::{blake2_128, twox_128};
+use std::cmp::Reverse;
+
+/// What to do with a key that is in multiple snapshots.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Conflict {
+	/// Take the value from the snapshot at the highest block, or the last given one on a tie.
+	PreferNewer,
+	/// Fail if the values differ. Equal values are no conflict.
+	ErrorOnConflict,
+}
+
+/// Merge the snapshots at `inputs` into a snapshot at `out`, sorted by key like any snapshot.
+///
+/// The storage root and header are taken from the newest snapshot, so the root does not match the
+/// merged entries.
+pub fn run(inputs: &[String], out: &str, conflict: Conflict) -> Result<()> {
+	let mut blocks = Vec::new();
+	for input in inputs {
+		let block = block_number(input)?;
+		log::info!("Snapshot {} is at block {:?}", input, block);
+		blocks.push(block);
+	}
+	// Newest first, so that its entries win. The sort is stable, so later inputs win on a tie.
+	let order = (0..inputs.len()).rev().sorted_by_key(|&i| Reverse(blocks[i])).collect::<Vec<_>>();
+
+	// All snapshots are sorted by key, so merging their heads keeps the merged one sorted.
+	let mut readers = Vec::new();
+	for &index in &order {
+		let reader = SnapshotReader::open(&inputs[index])?;
+		readers.push((index, reader));
+	}
+	let state_version = readers[0].1.state_version();
+	for (index, reader) in &readers {
+		if reader.state_version() != state_version {
+			log::warn!("Snapshot {} has state version {}", inputs[*index], reader.state_version());
+		}
+	}
+	let mut heads = Vec::new();
+	for (_, reader) in &mut readers {
+		heads.push(reader.next().transpose()?);
+	}
+
+	let mut writer = SnapshotWriter::create(out, state_version)?;
+	let (mut keys, mut conflicts) = (0, 0);
+	while let Some(next_key) = heads.iter().flatten().map(|(key, _)| key).min().cloned() {
+		// The input and value hash of the entry that was written for the key.
+		let mut written: Option<(usize, [u8; 16])> = None;
+
+		for ((index, reader), head) in readers.iter_mut().zip(&mut heads) {
+			if head.as_ref().is_none_or(|(key, _)| *key != next_key) {
+				continue
+			}
+			let next = reader.next().transpose()?;
+			let (key, (value, ref_count)) =
+				std::mem::replace(head, next).expect("Checked above; qed");
+			let hash = blake2_128(&value);
+
+			match written {
+				None => {
+					writer.write(&(key, (value, ref_count)))?;
+					written = Some((*index, hash));
+				},
+				Some((first, first_hash)) if first_hash != hash => {
+					if conflict == Conflict::ErrorOnConflict {
+						return Err(anyhow!(
+							"Key 0x{} has different values in {} and {}",
+							hex::encode(&key),
+							inputs[first],
+							inputs[*index]
+						))
+					}
+					conflicts += 1;
+				},
+				Some(_) => (),
+			}
+		}
+		keys += 1;
+	}
+
+	let mut trailer = None;
+	for (_, reader) in readers {
+		// The first one is the newest snapshot.
+		let input_trailer = reader.into_trailer()?;
+		trailer.get_or_insert(input_trailer);
+	}
+
+	writer.finish(&trailer.unwrap_or_default())?;
+	println!(
+		"Merged {} snapshots into {} with {} keys, {} conflicts resolved",
+		inputs.len(),
+		out,
+		keys,
+		conflicts
+	);
+	Ok(())
+}
+
+/// The block number of a snapshot as per `System::Number`.
+fn block_number(path: &str) -> Result<Option<u64>> {
+	let key = [twox_128(b"System"), twox_128(b"Number")].concat();
+
+	for entry in SnapshotReader::open(path)? {
+		let (k, (value, _)) = entry?;
+		if k != key {
+			continue
+		}
+		// The block number type depends on the runtime.
+		return Ok(match value.len() {
+			4 => u32::decode(&mut &value[..]).ok().map(Into::into),
+			8 => u64::decode(&mut &value[..]).ok(),
+			_ => None,
+		})
+	}
+
+	Ok(None)
+}
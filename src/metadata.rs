@@ -0,0 +1,156 @@
+//! Where the metadata of an analysis comes from.
+//!
+//! The sources are tried in order: the file given with `--metadata`, the cached `<network>.meta`,
+//! the runtime code `:code` in the snapshot and finally the node. Only the node needs the network,
+//! so snapshots of chains whose nodes are gone can still be analyzed.
+
+use crate::genesis_path;
+use anyhow::{anyhow, Context, Result};
+use parity_scale_codec::{Decode, Encode};
+use polkadot_du::{rpc::RpcOptions, snapshot::SnapshotReader};
+use std::{fmt, process::Command};
+use subxt::Metadata;
+
+/// Key of the runtime code in the state.
+const CODE: &[u8] = b":code";
+
+/// The sources to get the metadata from, by precedence.
+pub struct Sources<'a> {
+	/// Metadata that was explicitly asked for, which must load.
+	pub file: Option<&'a str>,
+	/// Metadata that was fetched from the node before.
+	pub cache: &'a str,
+	/// Snapshot whose runtime code the metadata is read from, with the identity to decrypt it.
+	pub snapshot: Option<(&'a str, Option<&'a str>)>,
+	pub url: &'a str,
+	pub rpc: &'a RpcOptions,
+}
+
+/// A source that the metadata was taken from, for the logs.
+enum Source<'a> {
+	File(&'a str),
+	Cache(&'a str),
+	Code(&'a str),
+	Rpc(&'a str),
+}
+
+impl fmt::Display for Source<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Source::File(path) => write!(f, "{}", path),
+			Source::Cache(path) => write!(f, "the cache {}", path),
+			Source::Code(path) => write!(f, "the runtime code in {}", path),
+			Source::Rpc(url) => write!(f, "the node at {}", url),
+		}
+	}
+}
+
+impl Sources<'_> {
+	/// Metadata from the cache or else the node, for commands without a snapshot.
+	pub fn cached<'a>(cache: &'a str, url: &'a str, rpc: &'a RpcOptions) -> Sources<'a> {
+		Sources { file: None, cache, snapshot: None, url, rpc }
+	}
+
+	/// Get the metadata from the first source that has it.
+	///
+	/// A missing or broken cache or runtime code falls through to the next source, since the node
+	/// can still provide the metadata. The explicit file cannot be replaced by anything else.
+	pub async fn resolve(&self) -> Result<Metadata> {
+		if let Some(path) = self.file {
+			let meta =
+				load(path)?.ok_or_else(|| anyhow!("Metadata file {} does not exist", path))?;
+			return Ok(used(Source::File(path), meta))
+		}
+
+		match load(self.cache) {
+			Ok(Some(meta)) => return Ok(used(Source::Cache(self.cache), meta)),
+			Ok(None) => log::debug!("No metadata cached in {}", self.cache),
+			Err(e) => log::warn!("Ignoring the cached metadata: {}", e),
+		}
+
+		if let Some((path, identity)) = self.snapshot {
+			match from_code(path, identity) {
+				Ok(meta) => return Ok(used(Source::Code(path), meta)),
+				Err(e) => log::warn!("Could not read the metadata from the snapshot: {:#}", e),
+			}
+		}
+
+		let meta = fetch(self.cache, self.url, self.rpc)
+			.await
+			.with_context(|| format!("Failed to fetch the metadata from {}", self.url))?;
+		Ok(used(Source::Rpc(self.url), meta))
+	}
+}
+
+fn used(source: Source, meta: Metadata) -> Metadata {
+	log::info!("Using the metadata from {}", source);
+	meta
+}
+
+/// Load SCALE or hex encoded metadata, or `None` if the file does not exist.
+fn load(path: &str) -> Result<Option<Metadata>> {
+	let mut bytes = match std::fs::read(path) {
+		Ok(bytes) => bytes,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+		Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path)),
+	};
+	// SCALE encoded metadata starts with `meta`, which is no hex.
+	let text = bytes.trim_ascii();
+	let hex = text.strip_prefix(b"0x").unwrap_or(text);
+	if !hex.is_empty() && hex.iter().all(u8::is_ascii_hexdigit) {
+		bytes = hex::decode(hex).with_context(|| format!("{} is invalid hex", path))?;
+	}
+	let meta = Metadata::decode(&mut bytes.as_slice())
+		.with_context(|| format!("{} is neither SCALE nor hex encoded metadata", path))?;
+	Ok(Some(meta))
+}
+
+/// Read the metadata out of the runtime code of a snapshot with the `subwasm` tool.
+///
+/// Running a runtime needs a full Wasm executor, which `subwasm` brings along.
+fn from_code(path: &str, identity: Option<&str>) -> Result<Metadata> {
+	if path == "-" {
+		return Err(anyhow!("a snapshot from stdin can only be read once"))
+	}
+	// The entries are sorted, so everything before the code is skipped without its value.
+	let end = [CODE, &[0]].concat();
+	let code = SnapshotReader::open_encrypted(path, identity)?
+		.range(Some(CODE.to_vec()), Some(end))
+		.find_map(|entry| match entry {
+			Ok((key, (value, _))) if key == CODE => Some(Ok(value)),
+			Ok(_) => None,
+			Err(e) => Some(Err(e)),
+		})
+		.ok_or_else(|| anyhow!("it has no runtime code"))??;
+
+	let wasm = std::env::temp_dir().join(format!("pdu-{}.wasm", std::process::id()));
+	std::fs::write(&wasm, &code).context("Failed to write the runtime code")?;
+	let output = Command::new("subwasm").args(["meta", "--format", "scale"]).arg(&wasm).output();
+	let _ = std::fs::remove_file(&wasm);
+
+	let output = output.context("Failed to run subwasm, is it installed?")?;
+	if !output.status.success() {
+		return Err(anyhow!(
+			"subwasm failed with {}: {}",
+			output.status,
+			String::from_utf8_lossy(&output.stderr).trim()
+		))
+	}
+	Metadata::decode(&mut output.stdout.as_slice()).context("subwasm returned invalid metadata")
+}
+
+/// Fetch the metadata from the node and cache it together with the genesis hash.
+async fn fetch(cache: &str, url: &str, rpc: &RpcOptions) -> Result<Metadata> {
+	let cl =
+		subxt::OnlineClient::<subxt::SubstrateConfig>::from_rpc_client(rpc.connect(url).await?)
+			.await?;
+	let meta = cl.metadata();
+
+	std::fs::write(cache, meta.encode())
+		.with_context(|| format!("Failed to cache the metadata in {}", cache))?;
+	// Kept next to the metadata to check that snapshots are of the same network.
+	std::fs::write(genesis_path(cache), cl.genesis_hash())?;
+	log::info!("Metadata written to {}", cache);
+
+	Ok(meta)
+}
@@ -0,0 +1,69 @@
+//! Write the results as an OpenMetrics text file, eg. for the textfile collector of
+//! node_exporter on machines that cannot be scraped.
+
+use crate::{unknown_size, Analysis, ItemInfo};
+use anyhow::Result;
+use std::{fmt::Write, fs};
+
+/// Name, help text and value of a gauge per storage item.
+type ItemGauge = (&'static str, &'static str, fn(&ItemInfo) -> usize);
+
+/// Write all gauges of the analysis of `network` to `path`.
+pub fn save(path: &str, analysis: &Analysis, network: &str) -> Result<()> {
+	let network = escape(network);
+	let mut out = String::new();
+
+	gauge(&mut out, "pdu_pallet_size_bytes", "Size of the keys and values of a pallet.");
+	for pallet in analysis.found_by_pallet.values() {
+		let labels = format!("network=\"{}\",pallet=\"{}\"", network, escape(&pallet.name));
+		writeln!(out, "pdu_pallet_size_bytes{{{}}} {}", labels, pallet.size)?;
+	}
+
+	let items =
+		analysis.found_by_pallet.values().flat_map(|p| p.items.values().map(move |i| (p, i)));
+	let item_gauges: [ItemGauge; 3] = [
+		("pdu_item_keys", "Number of keys of a storage item.", |i| i.num_entries),
+		("pdu_item_key_bytes", "Size of the keys of a storage item.", |i| i.key_len),
+		("pdu_item_value_bytes", "Size of the values of a storage item.", |i| i.value_len),
+	];
+	for (name, help, value) in item_gauges {
+		gauge(&mut out, name, help);
+		for (pallet, item) in items.clone() {
+			let labels = format!(
+				"network=\"{}\",pallet=\"{}\",item=\"{}\"",
+				network,
+				escape(&pallet.name),
+				escape(&item.name)
+			);
+			writeln!(out, "{}{{{}}} {}", name, labels, value(item))?;
+		}
+	}
+
+	let (pallets, items) = unknown_size(&analysis.found_by_pallet);
+	gauge(&mut out, "pdu_unknown_size_bytes", "Size of the keys that are not in the metadata.");
+	writeln!(out, "pdu_unknown_size_bytes{{network=\"{}\"}} {}", network, pallets + items)?;
+
+	if let Some(block) = analysis.provenance.block {
+		gauge(&mut out, "pdu_snapshot_block", "Block number of the analyzed snapshot.");
+		writeln!(out, "pdu_snapshot_block{{network=\"{}\"}} {}", network, block)?;
+	}
+	gauge(&mut out, "pdu_partial", "Whether the analysis stopped early.");
+	writeln!(out, "pdu_partial{{network=\"{}\"}} {}", network, analysis.stopped.is_some() as u8)?;
+	out.push_str("# EOF\n");
+
+	// Collectors may read the file at any time, so replace it at once.
+	let tmp = format!("{}.tmp", path);
+	fs::write(&tmp, out)?;
+	fs::rename(&tmp, path)?;
+	log::info!("Wrote the metrics to {}", path);
+	Ok(())
+}
+
+fn gauge(out: &mut String, name: &str, help: &str) {
+	out.push_str(&format!("# TYPE {} gauge\n# HELP {} {}\n", name, name, help));
+}
+
+/// Escape a label value.
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
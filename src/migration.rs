@@ -0,0 +1,120 @@
+//! Plan multi-block migrations that clean up storage items.
+//!
+//! A migration that removes or rewrites an item has to read all of its entries, which puts their
+//! keys and values into the proof of each block. Dividing the size of the item by the budget per
+//! block tells how many blocks the migration needs at least. The proof also contains the trie
+//! nodes above the entries, so the budget should leave some headroom.
+
+use crate::{fmt_bytes, index, next_prefix, Units};
+use anyhow::{anyhow, Result};
+use sp_crypto_hashing::twox_128;
+use subxt::Metadata;
+
+/// A storage item as `(pallet, item)`.
+pub type Item = (String, String);
+
+/// Parse a storage item of the form `Pallet::Item`.
+pub fn parse_item(s: &str) -> Result<Item, String> {
+	let (pallet, item) =
+		s.split_once("::").ok_or_else(|| format!("Item must be like Pallet::Item, not {}", s))?;
+
+	Ok((pallet.into(), item.into()))
+}
+
+/// Parse a size like `2MB`, `512KiB` or `1000`.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+	let s = s.trim();
+	let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+	let (number, unit) = s.split_at(split);
+	let number = number.parse::<f64>().map_err(|_| format!("{} is not a size", s))?;
+
+	let factor = match unit.trim().to_ascii_lowercase().as_str() {
+		"" | "b" => 1.0,
+		"kb" | "k" => 1e3,
+		"mb" | "m" => 1e6,
+		"gb" | "g" => 1e9,
+		"kib" => 1024.0,
+		"mib" => 1024.0 * 1024.0,
+		"gib" => 1024.0 * 1024.0 * 1024.0,
+		_ => return Err(format!("Unknown unit {}, use eg. KB, MB, KiB or MiB", unit)),
+	};
+	let size = (number * factor) as usize;
+	if size == 0 {
+		return Err("The size must not be zero".into())
+	}
+
+	Ok(size)
+}
+
+/// Print how many blocks a migration of each item needs with `per_block` bytes per block and at
+/// most `keys_per_block` entries per block.
+pub fn print(
+	snapshot: &str,
+	meta: &Metadata,
+	items: &[Item],
+	per_block: usize,
+	keys_per_block: Option<usize>,
+	units: Units,
+) -> Result<()> {
+	for (pallet, item) in items {
+		meta.pallet_by_name(pallet)
+			.and_then(|p| p.storage())
+			.and_then(|s| s.entry_by_name(item))
+			.ok_or_else(|| anyhow!("{}::{} is not in the metadata", pallet, item))?;
+	}
+
+	let keys_limit = keys_per_block.map_or("".into(), |keys| format!(" and {} keys", keys));
+	println!(
+		"Migration plan with {} per block{}:",
+		fmt_bytes(per_block, false, units),
+		keys_limit
+	);
+
+	let mut total_blocks = 0;
+	for (pallet, item) in items {
+		let prefix = [twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat();
+		let (keys, size, largest) = measure(snapshot, prefix)?;
+
+		// Every started block counts, and the keys limit may be tighter than the size.
+		let by_size = size.div_ceil(per_block);
+		let by_keys = keys_per_block.map_or(0, |limit| keys.div_ceil(limit.max(1)));
+		let blocks = by_size.max(by_keys);
+		total_blocks += blocks;
+
+		println!(
+			"  {}::{}: {} keys, {} => {} blocks",
+			pallet,
+			item,
+			keys,
+			fmt_bytes(size, false, units),
+			blocks
+		);
+		if largest > per_block {
+			println!(
+				"    The largest entry has {} and does not fit into a block",
+				fmt_bytes(largest, false, units)
+			);
+		}
+	}
+	if items.len() > 1 {
+		println!("  Total: {} blocks", total_blocks);
+	}
+
+	Ok(())
+}
+
+/// Number of entries with this prefix, their total size and the size of the largest one.
+fn measure(snapshot: &str, prefix: Vec<u8>) -> Result<(usize, usize, usize)> {
+	let end = next_prefix(&prefix);
+	let reader = index::open_range(snapshot, None, prefix, end)?;
+
+	let (mut keys, mut size, mut largest) = (0, 0, 0);
+	for entry in reader {
+		let (key, (value, _)) = entry?;
+		keys += 1;
+		size += key.len() + value.len();
+		largest = largest.max(key.len() + value.len());
+	}
+
+	Ok((keys, size, largest))
+}
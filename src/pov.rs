@@ -0,0 +1,269 @@
+//! Break down the size of a parachain PoV by the storage that its proof touches.
+//!
+//! The block data of a PoV consists of the header, the extrinsics and a compact storage proof. The
+//! compact proof lists the trie nodes in the order of a depth-first walk from the root, so the key
+//! under each node can be recovered and categorized like the keys of a snapshot.
+
+use crate::{
+	build_prefix_lookup, categorize_prefix, fmt_bytes, fmt_percent, CategorizedKey, Units,
+	CHILD_TRIES, UNKNOWN,
+};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use parity_scale_codec::{Compact, CompactLen, Decode};
+use std::{collections::BTreeMap as Map, fs};
+use subxt::Metadata;
+use termtree::Tree;
+
+/// Prefix of a node in a compact proof whose value follows as the next node.
+const ESCAPE_HEADER: u8 = 0x01;
+
+/// Magic prefix of a compressed PoV, see `sp-maybe-compressed-blob`.
+const ZSTD_PREFIX: [u8; 8] = [82, 188, 83, 118, 70, 219, 142, 5];
+
+/// Name of the proof nodes above the storage prefix of a pallet or item.
+const SHARED: &str = "Shared nodes";
+
+/// Size of the parts of a PoV in bytes.
+struct Breakdown {
+	number: u32,
+	header: usize,
+	extrinsics: Vec<usize>,
+	/// Number of nodes and their size per pallet and storage item.
+	proof: Map<String, Map<String, (usize, usize)>>,
+	/// Nodes of child tries, which follow after the main trie.
+	child_nodes: (usize, usize),
+}
+
+/// Print the size breakdown of a PoV as exported by a collator with `--export-pov-to-path`.
+pub fn print(path: &str, meta: &Metadata, units: Units) -> Result<()> {
+	let bytes = fs::read(path)?;
+	// The export contains the PoV followed by the parent header and relay parent.
+	let block_data = Vec::<u8>::decode(&mut &bytes[..])?;
+	if block_data.starts_with(&ZSTD_PREFIX) {
+		return Err(anyhow!("Compressed PoVs are not supported, please decompress it first"))
+	}
+	let breakdown = decode(&block_data, meta)?;
+
+	let extrinsics = breakdown.extrinsics.iter().sum::<usize>();
+	let proof = breakdown.proof.values().flat_map(|items| items.values()).collect::<Vec<_>>();
+	let proof_size = proof.iter().map(|(_, size)| size).sum::<usize>() + breakdown.child_nodes.1;
+	let num_nodes = proof.iter().map(|(nodes, _)| nodes).sum::<usize>() + breakdown.child_nodes.0;
+	let total = block_data.len();
+
+	let node = |size: usize, name: &str| {
+		format!("{} {} {}", fmt_bytes(size, true, units), fmt_percent(size, total), name)
+	};
+	let mut tree = Tree::new(format!(
+		"{} PoV of block #{}",
+		fmt_bytes(total, true, units),
+		breakdown.number
+	));
+	tree.push(node(breakdown.header, "Header"));
+	tree.push(node(extrinsics, &format!("Extrinsics ({})", breakdown.extrinsics.len())));
+
+	let mut proof_node =
+		Tree::new(node(proof_size, &format!("Storage proof ({} nodes)", num_nodes)));
+	let pallet_size =
+		|items: &Map<String, (usize, usize)>| items.values().map(|i| i.1).sum::<usize>();
+	for (pallet, items) in breakdown.proof.iter().sorted_by_key(|(_, i)| pallet_size(i)).rev() {
+		let mut pallet_node = Tree::new(node(pallet_size(items), pallet));
+
+		for (item, (nodes, size)) in items.iter().sorted_by_key(|(_, i)| i.1).rev() {
+			pallet_node.push(node(*size, &format!("{} ({} nodes)", item, nodes)));
+		}
+		proof_node.push(pallet_node);
+	}
+	if breakdown.child_nodes.0 > 0 {
+		let (nodes, size) = breakdown.child_nodes;
+		proof_node.push(node(size, &format!("Child tries ({} nodes)", nodes)));
+	}
+	tree.push(proof_node);
+
+	println!("{}", tree);
+	Ok(())
+}
+
+/// Decode the block data of a PoV and categorize its proof nodes.
+fn decode(block_data: &[u8], meta: &Metadata) -> Result<Breakdown> {
+	let input = &mut &block_data[..];
+
+	let header_len = input.len();
+	let number = decode_header(input)?;
+	let header = header_len - input.len();
+
+	let extrinsics = Vec::<Vec<u8>>::decode(input)?
+		.iter()
+		.map(|x| Compact::<u32>::compact_len(&(x.len() as u32)) + x.len())
+		.collect();
+	let nodes = Vec::<Vec<u8>>::decode(input)?;
+
+	let pallets = meta.pallets().collect::<Vec<_>>();
+	let lookup = build_prefix_lookup(&pallets);
+	let mut proof = Map::<String, Map<String, (usize, usize)>>::new();
+
+	let mut walker = Walker { nodes: &nodes, next: 0 };
+	if !nodes.is_empty() {
+		walker.walk(&mut Vec::new(), &mut |path, size, value| {
+			let key = path.chunks_exact(2).map(|n| n[0] << 4 | n[1]).collect::<Vec<_>>();
+			// Branches above the prefix of an item are shared by all of its keys.
+			let shared = |prefix_len| !value && key.len() < prefix_len;
+			let (pallet, item) = match categorize_prefix(&key, &lookup) {
+				CategorizedKey::Item(pallet, item) => (pallet, item.name().to_string()),
+				CategorizedKey::Pallet(pallet) if shared(32) => (pallet, SHARED.into()),
+				CategorizedKey::Pallet(pallet) => (pallet, UNKNOWN.into()),
+				CategorizedKey::Unknown if shared(16) => (SHARED.into(), SHARED.into()),
+				CategorizedKey::Unknown => (UNKNOWN.into(), UNKNOWN.into()),
+				CategorizedKey::Child { trie, .. } => (CHILD_TRIES.into(), trie),
+			};
+			let found = proof.entry(pallet).or_default().entry(item).or_default();
+			found.0 += 1;
+			found.1 += size;
+		})?;
+	}
+	let child_nodes = nodes[walker.next..].iter().map(|n| encoded_len(n)).sum();
+
+	Ok(Breakdown {
+		number,
+		header,
+		extrinsics,
+		proof,
+		child_nodes: (nodes.len() - walker.next, child_nodes),
+	})
+}
+
+/// Skip over a header and return its block number.
+fn decode_header(input: &mut &[u8]) -> Result<u32> {
+	let _parent_hash = <[u8; 32]>::decode(input)?;
+	let number = Compact::<u32>::decode(input)?.0;
+	let _roots = <[[u8; 32]; 2]>::decode(input)?;
+
+	for _ in 0..Compact::<u32>::decode(input)?.0 {
+		match u8::decode(input)? {
+			// `Other`
+			0 => drop(Vec::<u8>::decode(input)?),
+			// `Consensus`, `Seal` and `PreRuntime`
+			4..=6 => drop(<([u8; 4], Vec<u8>)>::decode(input)?),
+			// `RuntimeEnvironmentUpdated`
+			8 => (),
+			other => return Err(anyhow!("Unknown digest item {}", other)),
+		}
+	}
+
+	Ok(number)
+}
+
+/// Length of a proof node including its length prefix.
+fn encoded_len(node: &[u8]) -> usize {
+	Compact::<u32>::compact_len(&(node.len() as u32)) + node.len()
+}
+
+/// Walks the nodes of a compact proof in the order in which they were encoded.
+struct Walker<'a> {
+	nodes: &'a [Vec<u8>],
+	next: usize,
+}
+
+impl Walker<'_> {
+	/// Walk the node at the given path and all of its children that are part of the proof.
+	///
+	/// The path is given in nibbles. Calls `visit` with the path and size of every node and whether
+	/// it holds a value.
+	fn walk(
+		&mut self,
+		path: &mut Vec<u8>,
+		visit: &mut impl FnMut(&[u8], usize, bool),
+	) -> Result<()> {
+		let mut node = &self.nodes.get(self.next).ok_or_else(|| anyhow!("Proof ended early"))?[..];
+		let mut size = encoded_len(node);
+		self.next += 1;
+
+		// A hashed value that is part of the proof follows right after its node.
+		if node.first() == Some(&ESCAPE_HEADER) {
+			node = &node[1..];
+			let value = self.nodes.get(self.next).ok_or_else(|| anyhow!("Proof ended early"))?;
+			size += encoded_len(value);
+			self.next += 1;
+		}
+
+		let input = &mut &node[..];
+		let first = u8::decode(input)?;
+		// Whether the node is a branch, whether it has a value and whether that value is hashed.
+		let (branch, value, hashed, nibbles) = match first >> 6 {
+			0b01 => (false, true, false, nibble_count(first, 2, input)?),
+			0b10 => (true, false, false, nibble_count(first, 2, input)?),
+			0b11 => (true, true, false, nibble_count(first, 2, input)?),
+			_ if first >> 5 == 0b001 => (false, true, true, nibble_count(first, 3, input)?),
+			_ if first >> 4 == 0b0001 => (true, true, true, nibble_count(first, 4, input)?),
+			_ if first == 0 => {
+				visit(path, size, false);
+				return Ok(())
+			},
+			_ => return Err(anyhow!("Invalid trie node header {:#04x}", first)),
+		};
+
+		let depth = path.len();
+		let partial = take(input, nibbles.div_ceil(2))?;
+		if nibbles % 2 == 1 {
+			path.push(partial[0] & 0x0f);
+		}
+		for byte in &partial[nibbles % 2..] {
+			path.extend([byte >> 4, byte & 0x0f]);
+		}
+		visit(path, size, value);
+
+		let bitmap = if branch { u16::decode(input)? } else { 0 };
+		if value && hashed {
+			take(input, 32)?;
+		} else if value {
+			let len = Compact::<u32>::decode(input)?.0 as usize;
+			take(input, len)?;
+		}
+
+		for nibble in 0..16 {
+			if bitmap & (1 << nibble) == 0 {
+				continue
+			}
+			// Children that are part of the proof are omitted and follow as separate nodes.
+			let len = Compact::<u32>::decode(input)?.0 as usize;
+			if len == 0 {
+				path.push(nibble);
+				self.walk(path, visit)?;
+				path.pop();
+			} else {
+				take(input, len)?;
+			}
+		}
+
+		path.truncate(depth);
+		Ok(())
+	}
+}
+
+/// Decode the number of nibbles in the partial key of a node.
+///
+/// The header byte holds the number in its lower bits after the prefix. If those bits are all set,
+/// the number continues in the following bytes.
+fn nibble_count(first: u8, prefix_bits: u32, input: &mut &[u8]) -> Result<usize> {
+	let max = 255u8 >> prefix_bits;
+	let mut count = (first & max) as usize;
+	if count < max as usize {
+		return Ok(count)
+	}
+	loop {
+		let next = u8::decode(input)?;
+		count += next as usize;
+		if next < 255 {
+			return Ok(count)
+		}
+	}
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+	if input.len() < len {
+		return Err(anyhow!("Trie node ended early"))
+	}
+	let (taken, rest) = input.split_at(len);
+	*input = rest;
+	Ok(taken)
+}
@@ -0,0 +1,101 @@
+//! External tools that snapshots are streamed through, like `age` or `aws`.
+
+use crate::error::{PduError, Result};
+use std::{
+	io::{self, Read},
+	process::{Child, ChildStdout, Command, ExitStatus, Stdio},
+};
+
+/// The output of running tools, where each tool reads the output of the one before.
+///
+/// Whether the tools succeeded is only known once they exit, eg. `gpg` checks the integrity of a
+/// file at its end. So [`Output::finish`] must be called after reading what is needed, otherwise
+/// the tools are killed once the output is dropped.
+pub struct Output {
+	/// The running tools by their name.
+	children: Vec<(&'static str, Child)>,
+	/// Taken when another tool reads it instead, see `Output::pipe`.
+	stdout: Option<ChildStdout>,
+}
+
+impl Output {
+	/// Run a tool and stream its output.
+	pub fn spawn(name: &'static str, command: &mut Command) -> Result<Self> {
+		Self::spawn_with(name, command, Stdio::null(), Vec::new())
+	}
+
+	/// Run a tool that reads the output of this one.
+	pub fn pipe(mut self, name: &'static str, command: &mut Command) -> Result<Self> {
+		let stdout = self.stdout.take().expect("Only taken here; qed");
+		Self::spawn_with(name, command, stdout.into(), std::mem::take(&mut self.children))
+	}
+
+	fn spawn_with(
+		name: &'static str,
+		command: &mut Command,
+		stdin: Stdio,
+		mut children: Vec<(&'static str, Child)>,
+	) -> Result<Self> {
+		let mut child = command
+			.stdin(stdin)
+			.stdout(Stdio::piped())
+			.spawn()
+			.map_err(|e| spawn_error(name, e))?;
+		let stdout = child.stdout.take().expect("Stdout is piped; qed");
+		children.push((name, child));
+
+		Ok(Self { children, stdout: Some(stdout) })
+	}
+}
+
+impl Output {
+	/// Read the rest of the output and wait for the tools to exit.
+	///
+	/// Fails if any of them failed, eg. because a file was tampered with.
+	pub fn finish(mut self) -> Result<()> {
+		if let Some(mut stdout) = self.stdout.take() {
+			io::copy(&mut stdout, &mut io::sink())
+				.map_err(|e| PduError::io("Failed to read the output of the tools", e))?;
+		}
+		for (name, child) in &mut self.children {
+			let status = child.wait().map_err(|e| PduError::io(format!("{} failed", name), e))?;
+			check_status(name, status)?;
+		}
+		Ok(())
+	}
+}
+
+impl Read for Output {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let Some(stdout) = &mut self.stdout else { return Ok(0) };
+		stdout.read(buf)
+	}
+}
+
+impl Drop for Output {
+	fn drop(&mut self) {
+		// The output may not be read to the end, eg. with `--end-key`.
+		for (_, child) in &mut self.children {
+			let _ = child.kill();
+			let _ = child.wait();
+		}
+	}
+}
+
+/// Run a tool to completion.
+pub fn run(name: &'static str, command: &mut Command) -> Result<()> {
+	let status = command.status().map_err(|e| spawn_error(name, e))?;
+	check_status(name, status)
+}
+
+fn check_status(name: &str, status: ExitStatus) -> Result<()> {
+	if !status.success() {
+		let e = io::Error::other(format!("exited with {}", status));
+		return Err(PduError::io(format!("{} failed", name), e))
+	}
+	Ok(())
+}
+
+fn spawn_error(name: &str, e: io::Error) -> PduError {
+	PduError::io(format!("Failed to run {}, is it installed?", name), e)
+}
@@ -0,0 +1,22 @@
+//! Progress of an analysis, for embedders that want to show it in their own way.
+
+use crate::info::PalletInfo;
+use std::sync::Arc;
+
+/// A step of the analysis.
+pub enum Progress<'a> {
+	/// Started to load or fetch the metadata.
+	Metadata,
+	/// Started to categorize this many keys, if the source knows how many.
+	Categorize { keys: Option<usize> },
+	/// Processed a key of this pallet, or of an unknown one if it was skipped by sampling.
+	Key { pallet: Option<&'a str> },
+	/// Categorized all keys of a pallet. Only sent if the snapshot is read pallet by pallet, which
+	/// it is with an index, see `--index`.
+	Pallet { pallet: &'a PalletInfo },
+	/// Stopped categorizing, either because all keys are done or because of an interrupt.
+	Done { interrupted: bool },
+}
+
+/// Receives the progress of an analysis. Called concurrently by all workers.
+pub type ProgressFn = Arc<dyn Fn(Progress) + Send + Sync>;
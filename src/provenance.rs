@@ -0,0 +1,78 @@
+//! Where the results of an analysis come from, so that saved outputs describe themselves.
+
+use crate::report::{block_number, Collected, Decoder};
+use parity_scale_codec::Decode;
+use std::{
+	fmt,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+pub struct Provenance {
+	/// Block number of the snapshot as per `System::Number`.
+	pub block: Option<u128>,
+	/// Name and spec version of the runtime as per the metadata.
+	pub runtime: Option<(String, u32)>,
+	/// Version of PDU that did the analysis.
+	pub version: &'static str,
+	/// When the analysis was done, in seconds since the Unix epoch.
+	pub analyzed_at: u64,
+}
+
+impl Provenance {
+	pub fn new(collected: &Collected, decoder: &Decoder) -> Self {
+		// The runtime version starts with the spec name, impl name, authoring and spec version.
+		let runtime = decoder
+			.meta()
+			.pallet_by_name("System")
+			.and_then(|p| p.constant_by_name("Version"))
+			.and_then(|c| <(String, String, u32, u32)>::decode(&mut c.value()).ok())
+			.map(|(spec_name, _, _, spec_version)| (spec_name, spec_version));
+
+		Self {
+			block: block_number(collected, decoder).ok(),
+			runtime,
+			version: env!("CARGO_PKG_VERSION"),
+			analyzed_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+		}
+	}
+}
+
+impl fmt::Display for Provenance {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.block {
+			Some(block) => write!(f, "Snapshot at block {}", block)?,
+			None => write!(f, "Snapshot at unknown block")?,
+		}
+		if let Some((name, spec_version)) = &self.runtime {
+			write!(f, " of {} v{}", name, spec_version)?;
+		}
+		write!(f, ", analyzed by pdu {} at {}", self.version, fmt_timestamp(self.analyzed_at))
+	}
+}
+
+/// Format a Unix timestamp as RFC 3339 date and time in UTC.
+pub fn fmt_timestamp(secs: u64) -> String {
+	let (days, secs) = (secs / 86_400, secs % 86_400);
+
+	// Convert the days since the epoch into a civil date, see
+	// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+	let z = days as i64 + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + (month <= 2) as i64;
+
+	format!(
+		"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+		year,
+		month,
+		day,
+		secs / 3_600,
+		secs % 3_600 / 60,
+		secs % 60
+	)
+}
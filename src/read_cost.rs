@@ -0,0 +1,63 @@
+//! Estimated number of trie nodes and bytes that reading one entry of a storage item touches.
+//!
+//! The path to an entry leads through the branches that tell the pallets apart, then those that
+//! tell the items of the pallet apart and then those that tell the entries of the item apart. Each
+//! level is estimated as if its keys were random, which they are for hashed keys. So a prefix of
+//! many pallets, items or entries costs more to read through, and a map with a million entries
+//! costs five to six branches more per read than a plain value.
+
+/// Radix of the trie, which branches by nibble.
+const RADIX: f64 = 16.0;
+
+/// Length of a child hash within a branch node.
+const HASH_LEN: f64 = 32.0;
+
+/// Length of the header and the bitmap of the children of a branch node.
+const BRANCH_HEADER_LEN: f64 = 3.0;
+
+/// Values longer than this are stored in a node of their own since state version 1.
+const MAX_INLINE_VALUE: usize = 32;
+
+/// Expected nodes and their encoded bytes to read one entry.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReadCost {
+	pub nodes: f64,
+	pub bytes: f64,
+}
+
+impl ReadCost {
+	/// The cost to read an entry of an item with `entries` entries whose values have a mean length
+	/// of `mean_value`, among `items` items of a pallet among `pallets` pallets.
+	pub fn estimate(pallets: usize, items: usize, entries: usize, mean_value: usize) -> Self {
+		let mut cost = ReadCost::default();
+		for keys in [pallets, items, entries] {
+			cost.add_branches(keys);
+		}
+
+		// The leaf with the rest of the key and the value, or the hash of the value.
+		cost.nodes += 1.0;
+		cost.bytes += BRANCH_HEADER_LEN + mean_value as f64;
+		if mean_value > MAX_INLINE_VALUE {
+			cost.nodes += 1.0;
+			cost.bytes += HASH_LEN;
+		}
+		cost
+	}
+
+	/// Add the branches on the path to one of `keys` random keys.
+	///
+	/// There is a branch at depth `d` if another key diverges there, and it holds the hash of every
+	/// nibble at depth `d` that any key continues with.
+	fn add_branches(&mut self, keys: usize) {
+		let others = keys.saturating_sub(1) as f64;
+		let mut share = 1.0;
+		// Stop once no other key is expected to share that many nibbles.
+		while others * share > 1e-6 {
+			let branch = 1.0 - (1.0 - share * (RADIX - 1.0) / RADIX).powf(others);
+			let siblings = (RADIX - 1.0) * (1.0 - (1.0 - share / RADIX).powf(others));
+			self.nodes += branch;
+			self.bytes += BRANCH_HEADER_LEN * branch + HASH_LEN * (branch + siblings);
+			share /= RADIX;
+		}
+	}
+}
@@ -0,0 +1,74 @@
+//! Snapshots and reports in S3 or GCS, through the `aws` and `gcloud` command line tools.
+//!
+//! The tools bring their own credentials and stream files in parts, so neither downloads nor
+//! uploads need to fit into memory or onto the disk.
+
+use crate::process::{self, Output};
+use crate::error::{PduError, Result};
+use std::process::{Command, Stdio};
+
+/// An object storage service.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Remote {
+	/// Amazon S3 with `s3://bucket/key` URIs.
+	S3,
+	/// Google Cloud Storage with `gs://bucket/key` URIs.
+	Gcs,
+}
+
+impl Remote {
+	/// The service of a URI, or `None` for local paths.
+	pub fn of(uri: &str) -> Option<Self> {
+		if uri.starts_with("s3://") {
+			Some(Remote::S3)
+		} else if uri.starts_with("gs://") {
+			Some(Remote::Gcs)
+		} else {
+			None
+		}
+	}
+
+	fn tool(self) -> &'static str {
+		match self {
+			Remote::S3 => "aws",
+			Remote::Gcs => "gcloud",
+		}
+	}
+
+	/// Copy from `from` to `to`, where `-` is stdin or stdout.
+	fn copy_command(self, from: &str, to: &str) -> Command {
+		let mut command = Command::new(self.tool());
+		match self {
+			Remote::S3 => command.args(["s3", "cp", "--only-show-errors", from, to]),
+			Remote::Gcs => command.args(["storage", "cp", from, to]),
+		};
+		command
+	}
+}
+
+/// Stream an object from S3 or GCS.
+pub fn download(uri: &str) -> Result<Output> {
+	let remote = Remote::of(uri).ok_or_else(|| invalid_uri(uri))?;
+	log::info!("Downloading {} with {}", uri, remote.tool());
+
+	Output::spawn(remote.tool(), &mut remote.copy_command(uri, "-"))
+}
+
+/// Upload a file to S3 or GCS, or stdin if the path is `-`.
+///
+/// Large files are uploaded in parts by the tools.
+pub fn upload(path: &str, uri: &str) -> Result<()> {
+	let remote = Remote::of(uri).ok_or_else(|| invalid_uri(uri))?;
+	let mut command = remote.copy_command(path, uri);
+	if path == "-" {
+		command.stdin(Stdio::inherit());
+	}
+
+	process::run(remote.tool(), &mut command)?;
+	log::info!("Uploaded {} to {}", path, uri);
+	Ok(())
+}
+
+fn invalid_uri(uri: &str) -> PduError {
+	PduError::Rpc(format!("{} is not an s3:// or gs:// URI", uri))
+}
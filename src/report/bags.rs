@@ -0,0 +1,268 @@
+//! Health of the bags list that sorts the voters of `pallet_staking` by their score.
+//!
+//! Nodes are linked into the bag of their score. A node whose score changed stays in its old bag
+//! until someone calls `rebag`, and a node that is missing from the links of its bag is never
+//! iterated, which means that its nominations are ignored by the election.
+
+use super::{
+	fmt_account, ss58_prefix, storage_entry, sub_keys::FirstKey, value_account, warn, Collected,
+	Decoder,
+};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use parity_scale_codec::Decode;
+use scale_value::{At, Value, ValueDef};
+use std::collections::{BTreeMap as Map, BTreeSet};
+use subxt::Metadata;
+
+/// Storage items of a bags list pallet.
+const ITEMS: &[&str] = &["ListNodes", "ListBags", "CounterForListNodes"];
+
+/// Number of bags to show, by the number of their nodes.
+const TOP_BAGS: usize = 20;
+
+/// Number of orphaned nodes to show.
+const TOP_ORPHANS: usize = 10;
+
+/// Pallets that are an instance of `pallet_bags_list`, like `VoterList`.
+fn pallets(meta: &Metadata) -> Vec<String> {
+	meta.pallets()
+		.filter(|p| p.storage().is_some_and(|s| s.entry_by_name("ListNodes").is_some()))
+		.filter(|p| p.storage().is_some_and(|s| s.entry_by_name("ListBags").is_some()))
+		.map(|p| p.name().to_string())
+		.collect()
+}
+
+/// Storage items of all bags list pallets, as `(pallet, item)`.
+pub fn items(meta: &Metadata) -> Vec<(String, String)> {
+	pallets(meta)
+		.into_iter()
+		.flat_map(|pallet| ITEMS.iter().map(move |item| (pallet.clone(), item.to_string())))
+		.collect()
+}
+
+/// A decoded entry of `ListNodes`.
+struct Node {
+	id: [u8; 32],
+	next: Option<[u8; 32]>,
+	bag_upper: u64,
+	score: u64,
+	size: usize,
+}
+
+impl Node {
+	fn decode(value: &Value<u32>, size: usize) -> Option<Self> {
+		Some(Self {
+			id: value_account(value.at("id")?)?,
+			next: option_account(value.at("next")?)?,
+			bag_upper: value.at("bag_upper")?.as_u128()?.try_into().ok()?,
+			score: value.at("score")?.as_u128()?.try_into().ok()?,
+			size,
+		})
+	}
+}
+
+/// Decode an `Option<AccountId>`, returning `None` if it is not an option.
+fn option_account(value: &Value<u32>) -> Option<Option<[u8; 32]>> {
+	let ValueDef::Variant(variant) = &value.value else { return None };
+
+	match variant.name.as_str() {
+		"None" => Some(None),
+		"Some" => value_account(variant.values.values().next()?).map(Some),
+		_ => None,
+	}
+}
+
+/// The bag that a score belongs into according to the thresholds.
+fn notional_bag(score: u64, thresholds: &[u64]) -> u64 {
+	thresholds.iter().copied().find(|t| score <= *t).unwrap_or(u64::MAX)
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let pallets = pallets(decoder.meta());
+	if pallets.is_empty() {
+		println!("Bags list: the metadata has no bags list pallet");
+	}
+	for pallet in pallets {
+		print_pallet(&pallet, collected, decoder, units)?;
+	}
+
+	Ok(())
+}
+
+fn print_pallet(
+	pallet: &str,
+	collected: &Collected,
+	decoder: &Decoder,
+	units: Units,
+) -> Result<()> {
+	let meta = decoder.meta();
+	let prefix = ss58_prefix(meta);
+	let thresholds = meta
+		.pallet_by_name(pallet)
+		.and_then(|p| p.constant_by_name("BagThresholds"))
+		.and_then(|c| Vec::<u64>::decode(&mut c.value()).ok())
+		.unwrap_or_default();
+
+	let mut nodes = Map::new();
+	let mut undecodable = 0;
+	for (key, value) in collected.entries(pallet, "ListNodes") {
+		let node = decoder.decode(pallet, "ListNodes", value);
+		match node.ok().and_then(|n| Node::decode(&n, key.len() + value.len())) {
+			Some(node) => {
+				nodes.insert(node.id, node);
+			},
+			None => undecodable += 1,
+		}
+	}
+
+	// The bag upper is the `Twox64Concat` key of `ListBags`.
+	let hash_len = storage_entry(meta, pallet, "ListBags")
+		.ok()
+		.and_then(|entry| FirstKey::of(entry, meta))
+		.map_or(8, |first_key| first_key.hasher.len_excluding_key());
+	let mut bags = Map::new();
+	for (key, value) in collected.entries(pallet, "ListBags") {
+		let Some(upper) = key.get(32 + hash_len..).and_then(|k| u64::decode(&mut &k[..]).ok())
+		else {
+			undecodable += 1;
+			continue
+		};
+		let head = decoder
+			.decode(pallet, "ListBags", value)
+			.ok()
+			.and_then(|bag| option_account(bag.at("head")?));
+		let Some(head) = head else {
+			undecodable += 1;
+			continue
+		};
+		bags.insert(upper, (head, key.len() + value.len()));
+	}
+
+	// Follow the links from the head of every bag to find the nodes that are iterated.
+	let mut linked = BTreeSet::new();
+	let mut broken = 0;
+	for (upper, (head, _)) in &bags {
+		let mut next = *head;
+		while let Some(id) = next {
+			match nodes.get(&id) {
+				Some(node) if node.bag_upper == *upper && linked.insert(id) => next = node.next,
+				_ => {
+					broken += 1;
+					break
+				},
+			}
+		}
+	}
+
+	let size = nodes.values().map(|n| n.size).sum::<usize>() +
+		bags.values().map(|(_, size)| size).sum::<usize>();
+	println!(
+		"Bags list {}: {} nodes in {} bags, {}",
+		pallet,
+		nodes.len(),
+		bags.len(),
+		fmt_bytes(size, false, units)
+	);
+
+	let mut by_bag = Map::<u64, (usize, usize)>::new();
+	for node in nodes.values() {
+		let (count, size) = by_bag.entry(node.bag_upper).or_default();
+		*count += 1;
+		*size += node.size;
+	}
+	let top = by_bag.iter().sorted_by_key(|(_, (count, _))| *count).rev().take(TOP_BAGS);
+	for (upper, (count, size)) in top {
+		let bag_size = bags.get(upper).map_or(0, |(_, size)| *size);
+		println!(
+			"  Bag {}: {} nodes, {}",
+			fmt_upper(*upper),
+			count,
+			fmt_bytes(size + bag_size, false, units)
+		);
+	}
+	if by_bag.len() > TOP_BAGS {
+		println!("  ... and {} more", by_bag.len() - TOP_BAGS);
+	}
+
+	let counter = collected
+		.entries(pallet, "CounterForListNodes")
+		.first()
+		.and_then(|(_, value)| u32::decode(&mut &value[..]).ok());
+	if let Some(counter) = counter.filter(|c| *c as usize != nodes.len()) {
+		warn(format!("{}::CounterForListNodes is {} instead of {}", pallet, counter, nodes.len()));
+	}
+	if undecodable > 0 {
+		warn(format!("{} entries of {} could not be decoded", undecodable, pallet));
+	}
+	if broken > 0 {
+		warn(format!("{} bags link to nodes that are missing or in another bag", broken));
+	}
+
+	let orphans = nodes.values().filter(|n| !linked.contains(&n.id)).collect::<Vec<_>>();
+	if !orphans.is_empty() {
+		let size = orphans.iter().map(|n| n.size).sum();
+		warn(format!(
+			"{} nodes with {} are not linked from their bag and never iterated",
+			orphans.len(),
+			fmt_bytes(size, false, units)
+		));
+		for node in orphans.iter().take(TOP_ORPHANS) {
+			println!("    {} in bag {}", fmt_account(&node.id, prefix), fmt_upper(node.bag_upper));
+		}
+		if orphans.len() > TOP_ORPHANS {
+			println!("    ... and {} more", orphans.len() - TOP_ORPHANS);
+		}
+	}
+
+	let empty = bags.values().filter(|(head, _)| head.is_none()).collect::<Vec<_>>();
+	if !empty.is_empty() {
+		let size = empty.iter().map(|(_, size)| size).sum();
+		let size = fmt_bytes(size, false, units);
+		warn(format!("{} bags without nodes occupy {}", empty.len(), size));
+	}
+
+	if thresholds.is_empty() {
+		return Ok(())
+	}
+	// A bag is removed with its last node and created with its first one.
+	let misplaced = nodes
+		.values()
+		.filter(|n| notional_bag(n.score, &thresholds) != n.bag_upper)
+		.collect::<Vec<_>>();
+	let mut rebagged =
+		by_bag.iter().map(|(upper, (count, _))| (*upper, *count)).collect::<Map<_, _>>();
+	for node in &misplaced {
+		*rebagged.entry(node.bag_upper).or_default() -= 1;
+		*rebagged.entry(notional_bag(node.score, &thresholds)).or_default() += 1;
+	}
+	let removed = rebagged
+		.iter()
+		.filter(|(upper, count)| **count == 0 && by_bag.contains_key(upper))
+		.filter_map(|(upper, _)| bags.get(upper).map(|(_, size)| *size))
+		.collect::<Vec<_>>();
+	let created = misplaced
+		.iter()
+		.map(|n| notional_bag(n.score, &thresholds))
+		.filter(|upper| !bags.contains_key(upper))
+		.unique()
+		.count();
+
+	println!(
+		"  Misplaced nodes: {}, rebagging them would remove {} bags with {} and create {}",
+		misplaced.len(),
+		removed.len(),
+		fmt_bytes(removed.iter().sum(), false, units),
+		created
+	);
+
+	Ok(())
+}
+
+fn fmt_upper(upper: u64) -> String {
+	match upper {
+		u64::MAX => "MAX".into(),
+		upper => upper.to_string(),
+	}
+}
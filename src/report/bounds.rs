@@ -0,0 +1,119 @@
+//! Entries whose value is longer than its type allows.
+//!
+//! Types without vectors or strings have a maximal encoded length. A longer value could not have
+//! been written by the current runtime, so it is most likely a leftover of a missed migration.
+
+use super::Collected;
+use anyhow::Result;
+use scale_info::{TypeDef, TypeDefPrimitive};
+use std::collections::BTreeSet;
+use subxt::Metadata;
+
+/// Values of a storage item that exceeded its bound.
+pub struct Oversized {
+	/// The maximal encoded length of the value type.
+	pub bound: usize,
+	pub entries: usize,
+	/// Length of the longest value.
+	pub longest: usize,
+}
+
+/// The storage items with bounded values as `(pallet, item, bound)`.
+pub fn items(meta: &Metadata) -> Vec<(String, String, usize)> {
+	let mut items = Vec::new();
+
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			let value_ty = entry.entry_type().value_ty();
+
+			if let Some(bound) = max_encoded_len(value_ty, meta, &mut BTreeSet::new()) {
+				items.push((pallet.name().to_string(), entry.name().to_string(), bound));
+			}
+		}
+	}
+
+	items
+}
+
+pub fn print(collected: &Collected, meta: &Metadata) -> Result<()> {
+	let oversized = collected.oversized.values().map(|o| o.entries).sum::<usize>();
+	println!(
+		"Values longer than their type allows: {} entries in {} of {} bounded storage items",
+		oversized,
+		collected.oversized.len(),
+		items(meta).len()
+	);
+
+	for (name, oversized) in &collected.oversized {
+		println!(
+			"  {}: {} entries of up to {} bytes, but at most {} are allowed",
+			name, oversized.entries, oversized.longest, oversized.bound
+		);
+	}
+
+	Ok(())
+}
+
+/// The maximal length of an encoded value of a type, if it is bounded.
+fn max_encoded_len(ty: u32, meta: &Metadata, seen: &mut BTreeSet<u32>) -> Option<usize> {
+	// Recursive types are only bounded when they are never instantiated, so ignore them.
+	if !seen.insert(ty) {
+		return None
+	}
+	let resolved = meta.types().resolve(ty)?;
+
+	let len = match &resolved.type_def {
+		TypeDef::Primitive(primitive) => match primitive {
+			TypeDefPrimitive::Bool | TypeDefPrimitive::U8 | TypeDefPrimitive::I8 => 1,
+			TypeDefPrimitive::U16 | TypeDefPrimitive::I16 => 2,
+			TypeDefPrimitive::Char | TypeDefPrimitive::U32 | TypeDefPrimitive::I32 => 4,
+			TypeDefPrimitive::U64 | TypeDefPrimitive::I64 => 8,
+			TypeDefPrimitive::U128 | TypeDefPrimitive::I128 => 16,
+			TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => 32,
+			TypeDefPrimitive::Str => return None,
+		},
+		TypeDef::Compact(compact) => max_compact_len(compact.type_param.id, meta)?,
+		TypeDef::Array(array) =>
+			array.len as usize * max_encoded_len(array.type_param.id, meta, seen)?,
+		TypeDef::Tuple(tuple) => tuple
+			.fields
+			.iter()
+			.map(|f| max_encoded_len(f.id, meta, seen))
+			.sum::<Option<usize>>()?,
+		TypeDef::Composite(composite) => composite
+			.fields
+			.iter()
+			.map(|f| max_encoded_len(f.ty.id, meta, seen))
+			.sum::<Option<usize>>()?,
+		TypeDef::Variant(variant) => {
+			let mut longest = 0;
+			for v in &variant.variants {
+				let fields = v.fields.iter().map(|f| max_encoded_len(f.ty.id, meta, seen));
+				longest = longest.max(fields.sum::<Option<usize>>()?);
+			}
+			// One byte for the index of the variant.
+			1 + longest
+		},
+		TypeDef::Sequence(_) | TypeDef::BitSequence(_) => return None,
+	};
+
+	// Types may appear multiple times, only nesting a type in itself is recursive.
+	seen.remove(&ty);
+	Some(len)
+}
+
+/// The maximal length of a compact encoded number.
+fn max_compact_len(ty: u32, meta: &Metadata) -> Option<usize> {
+	match &meta.types().resolve(ty)?.type_def {
+		TypeDef::Primitive(TypeDefPrimitive::U8) => Some(2),
+		TypeDef::Primitive(TypeDefPrimitive::U16) => Some(4),
+		TypeDef::Primitive(TypeDefPrimitive::U32) => Some(5),
+		TypeDef::Primitive(TypeDefPrimitive::U64) => Some(9),
+		TypeDef::Primitive(TypeDefPrimitive::U128) => Some(17),
+		// Wrappers like `Perbill` are encoded as their inner number.
+		TypeDef::Composite(composite) if composite.fields.len() == 1 =>
+			max_compact_len(composite.fields[0].ty.id, meta),
+		TypeDef::Tuple(tuple) if tuple.fields.is_empty() => Some(0),
+		_ => None,
+	}
+}
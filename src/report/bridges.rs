@@ -0,0 +1,236 @@
+//! Message lanes and imported headers of the bridge pallets on the BridgeHubs.
+//!
+//! Outbound messages stay in storage until the other side confirms their delivery, so a lane with
+//! many queued messages points to stalled relayers. Inbound lanes keep the relayers that delivered
+//! messages until their reward is confirmed. Bridges are found by their storage items, so that
+//! every instance like `BridgeKusamaMessages` or `BridgeRococoGrandpa` is included.
+
+use super::{key_value, storage_entry, value_bytes, warn, Collected, Decoder};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use scale_value::{At, Value, ValueDef};
+use std::collections::BTreeMap as Map;
+use subxt::Metadata;
+
+/// A bridge pallet.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+	/// `pallet_bridge_messages`, which queues the messages of its lanes.
+	Messages,
+	/// `pallet_bridge_grandpa`, which imports the finalized headers of the other relay chain.
+	Grandpa,
+	/// `pallet_bridge_parachains`, which imports the heads of the parachains of the other side.
+	Parachains,
+}
+
+impl Kind {
+	fn items(self) -> &'static [&'static str] {
+		match self {
+			Kind::Messages => &["OutboundMessages", "OutboundLanes", "InboundLanes"],
+			Kind::Grandpa => &["ImportedHeaders", "ImportedHashes"],
+			Kind::Parachains => &["ImportedParaHeads", "ImportedParaHashes", "ParasInfo"],
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Kind::Messages => "messages",
+			Kind::Grandpa => "grandpa",
+			Kind::Parachains => "parachains",
+		}
+	}
+}
+
+/// Bridge pallets by their kind.
+fn pallets(meta: &Metadata) -> Vec<(String, Kind)> {
+	meta.pallets()
+		.filter_map(|p| {
+			let has = |item| p.storage().is_some_and(|s| s.entry_by_name(item).is_some());
+			let kind = if has("OutboundMessages") && has("OutboundLanes") && has("InboundLanes") {
+				Kind::Messages
+			} else if has("ImportedHeaders") && has("ImportedHashes") {
+				Kind::Grandpa
+			} else if has("ImportedParaHeads") {
+				Kind::Parachains
+			} else {
+				return None
+			};
+			Some((p.name().to_string(), kind))
+		})
+		.collect()
+}
+
+/// The storage items of all bridge pallets, as `(pallet, item)`.
+pub fn items(meta: &Metadata) -> Vec<(String, String)> {
+	let mut items = Vec::new();
+	for (pallet, kind) in pallets(meta) {
+		items.extend(kind.items().iter().map(|item| (pallet.clone(), item.to_string())));
+	}
+	items
+}
+
+/// The queued messages of an outbound lane.
+#[derive(Default)]
+struct Queued {
+	messages: usize,
+	size: usize,
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let pallets = pallets(meta);
+	if pallets.is_empty() {
+		println!("Bridges: the runtime has no bridge pallets");
+		return Ok(())
+	}
+	println!("Bridges:");
+	let mut undecodable = 0;
+
+	for (pallet, kind) in pallets {
+		let count = |item| collected.entries(&pallet, item).len();
+		let size = kind
+			.items()
+			.iter()
+			.flat_map(|item| collected.entries(&pallet, item))
+			.map(|(key, value)| key.len() + value.len())
+			.sum();
+		let size = fmt_bytes(size, false, units);
+
+		match kind {
+			Kind::Messages => {
+				println!(
+					"  {} ({}): {} queued messages in {} outbound and {} inbound lanes, {}",
+					pallet,
+					kind.name(),
+					count("OutboundMessages"),
+					count("OutboundLanes"),
+					count("InboundLanes"),
+					size
+				);
+				undecodable += print_lanes(collected, decoder, &pallet, units);
+			},
+			Kind::Grandpa => println!(
+				"  {} ({}): {} imported headers, {}",
+				pallet,
+				kind.name(),
+				count("ImportedHeaders"),
+				size
+			),
+			Kind::Parachains => println!(
+				"  {} ({}): {} imported heads of {} parachains, {}",
+				pallet,
+				kind.name(),
+				count("ImportedParaHeads"),
+				count("ParasInfo"),
+				size
+			),
+		}
+	}
+
+	if undecodable > 0 {
+		warn(format!("Could not decode {} bridge entries", undecodable));
+	}
+
+	Ok(())
+}
+
+/// Print the queued messages and the nonces of the lanes of a messages pallet.
+///
+/// Returns the number of entries that could not be decoded.
+fn print_lanes(collected: &Collected, decoder: &Decoder, pallet: &str, units: Units) -> usize {
+	let meta = decoder.meta();
+	let mut undecodable = 0;
+	let mut queued = Map::<String, Queued>::new();
+
+	// Keys are `MessageKey { lane_id, nonce }`.
+	if let Ok(entry) = storage_entry(meta, pallet, "OutboundMessages") {
+		for (key, value) in collected.entries(pallet, "OutboundMessages") {
+			let lane = key_value(entry, key, meta).and_then(|k| Some(fmt_lane(k.at("lane_id")?)));
+			let Some(lane) = lane else {
+				undecodable += 1;
+				continue
+			};
+			let queued = queued.entry(lane).or_default();
+			queued.messages += 1;
+			queued.size += key.len() + value.len();
+		}
+	}
+
+	if let Ok(entry) = storage_entry(meta, pallet, "OutboundLanes") {
+		for (key, value) in collected.entries(pallet, "OutboundLanes") {
+			let lane = key_value(entry, key, meta).map(|lane| fmt_lane(&lane));
+			let data = decoder.decode(pallet, "OutboundLanes", value);
+			let (Some(lane), Ok(data)) = (lane, data) else {
+				undecodable += 1;
+				continue
+			};
+			let nonce = |field| data.at(field).and_then(|n| n.as_u128()).unwrap_or_default();
+			let oldest = nonce("oldest_unpruned_nonce");
+			let received = nonce("latest_received_nonce");
+			let generated = nonce("latest_generated_nonce");
+			let queued = queued.remove(&lane).unwrap_or_default();
+			println!(
+				"    Outbound lane {}: {} queued messages ({}), {} undelivered, {} delivered but not pruned",
+				lane,
+				queued.messages,
+				fmt_bytes(queued.size, false, units),
+				generated.saturating_sub(received),
+				(received + 1).saturating_sub(oldest)
+			);
+		}
+	}
+	// Messages of lanes that were closed, but not pruned.
+	for (lane, queued) in queued {
+		println!(
+			"    Outbound lane {} without lane data: {} queued messages ({})",
+			lane,
+			queued.messages,
+			fmt_bytes(queued.size, false, units)
+		);
+	}
+
+	if let Ok(entry) = storage_entry(meta, pallet, "InboundLanes") {
+		for (key, value) in collected.entries(pallet, "InboundLanes") {
+			let lane = key_value(entry, key, meta).map(|lane| fmt_lane(&lane));
+			let data = decoder.decode(pallet, "InboundLanes", value);
+			let (Some(lane), Ok(data)) = (lane, data) else {
+				undecodable += 1;
+				continue
+			};
+			let relayers = match data.at("relayers").map(|r| &r.value) {
+				Some(ValueDef::Composite(relayers)) => relayers.values().collect(),
+				_ => Vec::new(),
+			};
+			// The last relayer delivered the latest messages.
+			let delivered = relayers
+				.last()
+				.and_then(|r| r.at("messages")?.at("end")?.as_u128())
+				.or_else(|| data.at("last_confirmed_nonce")?.as_u128())
+				.unwrap_or_default();
+			println!(
+				"    Inbound lane {}: {} unrewarded relayer entries, last delivered nonce {} ({})",
+				lane,
+				relayers.len(),
+				delivered,
+				fmt_bytes(key.len() + value.len(), false, units)
+			);
+		}
+	}
+
+	undecodable
+}
+
+/// Format a lane id like `LaneId([u8; 4])` or `HashedLaneId(H256)` as hex.
+fn fmt_lane(lane: &Value<u32>) -> String {
+	if let ValueDef::Composite(composite) = &lane.value {
+		if let Ok(inner) = composite.values().exactly_one() {
+			return fmt_lane(inner)
+		}
+	}
+
+	match value_bytes(lane) {
+		Some(bytes) => format!("0x{}", hex::encode(bytes)),
+		None => lane.to_string(),
+	}
+}
@@ -0,0 +1,94 @@
+//! How much storage could be saved if items compressed their values, eg. with `ScaleCompressed`.
+//!
+//! Every value is compressed with deflate and decompressed again to estimate the overhead of
+//! decoding. A compressed value is stored as byte vector, so it also needs a length prefix.
+
+use super::Collected;
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec};
+use parity_scale_codec::{Compact, CompactLen};
+use std::time::{Duration, Instant};
+
+/// Deflate level that trades off compression ratio and speed like a runtime would.
+const LEVEL: u8 = 6;
+
+/// Number of storage items to recommend.
+const TOP_ITEMS: usize = 20;
+
+/// Measured compression of the values of a storage item.
+#[derive(Default)]
+pub struct Compression {
+	pub entries: usize,
+	pub value_len: usize,
+	/// Length of all compressed values including their length prefix.
+	pub compressed_len: usize,
+	/// Time spent decompressing all values.
+	pub decode: Duration,
+}
+
+impl Compression {
+	/// Compress and decompress a value to measure its savings and overhead.
+	pub fn measure(&mut self, value: &[u8]) {
+		let compressed = compress_to_vec(value, LEVEL);
+		let started = Instant::now();
+		let _ = decompress_to_vec(&compressed);
+		self.decode += started.elapsed();
+
+		self.entries += 1;
+		self.value_len += value.len();
+		self.compressed_len += stored_len(&compressed);
+	}
+
+	pub fn merge(&mut self, other: &Compression) {
+		self.entries += other.entries;
+		self.value_len += other.value_len;
+		self.compressed_len += other.compressed_len;
+		self.decode += other.decode;
+	}
+
+	fn saved(&self) -> usize {
+		self.value_len.saturating_sub(self.compressed_len)
+	}
+}
+
+/// Length of a value once compressed, including its length prefix.
+pub fn compressed_len(value: &[u8]) -> usize {
+	stored_len(&compress_to_vec(value, LEVEL))
+}
+
+/// Length of a compressed value as byte vector.
+fn stored_len(compressed: &[u8]) -> usize {
+	Compact::<u32>::compact_len(&(compressed.len() as u32)) + compressed.len()
+}
+
+pub fn print(collected: &Collected, units: Units) -> Result<()> {
+	let (worth, not_worth): (Vec<_>, Vec<_>) =
+		collected.compression.iter().partition(|(_, c)| c.saved() > 0);
+	let saved = worth.iter().map(|(_, c)| c.saved()).sum::<usize>();
+
+	println!(
+		"Compression advice: {} of values could be saved by compressing {} storage items",
+		fmt_bytes(saved, false, units),
+		worth.len()
+	);
+
+	let ranked = worth.iter().sorted_by_key(|(_, c)| c.saved()).rev();
+	for (name, compression) in ranked.take(TOP_ITEMS) {
+		let ratio = compression.compressed_len as f64 / compression.value_len as f64;
+		let decode = compression.decode / compression.entries.max(1) as u32;
+		println!(
+			"  {}: {} saved, values shrink to {:.0}%, {:.2?} to decode per entry",
+			name,
+			fmt_bytes(compression.saved(), false, units),
+			ratio * 100.0,
+			decode
+		);
+	}
+	if !not_worth.is_empty() {
+		println!("  {} storage items would not get any smaller", not_worth.len());
+	}
+
+	Ok(())
+}
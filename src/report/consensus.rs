@@ -0,0 +1,132 @@
+//! Consensus state of the Session, Babe, Grandpa and ImOnline pallets.
+//!
+//! Session keys and their owner records stay in storage until the validator purges them, even long
+//! after it stopped validating. Heartbeats of past sessions should be pruned as well.
+
+use super::{key_account, storage_entry, value_account, Collected, Decoder, RawEntry};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use parity_scale_codec::Decode;
+use scale_value::ValueDef;
+use std::collections::BTreeSet;
+use subxt_metadata::{StorageEntryMetadata, StorageEntryType};
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	println!("Consensus state:");
+	let print_stale = |name: &str, entries: &[RawEntry], stale: &[&RawEntry], reason: &str| {
+		println!(
+			"  {}: {} entries ({}), {} {} ({})",
+			name,
+			entries.len(),
+			fmt_bytes(total_size(entries), false, units),
+			stale.len(),
+			reason,
+			fmt_bytes(total_size(stale.iter().copied()), false, units)
+		);
+	};
+
+	// Accounts that validate or at least intend to.
+	let mut validating = BTreeSet::new();
+	let validators = plain_value(collected, decoder, "Session", "Validators");
+	if let Some(ValueDef::Composite(validators)) = validators.as_ref().map(|v| &v.value) {
+		validating.extend(validators.values().filter_map(value_account));
+	}
+	let num_validators = validating.len();
+	if let Ok(entry) = storage_entry(meta, "Staking", "Validators") {
+		let entries = collected.entries("Staking", "Validators");
+		validating.extend(entries.iter().filter_map(|(key, _)| key_account(entry, key, meta)));
+	}
+	let session =
+		plain_value(collected, decoder, "Session", "CurrentIndex").and_then(|v| v.as_u128());
+
+	if storage_entry(meta, "Session", "Validators").is_ok() {
+		let session = session.map_or("unknown".into(), |s| s.to_string());
+		println!("  Session: {} validators in session {}", num_validators, session);
+	}
+
+	if let Ok(entry) = storage_entry(meta, "Session", "NextKeys") {
+		let entries = collected.entries("Session", "NextKeys");
+		let stale = entries
+			.iter()
+			.filter(|(key, _)| {
+				key_account(entry, key, meta).is_some_and(|a| !validating.contains(&a))
+			})
+			.collect::<Vec<_>>();
+		print_stale("Session::NextKeys", entries, &stale, "of accounts that stopped validating");
+	}
+
+	if storage_entry(meta, "Session", "KeyOwner").is_ok() {
+		let entries = collected.entries("Session", "KeyOwner");
+		let stale = entries
+			.iter()
+			.filter(|(_, value)| {
+				let owner = decoder.decode("Session", "KeyOwner", value).ok();
+				owner.and_then(|o| value_account(&o)).is_some_and(|a| !validating.contains(&a))
+			})
+			.collect::<Vec<_>>();
+		let reason = "owned by accounts that stopped validating";
+		print_stale("Session::KeyOwner", entries, &stale, reason);
+	}
+
+	if storage_entry(meta, "Babe", "EpochIndex").is_ok() {
+		let epoch = plain_value(collected, decoder, "Babe", "EpochIndex").and_then(|v| v.as_u128());
+		let segments = collected.entries("Babe", "UnderConstruction");
+		println!(
+			"  Babe: epoch {}, {} randomness segments under construction ({})",
+			epoch.map_or("unknown".into(), |e| e.to_string()),
+			segments.len(),
+			fmt_bytes(total_size(segments), false, units)
+		);
+	}
+
+	if storage_entry(meta, "Grandpa", "CurrentSetId").is_ok() {
+		let set_id =
+			plain_value(collected, decoder, "Grandpa", "CurrentSetId").and_then(|v| v.as_u128());
+		let sessions = collected.entries("Grandpa", "SetIdSession");
+		println!(
+			"  Grandpa: set {}, {} sessions of authority sets recorded ({})",
+			set_id.map_or("unknown".into(), |s| s.to_string()),
+			sessions.len(),
+			fmt_bytes(total_size(sessions), false, units)
+		);
+	}
+
+	if let Ok(entry) = storage_entry(meta, "ImOnline", "ReceivedHeartbeats") {
+		let entries = collected.entries("ImOnline", "ReceivedHeartbeats");
+		let stale = entries
+			.iter()
+			.filter(|(key, _)| {
+				let heartbeat = first_key::<u32>(entry, key).map(u128::from);
+				heartbeat.zip(session).is_some_and(|(heartbeat, now)| heartbeat < now)
+			})
+			.collect::<Vec<_>>();
+		print_stale("ImOnline::ReceivedHeartbeats", entries, &stale, "of past sessions");
+	}
+
+	Ok(())
+}
+
+/// Combined size of the keys and values.
+fn total_size<'a>(entries: impl IntoIterator<Item = &'a RawEntry>) -> usize {
+	entries.into_iter().map(|(key, value)| key.len() + value.len()).sum()
+}
+
+/// The decoded value of a storage value.
+fn plain_value(
+	collected: &Collected,
+	decoder: &Decoder,
+	pallet: &str,
+	item: &str,
+) -> Option<scale_value::Value<u32>> {
+	let (_, value) = collected.entries(pallet, item).first()?;
+	decoder.decode(pallet, item, value).ok()
+}
+
+/// The first key of a storage map entry, if its hasher keeps the key.
+fn first_key<T: Decode>(entry: &StorageEntryMetadata, key: &[u8]) -> Option<T> {
+	let StorageEntryType::Map { hashers, .. } = entry.entry_type() else { return None };
+	let hasher = hashers.first().filter(|h| h.ends_with_key())?;
+
+	T::decode(&mut key.get(32 + hasher.len_excluding_key()..)?).ok()
+}
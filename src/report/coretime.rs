@@ -0,0 +1,158 @@
+//! Region inventory and bookkeeping of the Broker pallet on the Coretime chains.
+//!
+//! Regions stay in storage after their last timeslice passed, until someone calls `drop_region`.
+//! The same holds for renewals, pool contributions and pool history with their own drop calls.
+
+use super::{key_value, storage_entry, warn, Collected, Decoder};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use scale_value::{At, ValueDef};
+use std::collections::BTreeMap as Map;
+
+/// Number of cores to show the regions of.
+const TOP_CORES: usize = 10;
+
+/// Storage maps of the Broker pallet that are only counted.
+///
+/// Renewals were called `AllowedRenewals` before they became `PotentialRenewals`.
+const COUNTED_ITEMS: &[&str] = &[
+	"Workplan",
+	"Workload",
+	"PotentialRenewals",
+	"AllowedRenewals",
+	"InstaPoolContribution",
+	"InstaPoolIo",
+	"InstaPoolHistory",
+];
+
+/// Storage items of the Broker pallet that the report decodes or counts, as `(pallet, item)`.
+pub const ITEMS: &[(&str, &str)] = &[
+	("Broker", "Status"),
+	("Broker", "Regions"),
+	("Broker", "Leases"),
+	("Broker", "Reservations"),
+	("Broker", "Workplan"),
+	("Broker", "Workload"),
+	("Broker", "PotentialRenewals"),
+	("Broker", "AllowedRenewals"),
+	("Broker", "InstaPoolContribution"),
+	("Broker", "InstaPoolIo"),
+	("Broker", "InstaPoolHistory"),
+];
+
+/// Regions that start on the same core.
+#[derive(Default)]
+struct Core {
+	regions: usize,
+	size: usize,
+	/// First and last timeslice that the regions cover.
+	begin: u128,
+	end: u128,
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let Ok(regions_entry) = storage_entry(meta, "Broker", "Regions") else {
+		println!("Coretime: the runtime has no Broker pallet");
+		return Ok(())
+	};
+	let status = collected
+		.entries("Broker", "Status")
+		.first()
+		.and_then(|(_, value)| decoder.decode("Broker", "Status", value).ok());
+	let now = status.as_ref().and_then(|s| s.at("last_committed_timeslice")?.as_u128());
+	let cores = status.as_ref().and_then(|s| s.at("core_count")?.as_u128());
+	match (now, cores) {
+		(Some(now), Some(cores)) => println!("Coretime at timeslice {} with {} cores:", now, cores),
+		// The sales were not started yet.
+		_ => println!("Coretime before the start of the sales:"),
+	}
+	let mut undecodable = 0;
+
+	let mut by_core = Map::<u128, Core>::new();
+	let (mut owned, mut expired, mut expired_size) = (0, 0, 0);
+	let entries = collected.entries("Broker", "Regions");
+	for (key, value) in entries {
+		let size = key.len() + value.len();
+		let id = key_value(regions_entry, key, meta);
+		let (Some(id), Ok(record)) = (id, decoder.decode("Broker", "Regions", value)) else {
+			undecodable += 1;
+			continue
+		};
+		let begin = id.at("begin").and_then(|b| b.as_u128()).unwrap_or_default();
+		let end = record.at("end").and_then(|e| e.as_u128()).unwrap_or_default();
+		let core = id.at("core").and_then(|c| c.as_u128()).unwrap_or_default();
+
+		if let Some(ValueDef::Variant(owner)) = record.at("owner").map(|o| &o.value) {
+			owned += usize::from(owner.name == "Some");
+		}
+		if now.is_some_and(|now| end <= now) {
+			expired += 1;
+			expired_size += size;
+		}
+		let core = by_core.entry(core).or_insert(Core { begin, end, ..Default::default() });
+		core.regions += 1;
+		core.size += size;
+		core.begin = core.begin.min(begin);
+		core.end = core.end.max(end);
+	}
+
+	let size = entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+	println!(
+		"  Broker::Regions: {} regions on {} cores ({}), {} owned, {} expired ({}) that drop_region removes",
+		entries.len(),
+		by_core.len(),
+		fmt_bytes(size, false, units),
+		owned,
+		expired,
+		fmt_bytes(expired_size, false, units)
+	);
+	for (index, core) in by_core.iter().sorted_by_key(|(_, c)| c.regions).rev().take(TOP_CORES) {
+		println!(
+			"    Core {}: {} regions from timeslice {} to {} ({})",
+			index,
+			core.regions,
+			core.begin,
+			core.end,
+			fmt_bytes(core.size, false, units)
+		);
+	}
+	if by_core.len() > TOP_CORES {
+		println!("    ... and {} more", by_core.len() - TOP_CORES);
+	}
+
+	for (item, kind) in [("Leases", "leases"), ("Reservations", "reservations")] {
+		let Some((key, value)) = collected.entries("Broker", item).first() else { continue };
+		let Ok(decoded) = decoder.decode("Broker", item, value) else {
+			undecodable += 1;
+			continue
+		};
+		let count = match &decoded.value {
+			ValueDef::Composite(composite) => composite.len(),
+			_ => 0,
+		};
+		let size = fmt_bytes(key.len() + value.len(), false, units);
+		println!("  Broker::{}: {} {} ({})", item, count, kind, size);
+	}
+
+	for item in COUNTED_ITEMS {
+		if storage_entry(meta, "Broker", item).is_err() {
+			continue
+		}
+		let entries = collected.entries("Broker", item);
+		let size = entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+		println!(
+			"  Broker::{}: {} entries ({})",
+			item,
+			entries.len(),
+			fmt_bytes(size, false, units)
+		);
+	}
+
+	if undecodable > 0 {
+		warn(format!("Could not decode {} Broker entries", undecodable));
+	}
+
+	Ok(())
+}
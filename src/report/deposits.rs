@@ -0,0 +1,177 @@
+//! Storage deposits by the accounts that hold them.
+//!
+//! Pallets like Identity, Multisig, Proxy and Preimage reserve a deposit from whoever creates an
+//! entry. Adding them up per account shows who funds the state.
+
+use super::{
+	find_composites, fmt_account, key_account, ss58_prefix, storage_entry, value_account, warn,
+	Collected, Decoder,
+};
+use anyhow::Result;
+use itertools::Itertools;
+use scale_info::TypeDef;
+use scale_value::{At, Composite, Value, ValueDef};
+use std::collections::{BTreeMap as Map, BTreeSet};
+use subxt::Metadata;
+
+/// Fields that hold a deposit, either as amount or as `(AccountId, Balance)` ticket.
+const DEPOSIT_FIELDS: &[&str] = &["deposit", "ticket", "maybe_ticket"];
+
+/// Storage items whose values are tuples without field names, with the index of the deposit.
+///
+/// Their deposit is held from the account in the key.
+const TUPLE_DEPOSITS: &[(&str, &str, usize)] =
+	&[("Proxy", "Proxies", 1), ("Proxy", "Announcements", 1), ("Identity", "SubsOf", 0)];
+
+/// Number of accounts to show in the leaderboard.
+const TOP_DEPOSITORS: usize = 20;
+
+/// Storage items that hold deposits, as `(pallet, item)`.
+pub fn items(meta: &Metadata) -> Vec<(String, String)> {
+	let mut items = Vec::new();
+
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			let tuple =
+				TUPLE_DEPOSITS.iter().any(|(p, i, _)| *p == pallet.name() && *i == entry.name());
+			let value_ty = entry.entry_type().value_ty();
+
+			if tuple || has_deposit_field(value_ty, meta, &mut BTreeSet::new()) {
+				items.push((pallet.name().to_string(), entry.name().to_string()));
+			}
+		}
+	}
+
+	items
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder) -> Result<()> {
+	let meta = decoder.meta();
+	let mut by_account = Map::<[u8; 32], (u128, BTreeSet<String>)>::new();
+	let mut by_item = Vec::new();
+	let mut undecodable = 0;
+
+	for (pallet, item) in items(meta) {
+		let entries = collected.entries(&pallet, &item);
+		let entry = storage_entry(meta, &pallet, &item)?;
+		let name = format!("{}::{}", pallet, item);
+		let tuple_index =
+			TUPLE_DEPOSITS.iter().find(|(p, i, _)| *p == pallet && *i == item).map(|(_, _, i)| *i);
+		let mut total = 0;
+
+		for (key, value) in entries {
+			let Ok(decoded) = decoder.decode(&pallet, &item, value) else {
+				undecodable += 1;
+				continue
+			};
+			let key_account = key_account(entry, key, meta);
+
+			let deposits = match tuple_index {
+				Some(index) => decoded
+					.at(index)
+					.and_then(|d| d.as_u128())
+					.and_then(|amount| Some((key_account?, amount)))
+					.into_iter()
+					.collect(),
+				None => find_deposits(&decoded, key_account),
+			};
+			for (account, amount) in deposits {
+				let (held, items) = by_account.entry(account).or_default();
+				*held += amount;
+				items.insert(name.clone());
+				total += amount;
+			}
+		}
+
+		if !entries.is_empty() {
+			by_item.push((name, total, entries.len()));
+		}
+	}
+
+	let total = by_item.iter().map(|(_, total, _)| total).sum::<u128>();
+	println!(
+		"Deposits: {} held by {} accounts in {} storage items",
+		total,
+		by_account.len(),
+		by_item.len()
+	);
+	if undecodable > 0 {
+		warn(format!("Could not decode {} entries that hold deposits", undecodable));
+	}
+
+	for (name, total, entries) in by_item.iter().sorted_by_key(|(_, total, _)| *total).rev() {
+		println!("  {}: {} in {} entries", name, total, entries);
+	}
+
+	if !by_account.is_empty() {
+		println!("  Top depositors:");
+	}
+	let prefix = ss58_prefix(meta);
+	for (account, (held, items)) in
+		by_account.iter().sorted_by_key(|(_, (held, _))| *held).rev().take(TOP_DEPOSITORS)
+	{
+		let items = items.iter().join(", ");
+		println!("    {} {} ({})", fmt_account(account, prefix), held, items);
+	}
+
+	Ok(())
+}
+
+/// All deposits in a decoded value as `(depositor, amount)`.
+///
+/// A plain amount is held from the `depositor` next to it or else from the account in the key.
+fn find_deposits(value: &Value<u32>, key_account: Option<[u8; 32]>) -> Vec<([u8; 32], u128)> {
+	let mut found = Vec::new();
+
+	for field in DEPOSIT_FIELDS {
+		for composite in find_composites(value, field) {
+			let Some(deposit) = composite.at(*field) else { continue };
+
+			match deposit.as_u128() {
+				Some(amount) => {
+					let depositor = composite.at("depositor").and_then(value_account).or(key_account);
+					found.extend(depositor.map(|d| (d, amount)));
+				},
+				None => found.extend(ticket(deposit)),
+			}
+		}
+	}
+
+	found
+}
+
+/// Interpret a decoded value as `(AccountId, Balance)` ticket, possibly wrapped in an `Option`.
+fn ticket(value: &Value<u32>) -> Option<([u8; 32], u128)> {
+	match &value.value {
+		ValueDef::Variant(variant) if variant.name == "Some" =>
+			ticket(variant.values.values().exactly_one().ok()?),
+		ValueDef::Composite(Composite::Unnamed(fields)) if fields.len() == 2 =>
+			Some((value_account(&fields[0])?, fields[1].as_u128()?)),
+		_ => None,
+	}
+}
+
+/// Whether a type contains a field that holds a deposit.
+fn has_deposit_field(ty: u32, meta: &Metadata, seen: &mut BTreeSet<u32>) -> bool {
+	// Types can be recursive.
+	if !seen.insert(ty) {
+		return false
+	}
+	let Some(resolved) = meta.types().resolve(ty) else { return false };
+
+	let mut check = |fields: &[scale_info::Field<scale_info::form::PortableForm>]| {
+		fields.iter().any(|f| {
+			f.name.as_ref().is_some_and(|n| DEPOSIT_FIELDS.contains(&n.as_str())) ||
+				has_deposit_field(f.ty.id, meta, seen)
+		})
+	};
+
+	match &resolved.type_def {
+		TypeDef::Composite(composite) => check(&composite.fields),
+		TypeDef::Variant(variant) => variant.variants.iter().any(|v| check(&v.fields)),
+		TypeDef::Sequence(seq) => has_deposit_field(seq.type_param.id, meta, seen),
+		TypeDef::Array(array) => has_deposit_field(array.type_param.id, meta, seen),
+		TypeDef::Tuple(tuple) => tuple.fields.iter().any(|f| has_deposit_field(f.id, meta, seen)),
+		_ => false,
+	}
+}
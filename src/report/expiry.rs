@@ -0,0 +1,123 @@
+//! Vesting schedules and balance locks that expired but still occupy storage.
+//!
+//! A vesting schedule is expired once all of its funds are unlocked, but it stays in storage until
+//! someone calls `vest`. The same goes for the `vesting` balance lock of such an account. Locks of
+//! zero amount do not lock anything either.
+
+use super::{
+	block_number, field_u128, find_composites, key_account, storage_entry, value_bytes, warn,
+	Collected, Decoder,
+};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use scale_value::{At, Value};
+use std::collections::BTreeSet;
+
+const VESTING_ID: &[u8] = b"vesting ";
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let now = block_number(collected, decoder)?;
+	println!("Expired state at block {}:", now);
+
+	let vesting = storage_entry(meta, "Vesting", "Vesting");
+	let mut still_vesting = BTreeSet::new();
+	let mut undecodable = 0;
+
+	if let Ok(entry) = vesting {
+		let (mut schedules, mut expired_schedules) = (0, 0);
+		let (mut expired_entries, mut reclaimable) = (0, 0);
+		let entries = collected.entries("Vesting", "Vesting");
+
+		for (key, value) in entries {
+			let Ok(decoded) = decoder.decode("Vesting", "Vesting", value) else {
+				// Better assume that it is still vesting than to call its lock stale.
+				still_vesting.extend(key_account(entry, key, meta));
+				undecodable += 1;
+				continue
+			};
+			let found = find_composites(&decoded, "per_block");
+			let expired = found.iter().filter(|s| is_expired(s, now)).count();
+
+			schedules += found.len();
+			expired_schedules += expired;
+			// An entry without schedules was not understood, rather than being fully vested.
+			if !found.is_empty() && expired == found.len() {
+				expired_entries += 1;
+				reclaimable += key.len() + value.len();
+			} else if let Some(account) = key_account(entry, key, meta) {
+				still_vesting.insert(account);
+			}
+		}
+
+		println!(
+			"  Vesting: {} of {} schedules expired, {} of {} accounts fully vested ({} reclaimable)",
+			expired_schedules,
+			schedules,
+			expired_entries,
+			entries.len(),
+			fmt_bytes(reclaimable, false, units)
+		);
+	}
+
+	if let Ok(entry) = storage_entry(meta, "Balances", "Locks") {
+		let (mut locks, mut stale_locks) = (0, 0);
+		let (mut stale_entries, mut reclaimable) = (0, 0);
+		let entries = collected.entries("Balances", "Locks");
+
+		for (key, value) in entries {
+			let Ok(decoded) = decoder.decode("Balances", "Locks", value) else {
+				undecodable += 1;
+				continue
+			};
+			let account = key_account(entry, key, meta);
+			let found = find_composites(&decoded, "amount");
+			let stale = found
+				.iter()
+				.filter(|lock| {
+					let amount = field_u128(lock, &["amount"]).unwrap_or_default();
+					let id = lock.at("id").and_then(value_bytes).unwrap_or_default();
+					// Without the Vesting pallet the lock cannot be judged.
+					let vested = vesting.is_ok() &&
+						id == VESTING_ID && account.is_some_and(|a| !still_vesting.contains(&a));
+
+					amount == 0 || vested
+				})
+				.count();
+
+			locks += found.len();
+			stale_locks += stale;
+			if !found.is_empty() && stale == found.len() {
+				stale_entries += 1;
+				reclaimable += key.len() + value.len();
+			}
+		}
+
+		println!(
+			"  Locks: {} of {} locks are zero or for expired vesting, {} of {} accounts only have such locks ({} reclaimable)",
+			stale_locks,
+			locks,
+			stale_entries,
+			entries.len(),
+			fmt_bytes(reclaimable, false, units)
+		);
+	}
+
+	if undecodable > 0 {
+		warn(format!("Could not decode {} Vesting or Locks entries", undecodable));
+	}
+
+	Ok(())
+}
+
+/// Whether a vesting schedule unlocked all of its funds at block `now`.
+fn is_expired(schedule: &Value<u32>, now: u128) -> bool {
+	let locked = field_u128(schedule, &["locked"]).unwrap_or_default();
+	let per_block = field_u128(schedule, &["per_block"]).unwrap_or_default();
+	let start = field_u128(schedule, &["starting_block"]).unwrap_or_default();
+
+	if per_block == 0 {
+		return locked == 0
+	}
+	start + locked.div_ceil(per_block) <= now
+}
@@ -0,0 +1,738 @@
+//! Occurrences of an account, code hash, integer or any other bytes in the keys and values of the
+//! snapshot.
+//!
+//! Values often do not contain the account by itself but wrapped in an enum, like `Option` or
+//! `MultiAddress`. The byte in front of a match tells which one it likely was. The sovereign
+//! accounts of other chains are derived from their location like `xcm_builder` does.
+//!
+//! A code hash is also looked up in the storage of code blobs, since any other occurrence pins the
+//! code that it refers to.
+//!
+//! The entries that contain the subject are printed with their keys and a preview of their values
+//! decoded by the metadata, next to their hex.
+
+use super::{
+	decode_keys, fmt_account, preview, ss58_prefix, storage_entry, storage_prefix, Collected,
+	Decoder,
+};
+use crate::{fmt_bytes, index, next_prefix, Units};
+use anyhow::{anyhow, Result};
+use base58::FromBase58;
+use itertools::Itertools;
+use parity_scale_codec::{Compact, Encode};
+use polkadot_du::snapshot::{SnapshotReader, SnapshotWriter};
+use serde_json::json;
+use sp_crypto_hashing::{blake2_256, blake2_512, twox_128};
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap as Map, HashSet},
+	fmt, fs,
+};
+use subxt::Metadata;
+
+/// Storage maps of code blobs keyed by their hash.
+const CODE_ITEMS: &[(&str, &str)] =
+	&[("Paras", "CodeByHash"), ("Contracts", "PristineCode"), ("Revive", "PristineCode")];
+
+/// What to search for.
+#[derive(Clone)]
+pub enum Subject {
+	Account([u8; 32]),
+	/// Any of the accounts that are listed in a file.
+	Accounts(String, Vec<[u8; 32]>),
+	/// Hash of a runtime, parachain validation code or contract code.
+	CodeHash([u8; 32]),
+	/// Sovereign account of another chain.
+	Sovereign(Location),
+	/// Arbitrary bytes.
+	Hex(Vec<u8>),
+	/// A SCALE encoded integer, like a para or asset ID.
+	Integer(Integer),
+	/// All keys that start with these bytes, like those of a pallet or storage item.
+	///
+	/// The entries are read from the snapshot when they are shown instead of being kept.
+	Prefix(Vec<u8>, String),
+}
+
+impl Subject {
+	/// The encodings of the subject, any of which is a match.
+	fn patterns(&self) -> Vec<Cow<'_, [u8]>> {
+		match self {
+			Subject::Account(bytes) | Subject::CodeHash(bytes) => vec![Cow::Borrowed(&bytes[..])],
+			Subject::Accounts(_, accounts) =>
+				accounts.iter().map(|account| Cow::Borrowed(&account[..])).collect(),
+			Subject::Sovereign(location) => vec![Cow::Owned(location.account().to_vec())],
+			Subject::Hex(bytes) => vec![Cow::Borrowed(&bytes[..])],
+			Subject::Integer(integer) => vec![Cow::Owned(integer.encode())],
+			Subject::Prefix(..) => Vec::new(),
+		}
+	}
+
+	/// The prefix of the keys, if the subject is one.
+	pub fn prefix(&self) -> Option<&[u8]> {
+		match self {
+			Subject::Prefix(prefix, _) => Some(prefix),
+			_ => None,
+		}
+	}
+
+	/// Whether the subject is an account, which can be wrapped in a `MultiAddress`.
+	fn is_account(&self) -> bool {
+		matches!(self, Subject::Account(_) | Subject::Accounts(..) | Subject::Sovereign(_))
+	}
+
+	/// Derive the sovereign account of a parachain with another prefix, see `--raw-prefix`.
+	pub fn with_raw_prefix(self, prefix: &RawPrefix) -> Result<Self> {
+		match self {
+			Subject::Sovereign(
+				Location::Child(id) | Location::Sibling(id) | Location::Prefixed(_, id),
+			) => Ok(Subject::Sovereign(Location::Prefixed(prefix.0.clone(), id))),
+			_ => Err(anyhow!("A raw prefix only applies to Para(..) and Sibling(..)")),
+		}
+	}
+}
+
+/// A chain whose sovereign account is searched for.
+#[derive(Clone)]
+pub enum Location {
+	/// A parachain as seen from its relay chain.
+	Child(u32),
+	/// A parachain as seen from another parachain.
+	Sibling(u32),
+	/// The relay chain as seen from a parachain.
+	Parent,
+	/// A parachain whose account is derived with another prefix than `para` or `sibl`.
+	Prefixed(Vec<u8>, u32),
+}
+
+impl Location {
+	/// The sovereign account, which is the encoded location padded with zeros.
+	fn account(&self) -> [u8; 32] {
+		let encoded = match self {
+			Location::Child(id) => (b"para", id).encode(),
+			Location::Sibling(id) => (b"sibl", id).encode(),
+			Location::Parent => b"Parent".to_vec(),
+			Location::Prefixed(prefix, id) => [&prefix[..], &id.encode()].concat(),
+		};
+
+		let mut account = [0; 32];
+		account[..encoded.len()].copy_from_slice(&encoded);
+		account
+	}
+}
+
+impl fmt::Display for Location {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Location::Child(id) => write!(f, "Para({})", id),
+			Location::Sibling(id) => write!(f, "Sibling({})", id),
+			Location::Parent => write!(f, "Parent"),
+			Location::Prefixed(prefix, id) => write!(f, "0x{}({})", hex::encode(prefix), id),
+		}
+	}
+}
+
+/// An integer with the type that it is encoded as.
+#[derive(Clone, Copy)]
+pub enum Integer {
+	U32(u32),
+	U64(u64),
+	U128(u128),
+	Compact(u128),
+}
+
+impl Integer {
+	fn encode(self) -> Vec<u8> {
+		match self {
+			Integer::U32(n) => n.encode(),
+			Integer::U64(n) => n.encode(),
+			Integer::U128(n) => n.encode(),
+			Integer::Compact(n) => Compact(n).encode(),
+		}
+	}
+}
+
+impl fmt::Display for Integer {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Integer::U32(n) => write!(f, "U32({})", n),
+			Integer::U64(n) => write!(f, "U64({})", n),
+			Integer::U128(n) => write!(f, "U128({})", n),
+			Integer::Compact(n) => write!(f, "Compact({})", n),
+		}
+	}
+}
+
+/// Prefix to derive sovereign accounts with instead of `para` or `sibl`.
+#[derive(Clone)]
+pub struct RawPrefix(Vec<u8>);
+
+/// Parse a raw prefix from hex. It must leave room for the para ID within the account.
+pub fn parse_raw_prefix(s: &str) -> Result<RawPrefix, String> {
+	let prefix = hex::decode(s.strip_prefix("0x").unwrap_or(s)).map_err(|e| e.to_string())?;
+	if prefix.len() > 28 {
+		return Err(format!("Raw prefix must be at most 28 bytes, not {}", prefix.len()))
+	}
+	Ok(RawPrefix(prefix))
+}
+
+/// How an account was encoded where it was found.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Context {
+	/// Part of the storage key, like the key of a map.
+	Key,
+	/// The whole value.
+	Value,
+	/// After a `0x00` byte, like `MultiAddress::Id`.
+	MultiAddressId,
+	/// After a `0x01` byte, like `Option::Some`.
+	OptionSome,
+	/// Somewhere else within the value.
+	Embedded,
+	/// At the start of the key.
+	Prefix,
+}
+
+impl Context {
+	fn describe(self) -> &'static str {
+		match self {
+			Context::Key => "in key",
+			Context::Value => "as value",
+			Context::MultiAddressId => "after 0x00 (MultiAddress::Id)",
+			Context::OptionSome => "after 0x01 (Option::Some)",
+			Context::Embedded => "embedded in value",
+			Context::Prefix => "by prefix",
+		}
+	}
+}
+
+/// Parse a code hash like `CodeHash(0x..)`, a location like `Para(2000)`, `Sibling(2000)` or
+/// `Parent`, bytes like `Hex(0x..)`, an integer like `U32(2000)` or `Compact(2000)`, a file of
+/// accounts like `Accounts(path)`, a prefix like `Prefix(Pallet)`, `Prefix(Pallet::Item)` or
+/// `Prefix(0x..)`, or an account.
+pub fn parse_subject(s: &str) -> Result<Subject, String> {
+	let argument = |name: &str| s.strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')');
+	let para_id = |id: &str| id.trim().parse::<u32>().map_err(|_| format!("{} is no para ID", id));
+	let number = |n: &str| n.trim().parse::<u128>().map_err(|_| format!("{} is no integer", n));
+	let narrow = |n: u128, max: u128| {
+		(n <= max).then_some(n).ok_or_else(|| format!("{} is too large for {}", n, s))
+	};
+
+	if let Some(hash) = argument("CodeHash") {
+		let hash = hash.strip_prefix("0x").ok_or("Code hashes must be hex")?;
+		let bytes = hex::decode(hash).map_err(|e| e.to_string())?;
+		return Ok(Subject::CodeHash(bytes.try_into().map_err(|_| "Code hashes must be 32 bytes")?))
+	}
+	if let Some(id) = argument("Para") {
+		return Ok(Subject::Sovereign(Location::Child(para_id(id)?)))
+	}
+	if let Some(id) = argument("Sibling") {
+		return Ok(Subject::Sovereign(Location::Sibling(para_id(id)?)))
+	}
+	if s == "Parent" {
+		return Ok(Subject::Sovereign(Location::Parent))
+	}
+	if let Some(hex) = argument("Hex") {
+		let bytes =
+			hex::decode(hex.strip_prefix("0x").unwrap_or(hex)).map_err(|e| e.to_string())?;
+		if bytes.is_empty() {
+			return Err("Hex(..) needs at least one byte".into())
+		}
+		return Ok(Subject::Hex(bytes))
+	}
+	if let Some(n) = argument("U32") {
+		return Ok(Subject::Integer(Integer::U32(narrow(number(n)?, u32::MAX as u128)? as u32)))
+	}
+	if let Some(n) = argument("U64") {
+		return Ok(Subject::Integer(Integer::U64(narrow(number(n)?, u64::MAX as u128)? as u64)))
+	}
+	if let Some(n) = argument("U128") {
+		return Ok(Subject::Integer(Integer::U128(number(n)?)))
+	}
+	if let Some(n) = argument("Compact") {
+		return Ok(Subject::Integer(Integer::Compact(number(n)?)))
+	}
+	if let Some(prefix) = argument("Prefix") {
+		let bytes = match (prefix.strip_prefix("0x"), prefix.split_once("::")) {
+			(Some(hex), _) => hex::decode(hex).map_err(|e| e.to_string())?,
+			(None, Some((pallet, item))) => storage_prefix(pallet, item),
+			(None, None) => twox_128(prefix.as_bytes()).to_vec(),
+		};
+		return Ok(Subject::Prefix(bytes, prefix.into()))
+	}
+	if let Some(path) = argument("Accounts") {
+		return parse_accounts(path).map(|accounts| Subject::Accounts(path.into(), accounts))
+	}
+
+	parse_account(s).map(Subject::Account)
+}
+
+/// Parse an account, a location, bytes or a prefix like [`parse_subject`], but nothing that reads
+/// a file, since the subject comes from a remote client.
+pub fn parse_address(s: &str) -> Result<Subject, String> {
+	// Checked before parsing, so that the file is never opened.
+	if s.starts_with("Accounts(") {
+		return Err("Files of accounts can only be searched with --find".into())
+	}
+	let subject = parse_subject(s)?;
+	match subject {
+		Subject::Account(_) | Subject::Sovereign(_) | Subject::Hex(_) | Subject::Prefix(..) =>
+			Ok(subject),
+		_ => Err(format!("{} is no account, location, Hex(..) or Prefix(..)", s)),
+	}
+}
+
+/// Parse a file with one SS58 address or hex account per line.
+fn parse_accounts(path: &str) -> Result<Vec<[u8; 32]>, String> {
+	let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+	let accounts = text
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty())
+		.map(parse_account)
+		.collect::<Result<Vec<_>, _>>()?;
+	if accounts.is_empty() {
+		return Err(format!("{} lists no accounts", path))
+	}
+	Ok(accounts)
+}
+
+/// Parse an account from an SS58 address of any network or from 32 bytes of hex.
+fn parse_account(s: &str) -> Result<[u8; 32], String> {
+	if let Some(hex) = s.strip_prefix("0x") {
+		let bytes = hex::decode(hex).map_err(|e| e.to_string())?;
+		return bytes.try_into().map_err(|_| "Hex accounts must be 32 bytes".into())
+	}
+
+	let data = s.from_base58().map_err(|_| format!("{} is neither SS58 nor hex", s))?;
+	// One or two bytes of network prefix, 32 bytes of account and two bytes of checksum.
+	let prefix_len = if data.first().is_some_and(|b| b & 0b0100_0000 != 0) { 2 } else { 1 };
+	if data.len() != prefix_len + 32 + 2 {
+		return Err(format!("{} is not the SS58 address of a 32 byte account", s))
+	}
+
+	let checksum = blake2_512(&[&b"SS58PRE"[..], &data[..prefix_len + 32]].concat());
+	if checksum[..2] != data[prefix_len + 32..] {
+		return Err(format!("{} has an invalid SS58 checksum", s))
+	}
+	Ok(data[prefix_len..prefix_len + 32].try_into().expect("Slice has length 32; qed"))
+}
+
+/// An entry that contains the subject, with all places where it does.
+///
+/// Only the key is kept, since broad subjects can match a large part of the snapshot. The value is
+/// read again when the entry is shown.
+pub struct Found {
+	pub key: Vec<u8>,
+	pub contexts: Vec<Context>,
+}
+
+/// The encodings of a subject by their length, so that each window of an entry is looked up once
+/// instead of compared to every encoding.
+pub struct Matcher {
+	patterns: Vec<(usize, HashSet<Vec<u8>>)>,
+	prefix: Option<Vec<u8>>,
+	account: bool,
+}
+
+impl Matcher {
+	pub fn new(subject: &Subject) -> Self {
+		let mut patterns = Map::<usize, HashSet<Vec<u8>>>::new();
+		for pattern in subject.patterns() {
+			patterns.entry(pattern.len()).or_default().insert(pattern.into_owned());
+		}
+		Self {
+			patterns: patterns.into_iter().collect(),
+			prefix: subject.prefix().map(<[u8]>::to_vec),
+			account: subject.is_account(),
+		}
+	}
+
+	/// All places where the subject occurs in a Key-Value pair.
+	pub fn scan(&self, key: &[u8], value: &[u8]) -> Vec<Context> {
+		if let Some(prefix) = &self.prefix {
+			return if key.starts_with(prefix) { vec![Context::Prefix] } else { Vec::new() }
+		}
+		let mut found = Vec::new();
+		for (len, patterns) in &self.patterns {
+			// Each encoding in the key counts once.
+			let in_key =
+				key.windows(*len).filter(|w| patterns.contains(*w)).collect::<HashSet<_>>();
+			found.extend(in_key.iter().map(|_| Context::Key));
+
+			for (offset, window) in value.windows(*len).enumerate() {
+				if !patterns.contains(window) {
+					continue
+				}
+				let context = match offset.checked_sub(1).map(|i| value[i]) {
+					None if value.len() == *len => Context::Value,
+					Some(0) if self.account => Context::MultiAddressId,
+					Some(1) => Context::OptionSome,
+					_ => Context::Embedded,
+				};
+				found.push(context);
+			}
+		}
+
+		found
+	}
+}
+
+/// Whether the entry stores the code blob of the code hash, like the runtime itself in `:code`.
+pub fn is_code_blob(code_hash: &[u8; 32], key: &[u8], value: &[u8]) -> bool {
+	if key == b":code" {
+		return blake2_256(value) == *code_hash
+	}
+
+	key.ends_with(code_hash) &&
+		CODE_ITEMS.iter().any(|(pallet, item)| key.starts_with(&storage_prefix(pallet, item)))
+}
+
+/// How to show the entries that contain the subject.
+pub struct Show<'a> {
+	/// Snapshot that the values of the entries are read from again, with the identity to decrypt
+	/// it. Without one, only the number of matches is shown.
+	pub snapshot: Option<(&'a str, Option<&'a str>)>,
+	pub units: Units,
+	/// Show the entries only as hex.
+	pub raw: bool,
+	/// Number of characters of a value to show, as hex and decoded.
+	pub width: usize,
+}
+
+/// Print the entries that were found, or those under the prefix as they are read again.
+pub fn print(
+	collected: &Collected,
+	decoder: &Decoder,
+	subject: &Subject,
+	show: &Show,
+) -> Result<()> {
+	let meta = decoder.meta();
+	let names = item_names(meta);
+
+	if let Some(prefix) = subject.prefix() {
+		println!("Entries under {}:", describe(subject, meta));
+		let (mut entries, mut key_len, mut value_len) = (0, 0, 0);
+		prefixed(reread_from(show)?, prefix, |found, value| {
+			entries += 1;
+			key_len += found.key.len();
+			value_len += value.len();
+			print_entry(&found, &value, &names, decoder, show)
+		})?;
+		println!(
+			"{} entries with {} of keys and {} of values",
+			entries,
+			fmt_bytes(key_len, false, show.units),
+			fmt_bytes(value_len, false, show.units)
+		);
+		return Ok(())
+	}
+
+	let mut by_item = Map::<String, Map<Context, usize>>::new();
+	for found in &collected.found {
+		let name = item_name(&names, &found.key);
+		for context in &found.contexts {
+			*by_item.entry(name.clone()).or_default().entry(*context).or_default() += 1;
+		}
+	}
+
+	let total = by_item.values().flat_map(|c| c.values()).sum::<usize>();
+	let subject = describe(subject, meta);
+	println!("Found {} {} times:", subject, total);
+	for (prefix, size) in &collected.code_blobs {
+		let name = match &prefix[..] {
+			b":code" => ":code".into(),
+			prefix => item_name(&names, prefix),
+		};
+		println!("  Code blob of {} in {}", fmt_bytes(*size, false, show.units), name);
+	}
+	for (name, contexts) in by_item {
+		println!("  {}: {}", name, fmt_contexts(contexts));
+	}
+
+	if collected.found.is_empty() {
+		return Ok(())
+	}
+	let Some(snapshot) = show.snapshot else {
+		println!("The entries can only be shown when they are read from a snapshot file.");
+		return Ok(())
+	};
+	println!();
+	println!("Entries with {}:", subject);
+	reread(snapshot, &collected.found, |found, value| {
+		print_entry(found, &value, &names, decoder, show)
+	})
+}
+
+/// Print an entry with its size and hex, and its decoded key and value unless `raw`. Values are
+/// cut off after `width` characters.
+fn print_entry(
+	found: &Found,
+	value: &[u8],
+	names: &Map<Vec<u8>, (String, String)>,
+	decoder: &Decoder,
+	show: &Show,
+) -> Result<()> {
+	let contexts = found.contexts.iter().copied().counts().into_iter().sorted();
+	println!("  {}: {}", item_name(names, &found.key), fmt_contexts(contexts));
+	let decoded = if show.raw { None } else { decode(&found.key, value, names, decoder)? };
+	println!("    Key:   0x{}", hex::encode(&found.key));
+	if let Some((key, _)) = &decoded {
+		println!("           {}", key);
+	}
+	println!(
+		"    Size:  {} key, {} value",
+		fmt_bytes(found.key.len(), false, show.units),
+		fmt_bytes(value.len(), false, show.units)
+	);
+	println!("    Value: 0x{}", preview(&hex::encode(value), show.width));
+	if let Some((_, value)) = &decoded {
+		println!("           {}", preview(value, show.width));
+	}
+	Ok(())
+}
+
+/// Write the entries that contain the subject to a JSON file, decoded unless `raw`.
+pub fn save(
+	path: &str,
+	collected: &Collected,
+	decoder: &Decoder,
+	subject: &Subject,
+	show: &Show,
+) -> Result<()> {
+	let names = item_names(decoder.meta());
+	let snapshot = reread_from(show)?;
+	let mut entries = Vec::new();
+	match subject.prefix() {
+		Some(prefix) => prefixed(snapshot, prefix, |found, value| {
+			entries.push(to_json(&found, &value, &names, decoder, show.raw)?);
+			Ok(())
+		})?,
+		None => reread(snapshot, &collected.found, |found, value| {
+			entries.push(to_json(found, &value, &names, decoder, show.raw)?);
+			Ok(())
+		})?,
+	}
+
+	let json = json!({ "subject": describe(subject, decoder.meta()), "entries": entries });
+	fs::write(path, serde_json::to_string_pretty(&json)?)
+		.map_err(|e| anyhow!("Failed to write {}: {}", path, e))
+}
+
+/// Read the snapshot again for all entries that contain the subject, as JSON like [`save`] writes
+/// it.
+pub fn grep(
+	snapshot: (&str, Option<&str>),
+	subject: &Subject,
+	decoder: &Decoder,
+	raw: bool,
+) -> Result<serde_json::Value> {
+	let names = item_names(decoder.meta());
+	let mut entries = Vec::new();
+	let mut each = |found: &Found, value: Vec<u8>| -> Result<()> {
+		entries.push(to_json(found, &value, &names, decoder, raw)?);
+		Ok(())
+	};
+
+	match subject.prefix() {
+		Some(prefix) => prefixed(snapshot, prefix, |found, value| each(&found, value))?,
+		None => {
+			let (path, identity) = snapshot;
+			let matcher = Matcher::new(subject);
+			let mut reader = SnapshotReader::open_encrypted(path, identity)?;
+			for entry in &mut reader {
+				let (key, (value, _)) = entry?;
+				let contexts = matcher.scan(&key, &value);
+				if !contexts.is_empty() {
+					each(&Found { key, contexts }, value)?;
+				}
+			}
+			reader.finish()?;
+		},
+	}
+
+	Ok(json!({ "subject": describe(subject, decoder.meta()), "entries": entries }))
+}
+
+/// An entry with the places of the subject, and its decoded key and value unless `raw`.
+fn to_json(
+	found: &Found,
+	value: &[u8],
+	names: &Map<Vec<u8>, (String, String)>,
+	decoder: &Decoder,
+	raw: bool,
+) -> Result<serde_json::Value> {
+	let mut entry = json!({
+		"item": item_name(names, &found.key),
+		"key": format!("0x{}", hex::encode(&found.key)),
+		"value": format!("0x{}", hex::encode(value)),
+		"matches": found.contexts.iter().map(|context| context.describe()).collect_vec(),
+	});
+	let decoded = if raw { None } else { decode(&found.key, value, names, decoder)? };
+	if let Some((key, value)) = decoded {
+		entry["decoded_key"] = key.into();
+		entry["decoded_value"] = value.into();
+	}
+	Ok(entry)
+}
+
+/// The snapshot to read the values of the entries from again.
+fn reread_from<'a>(show: &Show<'a>) -> Result<(&'a str, Option<&'a str>)> {
+	show.snapshot
+		.ok_or_else(|| anyhow!("The entries can only be read again from a snapshot file"))
+}
+
+/// Read the entries under a prefix from the snapshot and pass them on one by one, instead of
+/// keeping them all.
+fn prefixed(
+	snapshot: (&str, Option<&str>),
+	prefix: &[u8],
+	mut each: impl FnMut(Found, Vec<u8>) -> Result<()>,
+) -> Result<()> {
+	let (path, identity) = snapshot;
+	let mut reader = index::open_range(path, identity, prefix.to_vec(), next_prefix(prefix))?;
+	for entry in &mut reader {
+		let (key, (value, _)) = entry?;
+		each(Found { key, contexts: vec![Context::Prefix] }, value)?;
+	}
+	Ok(reader.finish()?)
+}
+
+/// Read the values of the found entries from the snapshot again and pass them on by key.
+///
+/// Reads only the range from the first to the last found key, in one pass.
+fn reread(
+	snapshot: (&str, Option<&str>),
+	found: &[Found],
+	mut each: impl FnMut(&Found, Vec<u8>) -> Result<()>,
+) -> Result<()> {
+	let by_key = found.iter().map(|found| (&found.key[..], found)).collect::<Map<_, _>>();
+	let (Some((first, _)), Some((last, _))) = (by_key.first_key_value(), by_key.last_key_value())
+	else {
+		return Ok(())
+	};
+	let (path, identity) = snapshot;
+	// The smallest key after the last one.
+	let end = [last, &[0][..]].concat();
+	let mut reader = index::open_range(path, identity, first.to_vec(), Some(end))?;
+	for entry in &mut reader {
+		let (key, (value, _)) = entry?;
+		if let Some(found) = by_key.get(&key[..]) {
+			each(found, value)?;
+		}
+	}
+	Ok(reader.finish()?)
+}
+
+/// The decoded key and value of an entry, if its storage item is in the metadata.
+fn decode(
+	key: &[u8],
+	value: &[u8],
+	names: &Map<Vec<u8>, (String, String)>,
+	decoder: &Decoder,
+) -> Result<Option<(String, String)>> {
+	let Some((pallet, item)) = key.get(..32).and_then(|prefix| names.get(prefix)) else {
+		return Ok(None)
+	};
+	let meta = decoder.meta();
+	let entry = storage_entry(meta, pallet, item)?;
+	let key = decode_keys(entry.entry_type(), &key[32..], meta);
+	let value = match decoder.decode(pallet, item, value) {
+		Ok(value) => value.to_string(),
+		Err(e) => format!("({})", e),
+	};
+	Ok(Some((key, value)))
+}
+
+/// How often the subject occurred in which context, like `2 in key, 1 as value`.
+fn fmt_contexts(counts: impl IntoIterator<Item = (Context, usize)>) -> String {
+	counts
+		.into_iter()
+		.map(|(context, n)| format!("{} {}", n, context.describe()))
+		.join(", ")
+}
+
+/// Copy all entries that contain the subject into a snapshot at `out`, eg. to replay them.
+///
+/// Writes a manifest of why each entry matched next to it as `<out>.json`. The storage root and
+/// header are copied from the snapshot, so the root does not match the copied entries.
+pub fn export(
+	snapshot: &str,
+	identity: Option<&str>,
+	out: &str,
+	subject: &Subject,
+	meta: &Metadata,
+) -> Result<()> {
+	let names = item_names(meta);
+	let matcher = Matcher::new(subject);
+	let mut reader = SnapshotReader::open_encrypted(snapshot, identity)?;
+	let mut writer = SnapshotWriter::create(out, reader.state_version())?;
+	let mut manifest = Vec::new();
+
+	for entry in &mut reader {
+		let (key, (value, ref_count)) = entry?;
+		let mut reasons =
+			matcher.scan(&key, &value).into_iter().map(Context::describe).collect_vec();
+		if let Subject::CodeHash(code_hash) = subject {
+			if is_code_blob(code_hash, &key, &value) {
+				reasons.push("code blob");
+			}
+		}
+		if reasons.is_empty() {
+			continue
+		}
+
+		let name = match &key[..] {
+			b":code" => ":code".into(),
+			key => item_name(&names, key),
+		};
+		manifest.push(json!({
+			"key": format!("0x{}", hex::encode(&key)),
+			"item": name,
+			"matches": reasons,
+		}));
+		writer.write(&(key, (value, ref_count)))?;
+	}
+
+	writer.finish(&reader.into_trailer()?)?;
+	let json = json!({ "subject": describe(subject, meta), "entries": manifest });
+	fs::write(format!("{}.json", out), serde_json::to_string_pretty(&json)?)?;
+	println!("Exported {} entries with the subject to {}", manifest.len(), out);
+	Ok(())
+}
+
+/// Pallet and name of all storage items by their storage prefix.
+fn item_names(meta: &Metadata) -> Map<Vec<u8>, (String, String)> {
+	let mut names = Map::new();
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			let prefix = [twox_128(pallet.name().as_bytes()), twox_128(entry.name().as_bytes())];
+			names.insert(prefix.concat(), (pallet.name().to_string(), entry.name().to_string()));
+		}
+	}
+	names
+}
+
+/// Name of the storage item of a key as `Pallet::Item`.
+fn item_name(names: &Map<Vec<u8>, (String, String)>, key: &[u8]) -> String {
+	match key.get(..32).and_then(|prefix| names.get(prefix)) {
+		Some((pallet, item)) => format!("{}::{}", pallet, item),
+		None => "Unknown".into(),
+	}
+}
+
+fn describe(subject: &Subject, meta: &Metadata) -> String {
+	match subject {
+		Subject::Account(account) => fmt_account(account, ss58_prefix(meta)),
+		Subject::Accounts(path, accounts) => format!("{} accounts of {}", accounts.len(), path),
+		Subject::CodeHash(hash) => format!("code hash 0x{}", hex::encode(hash)),
+		Subject::Sovereign(location) => format!(
+			"sovereign account of {} {}",
+			location,
+			fmt_account(&location.account(), ss58_prefix(meta))
+		),
+		Subject::Hex(bytes) => format!("0x{}", hex::encode(bytes)),
+		Subject::Integer(integer) => format!("{} 0x{}", integer, hex::encode(integer.encode())),
+		Subject::Prefix(_, name) => name.clone(),
+	}
+}
@@ -0,0 +1,179 @@
+//! Governance state of the Referenda, ConvictionVoting, Treasury and Bounties pallets.
+//!
+//! Finished referenda keep their info until the deposits are refunded and votes on them stay until
+//! the voters remove them. A class lock is expired once no vote or prior lock of that account and
+//! class needs it anymore, but it stays until someone calls `unlock`.
+
+use super::{
+	account_key_offset, block_number, find_composites, key_account, storage_entry, warn, Collected,
+	Decoder,
+};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use parity_scale_codec::Decode;
+use scale_value::{At, Value, ValueDef};
+use std::collections::{BTreeMap as Map, BTreeSet};
+use subxt::Metadata;
+use subxt_metadata::{StorageEntryMetadata, StorageEntryType};
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let now = block_number(collected, decoder)?;
+	println!("Governance state at block {}:", now);
+	let mut undecodable = 0;
+
+	for (pallet, item, status) in [
+		("Referenda", "ReferendumInfoFor", &[][..]),
+		("Treasury", "Spends", &["status"][..]),
+		("Bounties", "Bounties", &["status"][..]),
+		("ChildBounties", "ChildBounties", &["status"][..]),
+	] {
+		if storage_entry(meta, pallet, item).is_err() {
+			continue
+		}
+		let mut states = Map::<String, (usize, usize)>::new();
+
+		for (key, value) in collected.entries(pallet, item) {
+			let state = decoder
+				.decode(pallet, item, value)
+				.ok()
+				.and_then(|v| variant_name(&v, status))
+				.unwrap_or_else(|| {
+					undecodable += 1;
+					"Undecodable".into()
+				});
+			let (count, size) = states.entry(state).or_default();
+			*count += 1;
+			*size += key.len() + value.len();
+		}
+
+		let states = states
+			.iter()
+			.sorted_by_key(|(_, (count, _))| *count)
+			.rev()
+			.map(|(state, (count, size))| {
+				format!("{} {} ({})", count, state, fmt_bytes(*size, false, units))
+			})
+			.join(", ");
+		let states = if states.is_empty() { "none".into() } else { states };
+		println!("  {}::{}: {}", pallet, item, states);
+	}
+
+	if storage_entry(meta, "Treasury", "Proposals").is_ok() {
+		let entries = collected.entries("Treasury", "Proposals");
+		let size = entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+		println!("  Treasury::Proposals: {} ({})", entries.len(), fmt_bytes(size, false, units));
+	}
+
+	if let Ok(entry) = storage_entry(meta, "ConvictionVoting", "VotingFor") {
+		// Accounts and classes whose lock is still needed.
+		let mut needed = BTreeSet::new();
+		let (mut votes, mut size) = (0, 0);
+		let entries = collected.entries("ConvictionVoting", "VotingFor");
+
+		for (key, value) in entries {
+			size += key.len() + value.len();
+			let (account, class) = (key_account(entry, key, meta), voting_class(entry, key, meta));
+			let Ok(decoded) = decoder.decode("ConvictionVoting", "VotingFor", value) else {
+				// Better assume that the lock is needed than to call it expired.
+				needed.insert((account, class));
+				undecodable += 1;
+				continue
+			};
+			let casted = find_composites(&decoded, "votes")
+				.iter()
+				.filter_map(|c| c.at("votes"))
+				.map(num_values)
+				.sum::<usize>();
+			let delegating = !find_composites(&decoded, "target").is_empty();
+			let prior_locked = find_composites(&decoded, "prior").iter().any(|c| {
+				let until = c.at("prior").at(0).and_then(|v| v.as_u128()).unwrap_or_default();
+				let amount = c.at("prior").at(1).and_then(|v| v.as_u128()).unwrap_or_default();
+				amount > 0 && until > now
+			});
+
+			votes += casted;
+			if casted > 0 || delegating || prior_locked {
+				needed.insert((account, class));
+			}
+		}
+		println!(
+			"  ConvictionVoting::VotingFor: {} votes in {} entries ({})",
+			votes,
+			entries.len(),
+			fmt_bytes(size, false, units)
+		);
+
+		if let Ok(locks_entry) = storage_entry(meta, "ConvictionVoting", "ClassLocksFor") {
+			let (mut locks, mut expired_locks) = (0, 0);
+			let (mut expired_entries, mut reclaimable) = (0, 0);
+			let entries = collected.entries("ConvictionVoting", "ClassLocksFor");
+
+			for (key, value) in entries {
+				let Ok(decoded) = decoder.decode("ConvictionVoting", "ClassLocksFor", value) else {
+					undecodable += 1;
+					continue
+				};
+				let account = key_account(locks_entry, key, meta);
+				let classes = match &decoded.value {
+					ValueDef::Composite(locks) => locks
+						.values()
+						.map(|lock| lock.at(0).and_then(|c| c.as_u128()).map(|c| c as u16))
+						.collect::<Vec<_>>(),
+					_ => Vec::new(),
+				};
+				let expired = classes.iter().filter(|c| !needed.contains(&(account, **c))).count();
+
+				locks += classes.len();
+				expired_locks += expired;
+				if expired == classes.len() {
+					expired_entries += 1;
+					reclaimable += key.len() + value.len();
+				}
+			}
+
+			println!(
+				"  ConvictionVoting::ClassLocksFor: {} of {} class locks expired, {} of {} accounts only have such locks ({} reclaimable)",
+				expired_locks,
+				locks,
+				expired_entries,
+				entries.len(),
+				fmt_bytes(reclaimable, false, units)
+			);
+		}
+	}
+
+	if undecodable > 0 {
+		warn(format!("Could not decode {} governance entries", undecodable));
+	}
+
+	Ok(())
+}
+
+/// Name of the enum variant at the end of a path of field names.
+fn variant_name(value: &Value<u32>, path: &[&str]) -> Option<String> {
+	let value = path.iter().try_fold(value, |v, field| v.at(*field))?;
+
+	match &value.value {
+		ValueDef::Variant(variant) => Some(variant.name.clone()),
+		_ => None,
+	}
+}
+
+/// Number of values in a decoded vector or struct.
+fn num_values(value: &Value<u32>) -> usize {
+	match &value.value {
+		ValueDef::Composite(composite) => composite.len(),
+		_ => 0,
+	}
+}
+
+/// The voting class in the second key of a `ConvictionVoting::VotingFor` entry.
+fn voting_class(entry: &StorageEntryMetadata, key: &[u8], meta: &Metadata) -> Option<u16> {
+	let StorageEntryType::Map { hashers, .. } = entry.entry_type() else { return None };
+	let hasher = hashers.get(1).filter(|h| h.ends_with_key())?;
+	let offset = account_key_offset(entry, meta)? + 32 + hasher.len_excluding_key();
+
+	u16::decode(&mut key.get(offset..)?).ok()
+}
@@ -0,0 +1,134 @@
+//! Whether historical sessions and era-keyed staking maps are pruned as configured.
+//!
+//! `pallet_staking` clears the era information older than `HistoryDepth` eras and the slashes and
+//! historical sessions of eras that are no longer bonded. Misconfigured constants or a stuck
+//! pruning keep them forever.
+
+use super::{
+	retention::is_index,
+	storage_entry,
+	sub_keys::{FirstKey, SubKey},
+	warn, Collected, Decoder,
+};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use parity_scale_codec::Decode;
+use scale_value::At;
+use std::collections::BTreeMap as Map;
+use subxt::Metadata;
+
+/// Historical sessions of `pallet_session`, keyed by the session index.
+const SESSIONS: (&str, &str) = ("Historical", "HistoricalSessions");
+
+/// The constant of `pallet_staking` that limits how many eras an era-keyed map keeps.
+fn depth_of(item: &str) -> Option<&'static str> {
+	if item.starts_with("Eras") || item == "ClaimedRewards" {
+		Some("HistoryDepth")
+	} else if item.ends_with("SlashInEra") {
+		Some("BondingDuration")
+	} else {
+		None
+	}
+}
+
+/// The era- and session-keyed maps as `(pallet, item, first key)`.
+pub fn items(meta: &Metadata) -> Vec<(String, String, FirstKey)> {
+	let staking = meta.pallet_by_name("Staking").and_then(|p| p.storage());
+	let staking = staking.map_or(&[][..], |s| s.entries()).iter();
+	let sessions = storage_entry(meta, SESSIONS.0, SESSIONS.1).ok();
+
+	staking
+		.filter(|entry| depth_of(entry.name()).is_some())
+		.map(|entry| ("Staking", entry))
+		.chain(sessions.map(|entry| (SESSIONS.0, entry)))
+		.filter_map(|(pallet, entry)| {
+			let first_key = FirstKey::of(entry, meta)
+				.filter(|k| k.hasher.ends_with_key() && is_index(k.ty, meta))?;
+			Some((pallet.to_string(), entry.name().to_string(), first_key))
+		})
+		.collect()
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let constant = |name| {
+		meta.pallet_by_name("Staking")
+			.and_then(|p| p.constant_by_name(name))
+			.and_then(|c| u32::decode(&mut c.value()).ok())
+			.map(u128::from)
+	};
+	let current_era = collected
+		.entries("Staking", "CurrentEra")
+		.first()
+		.and_then(|(_, value)| u32::decode(&mut &value[..]).ok())
+		.map(u128::from);
+	let active_era = collected
+		.entries("Staking", "ActiveEra")
+		.first()
+		.and_then(|(_, value)| decoder.decode("Staking", "ActiveEra", value).ok())
+		.and_then(|info| info.at("index")?.as_u128())
+		.or(current_era);
+
+	match current_era {
+		Some(era) => println!("History depth at era {}:", era),
+		None => println!("History depth: Staking::CurrentEra is not in the snapshot"),
+	}
+
+	// Era information is cleared once the planned era is more than `HistoryDepth` ahead and slashes
+	// once the active era is more than `BondingDuration` ahead.
+	for (name, eras) in &collected.retention {
+		let Some(("Staking", item)) = name.split_once("::") else { continue };
+		let Some(depth_name) = depth_of(item) else { continue };
+		let now = if depth_name == "HistoryDepth" { current_era } else { active_era };
+		let (Some(now), Some(depth)) = (now, constant(depth_name)) else { continue };
+
+		print_range(name, "eras", eras, units);
+		let stale = eras.range(..now.saturating_sub(depth)).map(|(_, sub_key)| sub_key);
+		report_stale(name, "eras", stale, &format!("beyond a {} of {}", depth_name, depth), units);
+	}
+
+	// Historical sessions are pruned up to the start of the first bonded era.
+	let name = format!("{}::{}", SESSIONS.0, SESSIONS.1);
+	if let Some(sessions) = collected.retention.get(name.as_str()) {
+		print_range(&name, "sessions", sessions, units);
+		let first_bonded = collected
+			.entries("Staking", "BondedEras")
+			.first()
+			.and_then(|(_, value)| Vec::<(u32, u32)>::decode(&mut &value[..]).ok())
+			.and_then(|eras| eras.first().map(|(_, session)| *session as u128));
+		if let Some(first) = first_bonded {
+			let stale = sessions.range(..first).map(|(_, sub_key)| sub_key);
+			report_stale(&name, "sessions", stale, "before the first bonded era", units);
+		}
+	}
+
+	Ok(())
+}
+
+fn print_range(name: &str, unit: &str, indices: &Map<u128, SubKey>, units: Units) {
+	let (Some(first), Some(last)) = (indices.keys().next(), indices.keys().last()) else { return };
+	let size = indices.values().map(|s| s.size).sum::<usize>();
+	println!(
+		"  {}: {} {} from {} to {}, {}",
+		name,
+		indices.len(),
+		unit,
+		first,
+		last,
+		fmt_bytes(size, false, units)
+	);
+}
+
+fn report_stale<'a>(
+	name: &str,
+	unit: &str,
+	stale: impl Iterator<Item = &'a SubKey>,
+	reason: &str,
+	units: Units,
+) {
+	let (count, size) = stale.fold((0, 0), |(count, size), s| (count + 1, size + s.size));
+	if count > 0 {
+		let size = fmt_bytes(size, false, units);
+		warn(format!("{} keeps {} {} with {} {}", name, count, unit, size, reason));
+	}
+}
@@ -0,0 +1,101 @@
+//! Entries of storage maps with multiple keys, grouped by their leading keys for `--expand-keys`.
+//!
+//! The tree then breaks down items like `Staking::ErasStakersPaged` by era, and with a larger
+//! depth by the keys that follow.
+
+use super::sub_keys::{fmt_key, FirstKey};
+use scale_info::TypeDef;
+use std::collections::BTreeMap as Map;
+use subxt::Metadata;
+use subxt_metadata::StorageEntryType;
+
+/// Entries that share their leading keys.
+#[derive(Default)]
+pub struct KeyGroup {
+	/// The last of the shared keys, decoded if the hasher allows it.
+	pub label: String,
+	pub entries: usize,
+	pub key_len: usize,
+	pub value_len: usize,
+	/// Groups by the next key, by its hashed form.
+	pub children: Map<Vec<u8>, KeyGroup>,
+}
+
+impl KeyGroup {
+	/// Account an entry to this group and to the groups of its keys, as far as they are expanded.
+	///
+	/// The key starts after the storage prefix.
+	pub fn record(
+		&mut self,
+		key: &[u8],
+		keys: &[FirstKey],
+		key_len: usize,
+		value_len: usize,
+		meta: &Metadata,
+	) {
+		let mut group = self;
+		let mut rest = key;
+		group.add(key_len, value_len);
+
+		for first_key in keys {
+			let Some(hashed) = first_key.split(rest, meta) else { return };
+			rest = &rest[hashed.len()..];
+			group = group.children.entry(hashed.to_vec()).or_insert_with(|| KeyGroup {
+				label: fmt_key(hashed, first_key, meta),
+				..Default::default()
+			});
+			group.add(key_len, value_len);
+		}
+	}
+
+	pub fn merge(&mut self, other: KeyGroup) {
+		if self.label.is_empty() {
+			self.label = other.label;
+		}
+		self.entries += other.entries;
+		self.key_len += other.key_len;
+		self.value_len += other.value_len;
+		for (key, other) in other.children {
+			self.children.entry(key).or_default().merge(other);
+		}
+	}
+
+	fn add(&mut self, key_len: usize, value_len: usize) {
+		self.entries += 1;
+		self.key_len += key_len;
+		self.value_len += value_len;
+	}
+}
+
+/// The storage maps with multiple keys as `(pallet, item, keys)`, with the keys to expand.
+///
+/// The last key of a map is never expanded, since every entry would be a group of its own.
+pub fn items(meta: &Metadata, depth: usize) -> Vec<(String, String, Vec<FirstKey>)> {
+	let mut items = Vec::new();
+
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			let StorageEntryType::Map { hashers, key_ty, .. } = entry.entry_type() else {
+				continue
+			};
+			if hashers.len() < 2 {
+				continue
+			}
+			// Multiple hashers mean that the key type is a tuple of all keys.
+			let Some(TypeDef::Tuple(tuple)) = meta.types().resolve(*key_ty).map(|t| &t.type_def)
+			else {
+				continue
+			};
+			let keys = hashers
+				.iter()
+				.zip(&tuple.fields)
+				.take(depth.min(hashers.len() - 1))
+				.map(|(hasher, ty)| FirstKey { hasher: *hasher, ty: ty.id })
+				.collect();
+
+			items.push((pallet.name().to_string(), entry.name().to_string(), keys));
+		}
+	}
+
+	items
+}
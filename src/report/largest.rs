@@ -0,0 +1,65 @@
+//! The largest entries of each storage item for `--top`.
+//!
+//! Aggregated sizes tell which item is large, but not whether a few entries dominate it, like a
+//! single overfull `Scheduler::Agenda`. Only entries that make it into the top are compressed.
+
+use super::compression;
+use std::cmp::Reverse;
+
+/// A single large entry of a storage item.
+#[derive(Clone)]
+pub struct LargeEntry {
+	pub key: Vec<u8>,
+	pub value_len: usize,
+	/// Length of the value once compressed, including its length prefix.
+	pub compressed_len: usize,
+}
+
+impl LargeEntry {
+	pub fn size(&self) -> usize {
+		self.key.len() + self.value_len
+	}
+
+	/// Larger entries rank higher, and ties by their key so that the workers do not matter.
+	fn rank(&self) -> (usize, Reverse<&[u8]>) {
+		(self.size(), Reverse(&self.key[..]))
+	}
+}
+
+/// The largest entries of a storage item, the largest first.
+#[derive(Clone, Default)]
+pub struct Largest {
+	/// Number of entries to keep.
+	top: usize,
+	pub entries: Vec<LargeEntry>,
+}
+
+impl Largest {
+	/// Keep the entry if it is one of the `top` largest so far.
+	pub fn record(&mut self, key: &[u8], value: &[u8], top: usize) {
+		self.top = top;
+		let rank = (key.len() + value.len(), Reverse(key));
+		if self.entries.len() >= top && self.entries.last().is_some_and(|e| e.rank() > rank) {
+			return
+		}
+
+		self.insert(LargeEntry {
+			key: key.to_vec(),
+			value_len: value.len(),
+			compressed_len: compression::compressed_len(value),
+		});
+	}
+
+	pub fn merge(&mut self, other: Largest) {
+		self.top = self.top.max(other.top);
+		for entry in other.entries {
+			self.insert(entry);
+		}
+	}
+
+	fn insert(&mut self, entry: LargeEntry) {
+		let position = self.entries.partition_point(|e| e.rank() > entry.rank());
+		self.entries.insert(position, entry);
+		self.entries.truncate(self.top);
+	}
+}
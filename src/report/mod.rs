@@ -0,0 +1,982 @@
+//! Reports that decode the values of specific storage items.
+//!
+//! The workers collect the raw entries that the selected reports need while categorizing the
+//! snapshot. The reports then decode them with the type information from the metadata.
+
+mod bags;
+mod bounds;
+mod bridges;
+mod compression;
+mod consensus;
+mod coretime;
+mod deposits;
+mod expiry;
+mod find;
+mod governance;
+mod history;
+mod key_groups;
+mod largest;
+mod paras;
+mod people;
+mod privileges;
+mod pruning;
+mod reaping;
+mod references;
+mod retention;
+mod shapes;
+mod sub_keys;
+mod system_retention;
+
+use crate::Units;
+use anyhow::{anyhow, Result};
+use base58::ToBase58;
+use clap::ValueEnum;
+use itertools::Itertools;
+use parity_scale_codec::Decode;
+use polkadot_du::child;
+use scale_info::TypeDef;
+use scale_value::{At, Composite, Value, ValueDef};
+use sp_crypto_hashing::{blake2_128, blake2_512, twox_128, twox_64};
+use std::{
+	collections::{BTreeMap as Map, BTreeSet},
+	sync::Arc,
+};
+use subxt::Metadata;
+use subxt_metadata::{StorageEntryMetadata, StorageEntryType, StorageHasher};
+
+/// An additional report that decodes storage values.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Report {
+	/// Accounts without any balance that are still kept alive by references.
+	Reaping,
+	/// Vesting schedules and balance locks that expired but still occupy storage.
+	Expiry,
+	/// Storage deposits aggregated by the accounts that hold them.
+	Deposits,
+	/// Referenda, votes, class locks, treasury proposals and bounties.
+	Governance,
+	/// Session keys, Babe and Grandpa state and heartbeats, with keys of former validators.
+	Consensus,
+	/// Values that are longer than the maximal encoded length of their type.
+	Bounds,
+	/// Storage items that would save the most space by compressing their values.
+	AdviseCompression,
+	/// Entries of storage maps with multiple keys, by their first key.
+	SubKeys,
+	/// Block hashes, events and event topics that `frame_system` should have pruned.
+	SystemRetention,
+	/// Relay chain storage of parachains that are no longer registered, with cleanup calls.
+	InactiveParas,
+	/// Pallets whose values contain accounts or hashes that are keys in other pallets.
+	References,
+	/// Nodes per bag of the bags list, with orphaned nodes and the effect of rebagging.
+	BagsList,
+	/// The sudo key and the members and pending proposals of collectives like the council.
+	Privileges,
+	/// Historical sessions and era-keyed staking maps that are kept longer than configured.
+	HistoryDepth,
+	/// Unreferenced and shared entries, with the disk that state pruning would reclaim.
+	Pruning,
+	/// Identities of the People chains by the registrars that judged them, with subs and usernames.
+	People,
+	/// Regions of the Coretime chains by core, with the expired ones and the Broker bookkeeping.
+	Coretime,
+	/// Queued messages and lanes of the bridges of the BridgeHubs, with their imported headers.
+	Bridges,
+	/// Shapes of the keys and values of storage that is not in the metadata, guessed from samples.
+	UnknownShapes,
+}
+
+impl Report {
+	/// Storage items whose entries this report decodes, as `(pallet, item)`.
+	fn items(self) -> &'static [(&'static str, &'static str)] {
+		match self {
+			Report::Reaping => &[("System", "Account")],
+			Report::Expiry =>
+				&[("System", "Number"), ("Vesting", "Vesting"), ("Balances", "Locks")],
+			// Depends on the metadata, see `deposits::items`.
+			Report::Deposits => &[],
+			Report::Governance => &[
+				("System", "Number"),
+				("Referenda", "ReferendumInfoFor"),
+				("ConvictionVoting", "VotingFor"),
+				("ConvictionVoting", "ClassLocksFor"),
+				("Treasury", "Proposals"),
+				("Treasury", "Spends"),
+				("Bounties", "Bounties"),
+				("ChildBounties", "ChildBounties"),
+			],
+			Report::Consensus => &[
+				("Session", "Validators"),
+				("Session", "CurrentIndex"),
+				("Session", "NextKeys"),
+				("Session", "KeyOwner"),
+				("Staking", "Validators"),
+				("Babe", "EpochIndex"),
+				("Babe", "UnderConstruction"),
+				("Grandpa", "CurrentSetId"),
+				("Grandpa", "SetIdSession"),
+				("ImOnline", "ReceivedHeartbeats"),
+			],
+			Report::SystemRetention => &[
+				("System", "Number"),
+				("System", "BlockHash"),
+				("System", "Events"),
+				("System", "EventCount"),
+				("System", "EventTopics"),
+			],
+			// Depends on the name of the pallet, see `bags::items`.
+			Report::BagsList => &[],
+			// Depends on the collectives in the metadata, see `privileges::items`.
+			Report::Privileges => &[],
+			// The era- and session-keyed maps are counted per index, see `history::items`.
+			Report::HistoryDepth =>
+				&[("Staking", "CurrentEra"), ("Staking", "ActiveEra"), ("Staking", "BondedEras")],
+			// The para-keyed items depend on the metadata, see `paras::items`.
+			Report::InactiveParas => &[("Paras", "CodeByHashRefs")],
+			Report::People => people::ITEMS,
+			Report::Coretime => coretime::ITEMS,
+			// Depends on the bridges in the metadata, see `bridges::items`.
+			Report::Bridges => &[],
+			// Checked by the workers without keeping any entries, see `Collector::record`.
+			Report::Bounds |
+			Report::AdviseCompression |
+			Report::SubKeys |
+			Report::References |
+			Report::Pruning |
+			Report::UnknownShapes => &[],
+		}
+	}
+
+	/// Whether this report needs to know which storage maps are keyed by which accounts.
+	fn needs_account_refs(self) -> bool {
+		matches!(self, Report::Reaping)
+	}
+
+	pub fn print(self, collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+		match self {
+			Report::Reaping => reaping::print(collected, decoder),
+			Report::Expiry => expiry::print(collected, decoder, units),
+			Report::Deposits => deposits::print(collected, decoder),
+			Report::Governance => governance::print(collected, decoder, units),
+			Report::Consensus => consensus::print(collected, decoder, units),
+			Report::Bounds => bounds::print(collected, decoder.meta()),
+			Report::AdviseCompression => compression::print(collected, units),
+			Report::SubKeys => sub_keys::print(collected, decoder, units),
+			Report::SystemRetention => system_retention::print(collected, decoder, units),
+			Report::InactiveParas => paras::print(collected, decoder, units),
+			Report::References => references::print(collected),
+			Report::BagsList => bags::print(collected, decoder, units),
+			Report::Privileges => privileges::print(collected, decoder, units),
+			Report::HistoryDepth => history::print(collected, decoder, units),
+			Report::Pruning => pruning::print(collected, decoder, units),
+			Report::People => people::print(collected, decoder, units),
+			Report::Coretime => coretime::print(collected, decoder, units),
+			Report::Bridges => bridges::print(collected, decoder, units),
+			Report::UnknownShapes => shapes::print(collected, decoder.meta(), units),
+		}
+	}
+}
+
+pub use find::{
+	grep, parse_address, parse_raw_prefix, parse_subject, RawPrefix, Show as FoundShow, Subject,
+};
+pub use key_groups::KeyGroup;
+pub use largest::{LargeEntry, Largest};
+pub use references::parse_path as parse_graph_path;
+pub use retention::{parse_rule, Rule as RetentionRule};
+
+/// Print where the subject of `--find` occurs and the entries that contain it.
+pub fn print_found(
+	collected: &Collected,
+	decoder: &Decoder,
+	subject: &Subject,
+	show: &FoundShow,
+) -> Result<()> {
+	find::print(collected, decoder, subject, show)
+}
+
+/// Write the entries that contain the subject of `--find` to a JSON file.
+pub fn save_found(
+	path: &str,
+	collected: &Collected,
+	decoder: &Decoder,
+	subject: &Subject,
+	show: &FoundShow,
+) -> Result<()> {
+	find::save(path, collected, decoder, subject, show)
+}
+
+/// Copy the entries that contain the subject of `--find` into a snapshot with a manifest.
+pub fn export_found(
+	snapshot: &str,
+	identity: Option<&str>,
+	out: &str,
+	subject: &Subject,
+	decoder: &Decoder,
+) -> Result<()> {
+	find::export(snapshot, identity, out, subject, decoder.meta())
+}
+
+/// Write the graph of the references between pallets to a `.dot` or `.json` file.
+pub fn save_reference_graph(path: &str, collected: &Collected) -> Result<()> {
+	references::save(path, collected)
+}
+
+/// Print how much storage the retention rules of `--retain` would free.
+pub fn print_retention(
+	collected: &Collected,
+	decoder: &Decoder,
+	rules: &[RetentionRule],
+	units: Units,
+) -> Result<()> {
+	retention::print(collected, rules, decoder.meta(), units)
+}
+
+/// Decides which raw data the workers keep for the selected reports.
+pub struct Collector {
+	/// Storage items whose entries are kept, by their storage prefix.
+	items: Map<Vec<u8>, (String, String)>,
+	/// Storage items whose keys are kept with only the length of their values.
+	sized: Map<Vec<u8>, (String, String)>,
+	/// Storage maps whose first key is an account, by their storage prefix.
+	///
+	/// Contains the name of the map and the offset of the account within the key.
+	account_keyed: Map<Vec<u8>, (Arc<str>, usize)>,
+	/// Account or code hash to search for in all keys and values, with its encodings.
+	find: Option<(Subject, find::Matcher)>,
+	/// Storage items with bounded values, by their storage prefix.
+	///
+	/// Contains the name of the item and the maximal length of its values.
+	bounded: Map<Vec<u8>, (Arc<str>, usize)>,
+	/// Storage items whose values are compressed, by their storage prefix.
+	compressed: Map<Vec<u8>, Arc<str>>,
+	/// Storage items whose entries are counted by their references, by their storage prefix.
+	ref_counted: Map<Vec<u8>, Arc<str>>,
+	/// Storage maps with multiple keys, by their storage prefix.
+	multi_keyed: Map<Vec<u8>, (Arc<str>, sub_keys::FirstKey)>,
+	/// Storage maps that a retention rule applies to, by their storage prefix.
+	retained: Map<Vec<u8>, (Arc<str>, sub_keys::FirstKey)>,
+	/// Storage maps whose entries are grouped by their leading keys, by their storage prefix.
+	expanded: Map<Vec<u8>, (Arc<str>, Vec<sub_keys::FirstKey>)>,
+	/// Storage items whose `top` largest entries are kept, by their storage prefix.
+	ranked: Map<Vec<u8>, Arc<str>>,
+	/// Number of the largest entries to keep per storage item.
+	top: usize,
+	/// Storage maps whose first key is an account or hash, by their storage prefix.
+	///
+	/// Contains the name of the map and the offset of the first key within the key.
+	id_keyed: Map<Vec<u8>, (Arc<str>, usize)>,
+	/// Storage items whose values can contain accounts or hashes, by their storage prefix.
+	///
+	/// Contains the name of the item and the type of its values.
+	id_values: Map<Vec<u8>, (Arc<str>, u32)>,
+	/// Types that are encoded as 32 bytes, like accounts and hashes.
+	id_types: BTreeSet<u32>,
+	/// Storage prefixes of all items in the metadata, to sample the entries of all other keys.
+	known_items: Option<BTreeSet<Vec<u8>>>,
+	/// Key of the genesis hash in `System::BlockHash`, to check the network of the snapshot.
+	genesis_key: Option<Vec<u8>>,
+	/// Needed to decode the first key of storage maps.
+	meta: Metadata,
+}
+
+impl Collector {
+	pub fn new(
+		reports: &[Report],
+		find: Option<Subject>,
+		retain: &[RetentionRule],
+		expand_keys: usize,
+		top: usize,
+		meta: &Metadata,
+	) -> Self {
+		// The block number is always kept to describe where the results come from.
+		let mut items = Map::new();
+		items.insert(storage_prefix("System", "Number"), ("System".into(), "Number".into()));
+		for (pallet, item) in reports.iter().flat_map(|r| r.items()) {
+			items.insert(storage_prefix(pallet, item), (pallet.to_string(), item.to_string()));
+		}
+		if reports.contains(&Report::Deposits) {
+			for (pallet, item) in deposits::items(meta) {
+				items.insert(storage_prefix(&pallet, &item), (pallet, item));
+			}
+		}
+		if reports.contains(&Report::BagsList) {
+			for (pallet, item) in bags::items(meta) {
+				items.insert(storage_prefix(&pallet, &item), (pallet, item));
+			}
+		}
+		if reports.contains(&Report::Privileges) {
+			for (pallet, item) in privileges::items(meta) {
+				items.insert(storage_prefix(&pallet, &item), (pallet, item));
+			}
+		}
+		if reports.contains(&Report::Bridges) {
+			for (pallet, item) in bridges::items(meta) {
+				items.insert(storage_prefix(&pallet, &item), (pallet, item));
+			}
+		}
+		let mut sized = Map::new();
+		if reports.contains(&Report::InactiveParas) {
+			for (pallet, item) in paras::items(meta) {
+				items.insert(storage_prefix(&pallet, &item), (pallet, item));
+			}
+			// Code blobs are too large to keep.
+			let (pallet, item) = paras::CODE_BLOBS;
+			sized.insert(storage_prefix(pallet, item), (pallet.to_string(), item.to_string()));
+		}
+
+		let mut account_keyed = Map::new();
+		if reports.iter().any(|r| r.needs_account_refs()) {
+			for pallet in meta.pallets() {
+				for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+					if pallet.name() == "System" && entry.name() == "Account" {
+						continue
+					}
+					if let Some(offset) = account_key_offset(entry, meta) {
+						let name = format!("{}::{}", pallet.name(), entry.name());
+						let prefix = storage_prefix(pallet.name(), entry.name());
+						account_keyed.insert(prefix, (name.into(), offset));
+					}
+				}
+			}
+		}
+
+		let mut bounded = Map::new();
+		if reports.contains(&Report::Bounds) {
+			for (pallet, item, bound) in bounds::items(meta) {
+				let name = format!("{}::{}", pallet, item);
+				bounded.insert(storage_prefix(&pallet, &item), (name.into(), bound));
+			}
+		}
+
+		let mut compressed = Map::new();
+		if reports.contains(&Report::AdviseCompression) {
+			for pallet in meta.pallets() {
+				for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+					let name = format!("{}::{}", pallet.name(), entry.name());
+					compressed.insert(storage_prefix(pallet.name(), entry.name()), name.into());
+				}
+			}
+		}
+
+		let mut ref_counted = Map::new();
+		if reports.contains(&Report::Pruning) {
+			for pallet in meta.pallets() {
+				for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+					let name = format!("{}::{}", pallet.name(), entry.name());
+					ref_counted.insert(storage_prefix(pallet.name(), entry.name()), name.into());
+				}
+			}
+		}
+
+		let mut multi_keyed = Map::new();
+		if reports.contains(&Report::SubKeys) {
+			for (pallet, item, first_key) in sub_keys::items(meta) {
+				let name = format!("{}::{}", pallet, item);
+				multi_keyed.insert(storage_prefix(&pallet, &item), (name.into(), first_key));
+			}
+		}
+
+		let mut retained = Map::new();
+		let mut history = Vec::new();
+		if reports.contains(&Report::HistoryDepth) {
+			history = history::items(meta);
+		}
+		for (pallet, item, first_key) in retention::items(meta, retain).into_iter().chain(history) {
+			let name = format!("{}::{}", pallet, item);
+			retained.insert(storage_prefix(&pallet, &item), (name.into(), first_key));
+		}
+
+		let mut expanded = Map::new();
+		if expand_keys > 0 {
+			for (pallet, item, keys) in key_groups::items(meta, expand_keys) {
+				let name = format!("{}::{}", pallet, item);
+				expanded.insert(storage_prefix(&pallet, &item), (name.into(), keys));
+			}
+		}
+
+		let mut ranked = Map::new();
+		if top > 0 {
+			for pallet in meta.pallets() {
+				for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+					let name = format!("{}::{}", pallet.name(), entry.name());
+					ranked.insert(storage_prefix(pallet.name(), entry.name()), name.into());
+				}
+			}
+		}
+
+		let mut id_keyed = Map::new();
+		let mut id_values = Map::new();
+		let mut id_types = BTreeSet::new();
+		if reports.contains(&Report::References) {
+			for (pallet, item, offset) in references::keyed_items(meta) {
+				let name = format!("{}::{}", pallet, item);
+				id_keyed.insert(storage_prefix(&pallet, &item), (name.into(), offset));
+			}
+			id_types = references::id_types(meta);
+			for (pallet, item, ty) in references::value_items(meta, &id_types) {
+				let name = format!("{}::{}", pallet, item);
+				id_values.insert(storage_prefix(&pallet, &item), (name.into(), ty));
+			}
+		}
+
+		let mut known_items = None;
+		if reports.contains(&Report::UnknownShapes) {
+			let known = meta.pallets().flat_map(|pallet| {
+				let entries = pallet.storage().map_or(&[][..], |s| s.entries());
+				entries.iter().map(move |entry| storage_prefix(pallet.name(), entry.name()))
+			});
+			known_items = Some(known.collect());
+		}
+
+		Self {
+			items,
+			sized,
+			account_keyed,
+			find: find.map(|subject| {
+				let matcher = find::Matcher::new(&subject);
+				(subject, matcher)
+			}),
+			bounded,
+			compressed,
+			ref_counted,
+			multi_keyed,
+			retained,
+			expanded,
+			ranked,
+			top,
+			id_keyed,
+			id_values,
+			id_types,
+			known_items,
+			genesis_key: genesis_key(meta),
+			meta: meta.clone(),
+		}
+	}
+
+	/// Keep whatever the reports need from this Key-Value pair.
+	pub fn record(&self, collected: &mut Collected, key: &[u8], value: &[u8], ref_count: i32) {
+		// The entries of a prefix are read again when they are shown, since they can be many.
+		let find = self.find.as_ref().filter(|(subject, _)| subject.prefix().is_none());
+		if let Some((subject, matcher)) = find {
+			let contexts = matcher.scan(key, value);
+			if !contexts.is_empty() {
+				collected.found.push(find::Found { key: key.to_vec(), contexts });
+			}
+			if let Subject::CodeHash(code_hash) = subject {
+				if find::is_code_blob(code_hash, key, value) {
+					collected.code_blobs.push((key[..key.len().min(32)].to_vec(), value.len()));
+				}
+			}
+		}
+		if let Some(known_items) = &self.known_items {
+			let known = key.get(..32).is_some_and(|prefix| known_items.contains(prefix));
+			if !known && !key.starts_with(child::PREFIX) {
+				let unknown = collected.unknown.entry(shapes::prefix(key).to_vec()).or_default();
+				unknown.record(key, value);
+			}
+		}
+		let Some(prefix) = key.get(..32) else { return };
+
+		if self.genesis_key.as_deref() == Some(key) {
+			collected.genesis = value.try_into().ok();
+		}
+		if let Some(item) = self.items.get(prefix) {
+			collected.entries.entry(item.clone()).or_default().push((key.to_vec(), value.to_vec()));
+		}
+		if let Some(item) = self.sized.get(prefix) {
+			collected.sizes.entry(item.clone()).or_default().push((key.to_vec(), value.len()));
+		}
+		if let Some((name, offset)) = self.account_keyed.get(prefix) {
+			if let Some(account) = key.get(*offset..*offset + 32) {
+				let account = account.try_into().expect("Slice has length 32; qed");
+				collected.account_refs.push((account, name.clone()));
+			}
+		}
+		if let Some((name, bound)) = self.bounded.get(prefix) {
+			if value.len() > *bound {
+				let oversized = collected.oversized.entry(name.clone()).or_insert(bounds::Oversized {
+					bound: *bound,
+					entries: 0,
+					longest: 0,
+				});
+				oversized.entries += 1;
+				oversized.longest = oversized.longest.max(value.len());
+			}
+		}
+		if let Some(name) = self.compressed.get(prefix) {
+			collected.compression.entry(name.clone()).or_default().measure(value);
+		}
+		if let Some(name) = self.ref_counted.get(prefix) {
+			let ref_counts = collected.ref_counts.entry(name.clone()).or_default();
+			ref_counts.count(key.len() + value.len(), ref_count);
+		}
+		if let Some((name, first_key)) = self.multi_keyed.get(prefix) {
+			if let Some(hashed) = first_key.split(&key[32..], &self.meta) {
+				let sub_keys = collected.sub_keys.entry(name.clone()).or_default();
+				let sub_key = sub_keys.entry(hashed.to_vec()).or_default();
+				sub_key.entries += 1;
+				sub_key.size += key.len() + value.len();
+			}
+		}
+		if let Some((name, first_key)) = self.retained.get(prefix) {
+			if let Some(index) = retention::index(first_key, &key[32..], &self.meta) {
+				let indices = collected.retention.entry(name.clone()).or_default();
+				let entries = indices.entry(index).or_default();
+				entries.entries += 1;
+				entries.size += key.len() + value.len();
+			}
+		}
+		if let Some((name, keys)) = self.expanded.get(prefix) {
+			let group = collected.key_groups.entry(name.clone()).or_default();
+			group.record(&key[32..], keys, key.len(), value.len(), &self.meta);
+		}
+		if let Some(name) = self.ranked.get(prefix) {
+			collected.largest.entry(name.clone()).or_default().record(key, value, self.top);
+		}
+		if let Some((name, offset)) = self.id_keyed.get(prefix) {
+			if let Some(id) = key.get(*offset..*offset + 32) {
+				let id = id.try_into().expect("Slice has length 32; qed");
+				collected.references.keys.entry(id).or_default().insert(name.clone());
+			}
+		}
+		if let Some((name, ty)) = self.id_values.get(prefix) {
+			let mut input = value;
+			let decoded = scale_value::scale::decode_as_type(&mut input, *ty, self.meta.types());
+			for id in decoded.map_or(Vec::new(), |v| references::scan(&v, &self.id_types)) {
+				collected.references.values.entry(id).or_default().insert(name.clone());
+			}
+		}
+	}
+}
+
+/// A raw Key-Value pair.
+pub type RawEntry = (Vec<u8>, Vec<u8>);
+
+/// Length of the values and of their compressed form per storage item.
+pub type Compressed = Map<String, (usize, usize)>;
+
+/// A raw key with the length of its value.
+pub type SizedEntry = (Vec<u8>, usize);
+
+/// Raw data that was collected for the reports.
+#[derive(Default)]
+pub struct Collected {
+	/// Raw Key-Value pairs per storage item.
+	pub entries: Map<(String, String), Vec<RawEntry>>,
+	/// Keys and value lengths per storage item, for items whose values are too large to keep.
+	pub sizes: Map<(String, String), Vec<SizedEntry>>,
+	/// Accounts that are the first key of a storage map, together with the name of that map.
+	pub account_refs: Vec<([u8; 32], Arc<str>)>,
+	/// Entries that contain the subject of `--find`.
+	pub found: Vec<find::Found>,
+	/// Storage prefixes and sizes of the code blobs of the code hash of `--find`.
+	pub code_blobs: Vec<(Vec<u8>, usize)>,
+	/// Storage items with values that are longer than their type allows.
+	pub oversized: Map<Arc<str>, bounds::Oversized>,
+	/// Measured compression of the values per storage item.
+	pub compression: Map<Arc<str>, compression::Compression>,
+	/// Entries per storage item by their reference count.
+	pub ref_counts: Map<Arc<str>, pruning::RefCounts>,
+	/// Entries of storage maps with multiple keys, by their hashed first key.
+	pub sub_keys: Map<Arc<str>, Map<Vec<u8>, sub_keys::SubKey>>,
+	/// Entries of storage maps that a retention rule applies to, by their index.
+	pub retention: Map<Arc<str>, Map<u128, sub_keys::SubKey>>,
+	/// Entries of storage maps with multiple keys, grouped by their leading keys.
+	pub key_groups: Map<Arc<str>, KeyGroup>,
+	/// The largest entries per storage item for `--top`.
+	pub largest: Map<Arc<str>, Largest>,
+	/// Entries of storage that is not in the metadata, by their storage prefix.
+	pub unknown: Map<Vec<u8>, shapes::Unknown>,
+	/// Accounts and hashes in keys and values, for the reference graph.
+	pub references: references::References,
+	/// Hash of the genesis block as per `System::BlockHash`.
+	pub genesis: Option<[u8; 32]>,
+}
+
+impl Collected {
+	/// Merge the data that another worker collected into this one.
+	pub fn merge(&mut self, other: Collected) {
+		for (item, entries) in other.entries {
+			self.entries.entry(item).or_default().extend(entries);
+		}
+		for (item, sizes) in other.sizes {
+			self.sizes.entry(item).or_default().extend(sizes);
+		}
+		self.account_refs.extend(other.account_refs);
+		self.found.extend(other.found);
+		self.code_blobs.extend(other.code_blobs);
+		for (name, other) in other.oversized {
+			self.oversized
+				.entry(name)
+				.and_modify(|o| {
+					o.entries += other.entries;
+					o.longest = o.longest.max(other.longest);
+				})
+				.or_insert(other);
+		}
+		for (name, other) in other.compression {
+			self.compression.entry(name).or_default().merge(&other);
+		}
+		for (name, other) in other.ref_counts {
+			self.ref_counts.entry(name).or_default().merge(&other);
+		}
+		for (name, other) in other.sub_keys {
+			let sub_keys = self.sub_keys.entry(name).or_default();
+			for (key, other) in other {
+				let sub_key = sub_keys.entry(key).or_default();
+				sub_key.entries += other.entries;
+				sub_key.size += other.size;
+			}
+		}
+		for (name, other) in other.retention {
+			let indices = self.retention.entry(name).or_default();
+			for (index, other) in other {
+				let entries = indices.entry(index).or_default();
+				entries.entries += other.entries;
+				entries.size += other.size;
+			}
+		}
+		for (name, other) in other.key_groups {
+			self.key_groups.entry(name).or_default().merge(other);
+		}
+		for (name, other) in other.largest {
+			self.largest.entry(name).or_default().merge(other);
+		}
+		for (prefix, other) in other.unknown {
+			self.unknown.entry(prefix).or_default().merge(other);
+		}
+		self.references.merge(other.references);
+		self.genesis = self.genesis.or(other.genesis);
+	}
+
+	/// Length of the values and of their compressed form per storage item, as `Pallet::Item`.
+	pub fn compressed(&self) -> Compressed {
+		self.compression
+			.iter()
+			.map(|(name, c)| (name.to_string(), (c.value_len, c.compressed_len)))
+			.collect()
+	}
+
+	/// The collected entries of a storage item.
+	fn entries(&self, pallet: &str, item: &str) -> &[RawEntry] {
+		self.entries
+			.get(&(pallet.to_string(), item.to_string()))
+			.map_or(&[][..], |e| e.as_slice())
+	}
+}
+
+fn storage_prefix(pallet: &str, item: &str) -> Vec<u8> {
+	[twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+}
+
+fn storage_entry<'a>(
+	meta: &'a Metadata,
+	pallet: &str,
+	item: &str,
+) -> Result<&'a StorageEntryMetadata> {
+	meta.pallet_by_name(pallet)
+		.and_then(|p| p.storage())
+		.and_then(|s| s.entry_by_name(item))
+		.ok_or_else(|| anyhow!("Storage item {}::{} not found in the metadata", pallet, item))
+}
+
+/// Decodes storage values with the metadata and optional type overrides.
+pub struct Decoder {
+	meta: Metadata,
+	/// Types to decode storage items with instead of their metadata types, by `(pallet, item)`.
+	overrides: Map<(String, String), u32>,
+}
+
+impl Decoder {
+	/// Create a decoder with the type overrides from a JSON file, if any.
+	///
+	/// The file maps `Pallet::Item` to either the id of a type in the metadata or its path like
+	/// `pallet_identity::types::Registration`. Unique type names without path work as well.
+	pub fn new(meta: Metadata, overrides: Option<&str>) -> Result<Self> {
+		let mut decoder = Self { meta, overrides: Map::new() };
+		let Some(path) = overrides else { return Ok(decoder) };
+
+		let json = std::fs::read_to_string(path)
+			.map_err(|e| anyhow!("Failed to read type overrides from {}: {}", path, e))?;
+		let hints = serde_json::from_str::<Map<String, String>>(&json)
+			.map_err(|e| anyhow!("Type overrides must map `Pallet::Item` to a type: {}", e))?;
+
+		for (item, hint) in hints {
+			let (pallet, name) = item
+				.split_once("::")
+				.ok_or_else(|| anyhow!("Type override for {} is not of the form Pallet::Item", item))?;
+			let ty = resolve_type(&hint, &decoder.meta)?;
+			decoder.overrides.insert((pallet.into(), name.into()), ty);
+		}
+		log::info!("Loaded {} type overrides", decoder.overrides.len());
+
+		Ok(decoder)
+	}
+
+	pub fn meta(&self) -> &Metadata {
+		&self.meta
+	}
+
+	/// Decode a value of a storage item.
+	pub fn decode(&self, pallet: &str, item: &str, value: &[u8]) -> Result<Value<u32>> {
+		let ty = match self.overrides.get(&(pallet.to_string(), item.to_string())) {
+			Some(ty) => *ty,
+			None => storage_entry(&self.meta, pallet, item)?.entry_type().value_ty(),
+		};
+
+		let mut input = value;
+		let decoded = scale_value::scale::decode_as_type(&mut input, ty, self.meta.types())
+			.map_err(|e| anyhow!("Failed to decode value of {}::{}: {}", pallet, item, e))?;
+		if !input.is_empty() {
+			return Err(anyhow!("Value of {}::{} has {} trailing bytes", pallet, item, input.len()))
+		}
+
+		Ok(decoded)
+	}
+}
+
+/// Find a type in the metadata by its id, path or unique name.
+fn resolve_type(hint: &str, meta: &Metadata) -> Result<u32> {
+	if let Ok(id) = hint.parse::<u32>() {
+		return meta.types().resolve(id).map(|_| id).ok_or_else(|| anyhow!("Unknown type id {}", id))
+	}
+
+	let by_path = |exact: bool| {
+		meta.types()
+			.types
+			.iter()
+			.filter(|t| {
+				let segments = &t.ty.path.segments;
+				if exact {
+					segments.join("::") == hint
+				} else {
+					segments.last().is_some_and(|name| name == hint)
+				}
+			})
+			.map(|t| t.id)
+			.collect::<Vec<_>>()
+	};
+	let mut found = by_path(true);
+	if found.is_empty() {
+		found = by_path(false);
+	}
+
+	match found.as_slice() {
+		[id] => Ok(*id),
+		[] => Err(anyhow!("No type {} in the metadata", hint)),
+		// Generic types share their path.
+		ids => Err(anyhow!("Type {} is ambiguous, use one of the type ids {:?}", hint, ids)),
+	}
+}
+
+/// Get a number from a decoded value by following a path of field names.
+fn field_u128(value: &Value<u32>, path: &[&str]) -> Option<u128> {
+	path.iter().try_fold(value, |v, field| v.at(*field))?.as_u128()
+}
+
+/// All composites and variants within a decoded value that have a field with the given name.
+///
+/// Finds the structs of interest regardless of the vectors and wrappers around them.
+fn find_composites<'a>(value: &'a Value<u32>, field: &str) -> Vec<&'a Value<u32>> {
+	let has_field = |composite: &Composite<u32>| {
+		matches!(composite, Composite::Named(fields) if fields.iter().any(|(n, _)| n == field))
+	};
+	let mut found = Vec::new();
+	let mut stack = vec![value];
+
+	while let Some(value) = stack.pop() {
+		match &value.value {
+			ValueDef::Composite(composite) if has_field(composite) => found.push(value),
+			ValueDef::Variant(variant) if has_field(&variant.values) => found.push(value),
+			ValueDef::Composite(composite) => stack.extend(composite.values()),
+			ValueDef::Variant(variant) => stack.extend(variant.values.values()),
+			_ => {},
+		}
+	}
+
+	found
+}
+
+/// Interpret a decoded value as byte array.
+fn value_bytes(value: &Value<u32>) -> Option<Vec<u8>> {
+	match &value.value {
+		ValueDef::Composite(composite) =>
+			composite.values().map(|v| v.as_u128().and_then(|b| u8::try_from(b).ok())).collect(),
+		_ => None,
+	}
+}
+
+/// Interpret a decoded value as account, possibly wrapped in a newtype like `AccountId32`.
+fn value_account(value: &Value<u32>) -> Option<[u8; 32]> {
+	if let ValueDef::Composite(composite) = &value.value {
+		if let Ok(inner) = composite.values().exactly_one() {
+			return value_account(inner)
+		}
+	}
+
+	value_bytes(value)?.try_into().ok()
+}
+
+/// Format a decoded key or value, showing a 32 byte array of type `ty` as account.
+pub fn fmt_value(value: &Value<u32>, ty: u32, meta: &Metadata) -> String {
+	match value_account(value) {
+		Some(account) if is_account_id(ty, meta) => fmt_account(&account, ss58_prefix(meta)),
+		_ => value.to_string(),
+	}
+}
+
+/// Decode the keys of a map entry after its storage prefix, or show their hashes if the hasher
+/// does not keep them.
+pub fn decode_keys(ty: &StorageEntryType, mut key: &[u8], meta: &Metadata) -> String {
+	let StorageEntryType::Map { hashers, key_ty, .. } = ty else { return "()".into() };
+	// Multiple hashers mean that the key type is a tuple of all keys.
+	let key_tys = match &meta.types().resolve(*key_ty).map(|t| &t.type_def) {
+		Some(TypeDef::Tuple(tuple)) if hashers.len() > 1 =>
+			tuple.fields.iter().map(|f| f.id).collect(),
+		_ => vec![*key_ty],
+	};
+
+	let mut keys = Vec::new();
+	for (hasher, ty) in hashers.iter().zip(key_tys) {
+		let Some((hash, rest)) = key.split_at_checked(hasher.len_excluding_key()) else { break };
+		key = rest;
+		if !hasher.ends_with_key() {
+			keys.push(format!("{:?}(0x{})", hasher, hex::encode(hash)));
+			continue
+		}
+		match scale_value::scale::decode_as_type(&mut key, ty, meta.types()) {
+			Ok(decoded) => keys.push(fmt_value(&decoded, ty, meta)),
+			Err(_) => break,
+		}
+	}
+	if !key.is_empty() {
+		keys.push(format!("0x{}", hex::encode(key)));
+	}
+
+	keys.iter().join(", ")
+}
+
+/// Shorten a long decoded value to `len` characters.
+pub fn preview(s: &str, len: usize) -> String {
+	match s.char_indices().nth(len) {
+		Some((end, _)) => format!("{}..", &s[..end]),
+		None => s.into(),
+	}
+}
+
+/// The block number at which the snapshot was taken, as per `System::Number`.
+pub fn block_number(collected: &Collected, decoder: &Decoder) -> Result<u128> {
+	let (_, value) = collected
+		.entries("System", "Number")
+		.first()
+		.ok_or_else(|| anyhow!("System::Number is not in the snapshot"))?;
+
+	decoder
+		.decode("System", "Number", value)?
+		.as_u128()
+		.ok_or_else(|| anyhow!("System::Number is not a number"))
+}
+
+/// The key of `System::BlockHash` for block zero, which holds the genesis hash.
+fn genesis_key(meta: &Metadata) -> Option<Vec<u8>> {
+	let entry = storage_entry(meta, "System", "BlockHash").ok()?;
+	let StorageEntryType::Map { hashers, key_ty, .. } = entry.entry_type() else { return None };
+	let mut number = Vec::new();
+	scale_value::scale::encode_as_type(&Value::u128(0), *key_ty, meta.types(), &mut number).ok()?;
+
+	let mut key = storage_prefix("System", "BlockHash");
+	match hashers.first()? {
+		StorageHasher::Twox64Concat => key.extend(twox_64(&number)),
+		StorageHasher::Blake2_128Concat => key.extend(blake2_128(&number)),
+		StorageHasher::Identity => (),
+		_ => return None,
+	}
+	key.extend(number);
+	Some(key)
+}
+
+/// The account that is the first key of a storage map entry.
+fn key_account(entry: &StorageEntryMetadata, key: &[u8], meta: &Metadata) -> Option<[u8; 32]> {
+	let offset = account_key_offset(entry, meta)?;
+	key.get(offset..offset + 32)?.try_into().ok()
+}
+
+/// The decoded first key of a storage map entry, if its hasher keeps the key.
+fn key_value(entry: &StorageEntryMetadata, key: &[u8], meta: &Metadata) -> Option<Value<u32>> {
+	let first_key = sub_keys::FirstKey::of(entry, meta)?;
+	if !first_key.hasher.ends_with_key() {
+		return None
+	}
+
+	let mut input = key.get(32 + first_key.hasher.len_excluding_key()..)?;
+	scale_value::scale::decode_as_type(&mut input, first_key.ty, meta.types()).ok()
+}
+
+/// Offset of the first key within the storage keys of a map if that key is an account.
+fn account_key_offset(entry: &StorageEntryMetadata, meta: &Metadata) -> Option<usize> {
+	let StorageEntryType::Map { hashers, key_ty, .. } = entry.entry_type() else { return None };
+	let hasher = hashers.first()?;
+	if !hasher.ends_with_key() {
+		return None
+	}
+
+	// Multiple hashers mean that the key type is a tuple of all keys.
+	let first_key_ty = if hashers.len() > 1 {
+		match &meta.types().resolve(*key_ty)?.type_def {
+			TypeDef::Tuple(tuple) => tuple.fields.first()?.id,
+			_ => return None,
+		}
+	} else {
+		*key_ty
+	};
+
+	is_account_id(first_key_ty, meta).then_some(32 + hasher.len_excluding_key())
+}
+
+/// Whether a type is encoded as 32 bytes, like an `AccountId32`.
+fn is_account_id(ty: u32, meta: &Metadata) -> bool {
+	let Some(ty) = meta.types().resolve(ty) else { return false };
+
+	match &ty.type_def {
+		TypeDef::Composite(composite) if composite.fields.len() == 1 =>
+			is_account_id(composite.fields[0].ty.id, meta),
+		TypeDef::Array(array) if array.len == 32 => matches!(
+			meta.types().resolve(array.type_param.id).map(|t| &t.type_def),
+			Some(TypeDef::Primitive(scale_info::TypeDefPrimitive::U8))
+		),
+		_ => false,
+	}
+}
+
+/// The SS58 prefix of the network or the generic Substrate prefix if it is unknown.
+fn ss58_prefix(meta: &Metadata) -> u16 {
+	meta.pallet_by_name("System")
+		.and_then(|p| p.constant_by_name("SS58Prefix"))
+		.and_then(|c| u16::decode(&mut c.value()).ok())
+		.unwrap_or(42)
+}
+
+/// Format an account as SS58 address.
+fn fmt_account(account: &[u8; 32], prefix: u16) -> String {
+	let mut data = match prefix {
+		0..=63 => vec![prefix as u8],
+		_ => {
+			// Two byte prefixes are split up with the lower bits first, as done by `sp_core`.
+			let ident = prefix & 0b0011_1111_1111_1111;
+			let first = ((ident & 0b0000_0000_1111_1100) as u8 >> 2) | 0b0100_0000;
+			let second = ((ident >> 8) as u8) | (((ident & 0b0000_0000_0000_0011) as u8) << 6);
+			vec![first, second]
+		},
+	};
+	data.extend(account);
+	let checksum = blake2_512(&[&b"SS58PRE"[..], &data].concat());
+	data.extend(&checksum[..2]);
+	data.to_base58()
+}
+
+/// Print a warning that stands out from the numbers, since the logs are off by default.
+fn warn(message: String) {
+	println!("{}", ansi_term::Color::Yellow.paint(format!("  Warning: {}", message)));
+}
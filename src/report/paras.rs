@@ -0,0 +1,255 @@
+//! Relay chain storage that is still held for parachains which are no longer registered.
+//!
+//! Offboarding a para should remove its heads, code, HRMP channels and registration. Anything that
+//! is keyed by a para ID without a `Paras::ParaLifecycles` entry is a leftover, as are code blobs
+//! that no para references anymore.
+
+use super::{storage_entry, sub_keys::FirstKey, Collected, Decoder};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use parity_scale_codec::Decode;
+use scale_info::TypeDef;
+use scale_value::Value;
+use std::collections::{BTreeMap as Map, BTreeSet};
+use subxt::Metadata;
+
+/// The code blobs by their hash. Only the lengths of their values are collected.
+pub const CODE_BLOBS: (&str, &str) = ("Paras", "CodeByHash");
+
+/// How a storage map refers to paras with its first key.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ParaKey {
+	/// A single para ID.
+	Id,
+	/// An HRMP channel as `(sender, recipient)`.
+	Channel,
+}
+
+/// Storage maps whose first key refers to paras, as `(pallet, item)`.
+pub fn items(meta: &Metadata) -> Vec<(String, String)> {
+	para_keyed(meta).into_iter().map(|(pallet, item, _, _)| (pallet, item)).collect()
+}
+
+/// Storage maps whose first key refers to paras, with how to find them in the key.
+fn para_keyed(meta: &Metadata) -> Vec<(String, String, FirstKey, ParaKey)> {
+	let mut items = Vec::new();
+
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			let Some(first_key) = FirstKey::of(entry, meta) else { continue };
+			let Some(kind) = para_key(&first_key, meta) else { continue };
+
+			items.push((pallet.name().to_string(), entry.name().to_string(), first_key, kind));
+		}
+	}
+
+	items
+}
+
+fn para_key(first_key: &FirstKey, meta: &Metadata) -> Option<ParaKey> {
+	if !first_key.hasher.ends_with_key() {
+		return None
+	}
+	// Keys like `(ParaId, BlockNumber)` of `Paras::PastCodeHash` start with the para ID.
+	let mut ty = meta.types().resolve(first_key.ty)?;
+	if let TypeDef::Tuple(tuple) = &ty.type_def {
+		ty = meta.types().resolve(tuple.fields.first()?.id)?;
+	}
+	let segments = &ty.path.segments;
+	if !segments.iter().any(|s| s.starts_with("polkadot_parachain")) {
+		return None
+	}
+
+	match segments.last()?.as_str() {
+		"Id" => Some(ParaKey::Id),
+		"HrmpChannelId" => Some(ParaKey::Channel),
+		_ => None,
+	}
+}
+
+/// The para IDs within a storage key that starts after the storage prefix.
+fn para_ids(key: &[u8], first_key: &FirstKey, kind: ParaKey) -> Vec<u32> {
+	let hash_len = first_key.hasher.len_excluding_key();
+	let Some(mut input) = key.get(hash_len..) else { return Vec::new() };
+
+	match kind {
+		ParaKey::Id => u32::decode(&mut input).ok().into_iter().collect(),
+		ParaKey::Channel =>
+			<(u32, u32)>::decode(&mut input).map_or(Vec::new(), |(s, r)| vec![s, r]),
+	}
+}
+
+/// Whether the values of a storage item are a `ValidationCodeHash`.
+fn holds_code_hash(pallet: &str, item: &str, meta: &Metadata) -> bool {
+	let Ok(entry) = storage_entry(meta, pallet, item) else { return false };
+	let ty = meta.types().resolve(entry.entry_type().value_ty());
+	ty.and_then(|ty| ty.path.segments.last()).is_some_and(|s| s == "ValidationCodeHash")
+}
+
+/// Storage that an inactive para still holds.
+#[derive(Default)]
+struct Leftovers {
+	/// Entries and their size per storage item.
+	items: Map<String, (usize, usize)>,
+	/// Still has an entry in `Registrar::Paras`.
+	registered: bool,
+	/// Number of HRMP channels to and from this para.
+	inbound: u32,
+	outbound: u32,
+}
+
+impl Leftovers {
+	fn size(&self) -> usize {
+		self.items.values().map(|(_, size)| size).sum()
+	}
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let Ok(lifecycles) = storage_entry(meta, "Paras", "ParaLifecycles") else {
+		println!("Inactive paras: the metadata has no Paras pallet");
+		return Ok(())
+	};
+	let active = FirstKey::of(lifecycles, meta)
+		.map(|first_key| {
+			collected
+				.entries("Paras", "ParaLifecycles")
+				.iter()
+				.flat_map(|(key, _)| para_ids(&key[32..], &first_key, ParaKey::Id))
+				.collect::<BTreeSet<_>>()
+		})
+		.unwrap_or_default();
+
+	let mut inactive = Map::<u32, Leftovers>::new();
+	// Code hashes that are used by active paras, and those used only by inactive ones.
+	let mut active_code = BTreeSet::<Vec<u8>>::new();
+	let mut inactive_code = Map::<Vec<u8>, u32>::new();
+
+	for (pallet, item, first_key, kind) in para_keyed(meta) {
+		let name = format!("{}::{}", pallet, item);
+		let code_hash = holds_code_hash(&pallet, &item, meta);
+
+		for (key, value) in collected.entries(&pallet, &item) {
+			let ids = para_ids(&key[32..], &first_key, kind);
+			let Some(&para) = ids.iter().find(|id| !active.contains(id)) else {
+				if code_hash {
+					active_code.insert(value.clone());
+				}
+				continue
+			};
+
+			let leftovers = inactive.entry(para).or_default();
+			let (entries, size) = leftovers.items.entry(name.clone()).or_default();
+			*entries += 1;
+			*size += key.len() + value.len();
+			if (pallet.as_str(), item.as_str()) == ("Registrar", "Paras") {
+				leftovers.registered = true;
+			}
+			if code_hash {
+				inactive_code.insert(value.clone(), para);
+			}
+			if (pallet.as_str(), item.as_str()) == ("Hrmp", "HrmpChannels") {
+				let [sender, recipient] = ids[..] else { continue };
+				if !active.contains(&sender) {
+					inactive.entry(sender).or_default().outbound += 1;
+				}
+				if !active.contains(&recipient) {
+					inactive.entry(recipient).or_default().inbound += 1;
+				}
+			}
+		}
+	}
+
+	let referenced = collected
+		.entries("Paras", "CodeByHashRefs")
+		.iter()
+		.filter_map(|(key, _)| key.get(32..))
+		.collect::<BTreeSet<_>>();
+	let blobs = collected.sizes.get(&(CODE_BLOBS.0.into(), CODE_BLOBS.1.into()));
+	let mut unused = Vec::new();
+	for (key, size) in blobs.into_iter().flatten() {
+		let hash = &key[32..];
+		if let (Some(para), false) = (inactive_code.get(hash), active_code.contains(hash)) {
+			let leftovers = inactive.entry(*para).or_default();
+			let (entries, blobs) = leftovers.items.entry("Paras::CodeByHash".into()).or_default();
+			*entries += 1;
+			*blobs += key.len() + size;
+		} else if !referenced.contains(hash) {
+			unused.push((hash.to_vec(), key.len() + size));
+		}
+	}
+
+	let total = inactive.values().map(Leftovers::size).sum::<usize>();
+	println!(
+		"Inactive paras: {} active, {} inactive with {}",
+		active.len(),
+		inactive.len(),
+		fmt_bytes(total, false, units)
+	);
+	for (para, leftovers) in &inactive {
+		println!("  Para {}: {}", para, fmt_bytes(leftovers.size(), false, units));
+		for (name, (entries, size)) in &leftovers.items {
+			println!("    {}: {} entries, {}", name, entries, fmt_bytes(*size, false, units));
+		}
+	}
+	if !unused.is_empty() {
+		let size = unused.iter().map(|(_, size)| size).sum::<usize>();
+		println!("  Unused code blobs: {} with {}", unused.len(), fmt_bytes(size, false, units));
+	}
+
+	let mut calls = Vec::new();
+	for (para, leftovers) in &inactive {
+		let id = || Value::u128(*para as u128);
+		if leftovers.registered {
+			calls.push(call(meta, "Registrar", "deregister", vec![id()], para.to_string()));
+		}
+		if leftovers.inbound + leftovers.outbound > 0 {
+			let args = vec![
+				id(),
+				Value::u128(leftovers.inbound as u128),
+				Value::u128(leftovers.outbound as u128),
+			];
+			let shown = format!("{}, {}, {}", para, leftovers.inbound, leftovers.outbound);
+			calls.push(call(meta, "Hrmp", "force_clean_hrmp", args, shown));
+		}
+	}
+	for (hash, _) in &unused {
+		let args = vec![Value::from_bytes(hash)];
+		let shown = format!("0x{}", hex::encode(hash));
+		calls.push(call(meta, "Paras", "poke_unused_validation_code", args, shown));
+	}
+
+	let calls = calls.into_iter().flatten().collect::<Vec<_>>();
+	if !calls.is_empty() {
+		println!("Cleanup calls:");
+		for (shown, encoded) in calls {
+			println!("  {}: 0x{}", shown, hex::encode(encoded));
+		}
+	}
+
+	Ok(())
+}
+
+/// Encode a call with its arguments, or `None` if the runtime does not have it.
+///
+/// Returns the call in a readable form together with its encoding.
+fn call(
+	meta: &Metadata,
+	pallet: &str,
+	name: &str,
+	args: Vec<Value>,
+	shown: String,
+) -> Option<(String, Vec<u8>)> {
+	let pallet_meta = meta.pallet_by_name(pallet)?;
+	let variant = pallet_meta.call_variant_by_name(name)?;
+	if variant.fields.len() != args.len() {
+		return None
+	}
+
+	let mut encoded = vec![pallet_meta.index(), variant.index];
+	for (field, arg) in variant.fields.iter().zip(&args) {
+		scale_value::scale::encode_as_type(arg, field.ty.id, meta.types(), &mut encoded).ok()?;
+	}
+
+	Some((format!("{}::{}({})", pallet, name, shown), encoded))
+}
@@ -0,0 +1,170 @@
+//! Identities on the People chains: judgements by registrar, sub-accounts and usernames.
+//!
+//! The identities of the relay chains moved to the People chains. The judgements show for how much
+//! of the identity storage a registrar vouches, the rest is only what accounts declare themselves.
+
+use super::{
+	find_composites, fmt_account, ss58_prefix, storage_entry, value_account, warn, Collected,
+	Decoder,
+};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use scale_value::{At, ValueDef};
+use std::collections::BTreeMap as Map;
+
+/// Storage items of the usernames, which were renamed between versions of the pallet.
+const USERNAME_ITEMS: &[&str] = &[
+	"AccountOfUsername",
+	"UsernameInfoOf",
+	"PendingUsernames",
+	"UnbindingUsernames",
+	"UsernameAuthorities",
+	"AuthorityOf",
+];
+
+/// Storage items of the Identity pallet that the report decodes or counts, as `(pallet, item)`.
+pub const ITEMS: &[(&str, &str)] = &[
+	("Identity", "IdentityOf"),
+	("Identity", "Registrars"),
+	("Identity", "SuperOf"),
+	("Identity", "SubsOf"),
+	("Identity", "AccountOfUsername"),
+	("Identity", "UsernameInfoOf"),
+	("Identity", "PendingUsernames"),
+	("Identity", "UnbindingUsernames"),
+	("Identity", "UsernameAuthorities"),
+	("Identity", "AuthorityOf"),
+];
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	if storage_entry(meta, "Identity", "IdentityOf").is_err() {
+		println!("Identities: the runtime has no Identity pallet");
+		return Ok(())
+	}
+	let prefix = ss58_prefix(meta);
+	let size_of = |item| {
+		let entries = collected.entries("Identity", item);
+		(entries.len(), entries.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>())
+	};
+	println!("Identities:");
+	let mut undecodable = 0;
+
+	// Accounts of the registrars by their index, or `None` for removed registrars.
+	let registrars = collected
+		.entries("Identity", "Registrars")
+		.first()
+		.and_then(|(_, value)| decoder.decode("Identity", "Registrars", value).ok())
+		.map(|registrars| match &registrars.value {
+			ValueDef::Composite(composite) => composite
+				.values()
+				.map(|r| r.at(0).and_then(|r| r.at("account")).and_then(value_account))
+				.collect(),
+			_ => Vec::new(),
+		})
+		.unwrap_or_default();
+
+	// Number and size of the judged identities by registrar and judgement.
+	let mut judged = Map::<u128, Map<String, (usize, usize)>>::new();
+	let (mut unjudged, mut unjudged_size) = (0, 0);
+	for (key, value) in collected.entries("Identity", "IdentityOf") {
+		let size = key.len() + value.len();
+		let Ok(decoded) = decoder.decode("Identity", "IdentityOf", value) else {
+			undecodable += 1;
+			continue
+		};
+		// Newer versions store the registration in a tuple with the username.
+		let judgements = find_composites(&decoded, "judgements")
+			.into_iter()
+			.filter_map(|registration| registration.at("judgements"))
+			.flat_map(|judgements| match &judgements.value {
+				ValueDef::Composite(composite) => composite.values().collect(),
+				_ => Vec::new(),
+			})
+			.filter_map(|judgement| {
+				let registrar = judgement.at(0)?.as_u128()?;
+				match &judgement.at(1)?.value {
+					ValueDef::Variant(variant) => Some((registrar, variant.name.clone())),
+					_ => None,
+				}
+			})
+			.collect::<Vec<_>>();
+
+		if judgements.is_empty() {
+			unjudged += 1;
+			unjudged_size += size;
+		}
+		for (registrar, judgement) in judgements {
+			let (count, judged_size) =
+				judged.entry(registrar).or_default().entry(judgement).or_default();
+			*count += 1;
+			*judged_size += size;
+		}
+	}
+
+	let (identities, size) = size_of("IdentityOf");
+	println!(
+		"  Identity::IdentityOf: {} identities ({}), {} without judgement ({})",
+		identities,
+		fmt_bytes(size, false, units),
+		unjudged,
+		fmt_bytes(unjudged_size, false, units)
+	);
+
+	let indices = (0..registrars.len() as u128).chain(judged.keys().copied()).unique().sorted();
+	for index in indices {
+		let account = match registrars.get(index as usize) {
+			Some(Some(account)) => fmt_account(account, prefix),
+			Some(None) => "removed".into(),
+			None => "unknown".into(),
+		};
+		let judgements = judged.remove(&index).unwrap_or_default();
+		let count = judgements.values().map(|(count, _)| count).sum::<usize>();
+		let size = judgements.values().map(|(_, size)| size).sum();
+		let by_kind = judgements
+			.iter()
+			.sorted_by_key(|(_, (count, _))| *count)
+			.rev()
+			.map(|(judgement, (count, _))| format!("{} {}", count, judgement))
+			.join(", ");
+		print!(
+			"    Registrar {} ({}): {} judgements ({})",
+			index,
+			account,
+			count,
+			fmt_bytes(size, false, units)
+		);
+		if by_kind.is_empty() {
+			println!();
+		} else {
+			println!(": {}", by_kind);
+		}
+	}
+
+	if storage_entry(meta, "Identity", "SuperOf").is_ok() {
+		let (subs, subs_size) = size_of("SuperOf");
+		let (supers, supers_size) = size_of("SubsOf");
+		println!(
+			"  Identity::SuperOf: {} sub-accounts ({}) of {} identities in Identity::SubsOf ({})",
+			subs,
+			fmt_bytes(subs_size, false, units),
+			supers,
+			fmt_bytes(supers_size, false, units)
+		);
+	}
+
+	for item in USERNAME_ITEMS {
+		if storage_entry(meta, "Identity", item).is_err() {
+			continue
+		}
+		let (entries, size) = size_of(item);
+		println!("  Identity::{}: {} entries ({})", item, entries, fmt_bytes(size, false, units));
+	}
+
+	if undecodable > 0 {
+		warn(format!("Could not decode {} identities", undecodable));
+	}
+
+	Ok(())
+}
@@ -0,0 +1,233 @@
+//! Who can act with privileges: the sudo key and the members of collectives.
+//!
+//! Gives auditors an overview of the governance surface. Collectives are found by their storage
+//! items, so that every instance like `Council`, `TechnicalCommittee` or `FellowshipCollective` is
+//! included.
+
+use super::{fmt_account, ss58_prefix, value_account, Collected, Decoder};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use scale_value::{At, Value, ValueDef};
+use std::collections::BTreeMap as Map;
+use subxt::Metadata;
+
+/// Number of members to show per collective.
+const TOP_MEMBERS: usize = 20;
+
+/// A pallet whose members have privileges.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+	/// `pallet_collective`, whose members propose and vote on calls.
+	Collective,
+	/// `pallet_membership`, which manages the members of a collective.
+	Membership,
+	/// `pallet_ranked_collective`, whose members have a rank.
+	Ranked,
+}
+
+impl Kind {
+	fn items(self) -> &'static [&'static str] {
+		match self {
+			Kind::Collective => &["Members", "Prime", "Proposals", "ProposalOf", "Voting"],
+			Kind::Membership => &["Members", "Prime"],
+			Kind::Ranked => &["Members", "MemberCount"],
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Kind::Collective => "collective",
+			Kind::Membership => "membership",
+			Kind::Ranked => "ranked collective",
+		}
+	}
+}
+
+/// Pallets with members by their kind.
+fn pallets(meta: &Metadata) -> Vec<(String, Kind)> {
+	meta.pallets()
+		.filter_map(|p| {
+			let has = |item| p.storage().is_some_and(|s| s.entry_by_name(item).is_some());
+			let kind = if !has("Members") {
+				return None
+			} else if has("ProposalOf") && has("Voting") {
+				Kind::Collective
+			} else if has("MemberCount") {
+				Kind::Ranked
+			} else if has("Prime") {
+				Kind::Membership
+			} else {
+				return None
+			};
+			Some((p.name().to_string(), kind))
+		})
+		.collect()
+}
+
+/// The sudo key and the storage items of all collectives, as `(pallet, item)`.
+pub fn items(meta: &Metadata) -> Vec<(String, String)> {
+	let mut items = vec![("Sudo".to_string(), "Key".to_string())];
+	for (pallet, kind) in pallets(meta) {
+		items.extend(kind.items().iter().map(|item| (pallet.clone(), item.to_string())));
+	}
+	items
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let prefix = ss58_prefix(meta);
+	println!("Privileges:");
+
+	if meta.pallet_by_name("Sudo").is_some() {
+		let key = collected
+			.entries("Sudo", "Key")
+			.first()
+			.and_then(|(_, value)| decoder.decode("Sudo", "Key", value).ok())
+			.and_then(|key| value_account(&key));
+		match key {
+			Some(key) => println!("  Sudo key: {}", fmt_account(&key, prefix)),
+			None => println!("  Sudo key: none"),
+		}
+	} else {
+		println!("  Sudo: not in the runtime");
+	}
+
+	for (pallet, kind) in pallets(meta) {
+		let size = kind
+			.items()
+			.iter()
+			.flat_map(|item| collected.entries(&pallet, item))
+			.map(|(key, value)| key.len() + value.len())
+			.sum();
+		match kind {
+			Kind::Collective | Kind::Membership => {
+				let members = accounts(collected, decoder, &pallet, "Members");
+				let prime = accounts(collected, decoder, &pallet, "Prime");
+				print!("  {} ({}): {} members", pallet, kind.name(), members.len());
+				if let Some(prime) = prime.first() {
+					print!(", prime {}", fmt_account(prime, prefix));
+				}
+				println!(", {}", fmt_bytes(size, false, units));
+				print_members(members.iter().map(|m| fmt_account(m, prefix)));
+			},
+			Kind::Ranked => {
+				let mut ranks = Vec::new();
+				for (key, value) in collected.entries(&pallet, "Members") {
+					let rank = decoder
+						.decode(&pallet, "Members", value)
+						.ok()
+						.and_then(|record| record.at("rank")?.as_u128());
+					if let (Some(rank), Some(account)) = (rank, key_suffix(key)) {
+						ranks.push((rank, account));
+					}
+				}
+				ranks.sort_by(|a, b| b.cmp(a));
+
+				let by_rank = ranks.iter().counts_by(|(rank, _)| *rank);
+				let by_rank = by_rank
+					.iter()
+					.sorted()
+					.map(|(rank, count)| format!("{} of rank {}", count, rank))
+					.join(", ");
+				println!(
+					"  {} ({}): {} members ({}), {}",
+					pallet,
+					kind.name(),
+					ranks.len(),
+					by_rank,
+					fmt_bytes(size, false, units)
+				);
+				let members = ranks.iter().map(|(rank, account)| {
+					format!("{} rank {}", fmt_account(account, prefix), rank)
+				});
+				print_members(members);
+			},
+		}
+
+		if kind == Kind::Collective {
+			print_proposals(collected, decoder, &pallet, units);
+		}
+	}
+
+	Ok(())
+}
+
+/// Accounts in a value like `Vec<AccountId>` or `AccountId`.
+fn accounts(collected: &Collected, decoder: &Decoder, pallet: &str, item: &str) -> Vec<[u8; 32]> {
+	let Some((_, value)) = collected.entries(pallet, item).first() else { return Vec::new() };
+	let Ok(value) = decoder.decode(pallet, item, value) else { return Vec::new() };
+
+	if let Some(account) = value_account(&value) {
+		return vec![account]
+	}
+	match &value.value {
+		ValueDef::Composite(composite) => composite.values().filter_map(value_account).collect(),
+		_ => Vec::new(),
+	}
+}
+
+fn print_members(members: impl ExactSizeIterator<Item = String>) {
+	let len = members.len();
+	for member in members.take(TOP_MEMBERS) {
+		println!("    {}", member);
+	}
+	if len > TOP_MEMBERS {
+		println!("    ... and {} more", len - TOP_MEMBERS);
+	}
+}
+
+/// The pending proposals of a collective with their call and votes.
+fn print_proposals(collected: &Collected, decoder: &Decoder, pallet: &str, units: Units) {
+	// Both maps are keyed by the proposal hash.
+	let votes = collected
+		.entries(pallet, "Voting")
+		.iter()
+		.filter_map(|(key, value)| Some((key_suffix(key)?, (value, key.len() + value.len()))))
+		.collect::<Map<_, _>>();
+	let proposals = collected.entries(pallet, "ProposalOf");
+	if proposals.is_empty() {
+		println!("    No pending proposals");
+		return
+	}
+
+	println!("    {} pending proposals:", proposals.len());
+	for (key, value) in proposals {
+		let Some(hash) = key_suffix(key) else { continue };
+		let call = decoder.decode(pallet, "ProposalOf", value).ok().and_then(|c| call_name(&c));
+		let mut size = key.len() + value.len();
+		let mut line = format!("0x{}: {}", hex::encode(hash), call.as_deref().unwrap_or("?"));
+
+		if let Some((voting, voting_size)) = votes.get(&hash) {
+			size += voting_size;
+			if let Ok(voting) = decoder.decode(pallet, "Voting", voting) {
+				let count = |field| match voting.at(field).map(|v| &v.value) {
+					Some(ValueDef::Composite(accounts)) => accounts.len(),
+					_ => 0,
+				};
+				let threshold = voting.at("threshold").and_then(|t| t.as_u128());
+				line += &format!(
+					", {} ayes and {} nays of {} needed",
+					count("ayes"),
+					count("nays"),
+					threshold.unwrap_or_default()
+				);
+			}
+		}
+		println!("      {}, {}", line, fmt_bytes(size, false, units));
+	}
+}
+
+/// The account or hash at the end of a key, as for the `Twox64Concat` or `Identity` hashers.
+fn key_suffix(key: &[u8]) -> Option<[u8; 32]> {
+	key.get(key.len().checked_sub(32)?..)?.try_into().ok()
+}
+
+/// Name of a call like `Treasury::spend`, where the outer variant is the pallet.
+fn call_name(call: &Value<u32>) -> Option<String> {
+	let ValueDef::Variant(pallet) = &call.value else { return None };
+	match &pallet.values.values().next()?.value {
+		ValueDef::Variant(inner) => Some(format!("{}::{}", pallet.name, inner.name)),
+		_ => Some(pallet.name.clone()),
+	}
+}
@@ -0,0 +1,157 @@
+//! How much disk an archive node would reclaim with state pruning.
+//!
+//! The raw snapshot counts the references of every entry. Entries without references are no longer
+//! part of the state and only kept for history, while entries with several references are shared
+//! and only freed once all of them are pruned. Items that the runtime rewrites in every block add
+//! a new version per block, which an archive node keeps forever and a pruning node only for the
+//! blocks in its window.
+
+use super::{block_number, retention::block_time, Collected, Decoder};
+use crate::{fmt_bytes, fmt_percent, Units};
+use anyhow::Result;
+use itertools::Itertools;
+
+/// Pruning windows in blocks to estimate, starting with the default of `--state-pruning`.
+const WINDOWS: &[u128] = &[256, 4_096, 65_536];
+
+/// Items that are rewritten in every block, as `(pallet, item)`.
+///
+/// `System::BlockHash` gains one entry per block instead of being rewritten.
+const PER_BLOCK: &[(&str, &str)] = &[
+	("System", "Number"),
+	("System", "ParentHash"),
+	("System", "Digest"),
+	("System", "BlockWeight"),
+	("System", "Events"),
+	("System", "EventCount"),
+	("System", "BlockHash"),
+	("Timestamp", "Now"),
+	("Babe", "AuthorVrfRandomness"),
+	("ParachainSystem", "ValidationData"),
+	("ParachainSystem", "LastRelayChainBlockNumber"),
+];
+
+/// Number of storage items to list.
+const TOP_ITEMS: usize = 20;
+
+/// Entries of a storage item by their reference count.
+#[derive(Default)]
+pub struct RefCounts {
+	pub entries: usize,
+	pub size: usize,
+	/// Entries without references, which only an archive node keeps.
+	pub dead_entries: usize,
+	pub dead_size: usize,
+	/// Entries with more than one reference.
+	pub shared_entries: usize,
+	pub shared_size: usize,
+}
+
+impl RefCounts {
+	pub fn count(&mut self, size: usize, ref_count: i32) {
+		self.entries += 1;
+		self.size += size;
+		if ref_count <= 0 {
+			self.dead_entries += 1;
+			self.dead_size += size;
+		} else if ref_count > 1 {
+			self.shared_entries += 1;
+			self.shared_size += size;
+		}
+	}
+
+	pub fn merge(&mut self, other: &RefCounts) {
+		self.entries += other.entries;
+		self.size += other.size;
+		self.dead_entries += other.dead_entries;
+		self.dead_size += other.dead_size;
+		self.shared_entries += other.shared_entries;
+		self.shared_size += other.shared_size;
+	}
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let now = block_number(collected, decoder)?;
+	let block_time = block_time(decoder.meta()) as u128;
+	let per_day = 86_400_000 / block_time;
+	println!("Pruning advice at block {} with a block time of {} ms:", now, block_time);
+
+	let total = collected.ref_counts.values().map(|r| r.size).sum::<usize>();
+	let dead = collected.ref_counts.values().map(|r| r.dead_size).sum::<usize>();
+	let dead_entries = collected.ref_counts.values().map(|r| r.dead_entries).sum::<usize>();
+	println!(
+		"  {} in {} entries are no longer referenced and only kept by archive nodes ({} of {})",
+		fmt_bytes(dead, false, units),
+		dead_entries,
+		fmt_percent(dead, total),
+		fmt_bytes(total, false, units)
+	);
+
+	// Size of the new versions of the items that are written in every block.
+	let per_block = PER_BLOCK
+		.iter()
+		.filter_map(|(pallet, item)| {
+			collected.ref_counts.get(format!("{}::{}", pallet, item).as_str())
+		})
+		.map(|r| r.size / r.entries.max(1))
+		.sum::<usize>() as u128;
+	if per_block == 0 {
+		println!("  None of the items that change in every block are in the snapshot");
+	} else {
+		let archived = per_block * now;
+		println!(
+			"  {} changes per block, archive nodes keep {} of {} blocks and grow by {} a day",
+			fmt_bytes(per_block as usize, false, units),
+			fmt_bytes(archived as usize, false, units),
+			now,
+			fmt_bytes((per_block * per_day) as usize, false, units)
+		);
+		for window in WINDOWS {
+			let kept = per_block * window.min(&now);
+			let reclaimed = (archived - kept) as usize + dead;
+			println!(
+				"  --state-pruning {}: keeps {} of history, reclaims {} ({} of the archive)",
+				window,
+				fmt_bytes(kept as usize, false, units),
+				fmt_bytes(reclaimed, false, units),
+				fmt_percent(reclaimed, archived as usize + dead)
+			);
+		}
+	}
+
+	let groups = collected
+		.ref_counts
+		.iter()
+		.filter(|(_, r)| r.dead_entries > 0 || r.shared_entries > 0)
+		.sorted_by_key(|(_, r)| (r.dead_size, r.shared_size))
+		.rev()
+		.collect::<Vec<_>>();
+	if groups.is_empty() {
+		println!("  All entries are referenced exactly once");
+		return Ok(())
+	}
+	println!("  By storage item:");
+	for (name, refs) in groups.iter().take(TOP_ITEMS) {
+		let mut line = format!("    {}:", name);
+		if refs.dead_entries > 0 {
+			line += &format!(
+				" {} reclaimable in {} entries,",
+				fmt_bytes(refs.dead_size, false, units),
+				refs.dead_entries
+			);
+		}
+		if refs.shared_entries > 0 {
+			line += &format!(
+				" {} shared by {} entries, freed only with all references,",
+				fmt_bytes(refs.shared_size, false, units),
+				refs.shared_entries
+			);
+		}
+		println!("{} {} in total", line, fmt_bytes(refs.size, false, units));
+	}
+	if groups.len() > TOP_ITEMS {
+		println!("    ... and {} more", groups.len() - TOP_ITEMS);
+	}
+
+	Ok(())
+}
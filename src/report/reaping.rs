@@ -0,0 +1,79 @@
+//! Accounts that could be reaped if not for their references.
+//!
+//! An account with neither free nor reserved balance should not exist anymore. If it still has
+//! consumers, providers or sufficients then some pallet is holding on to it. The storage maps that
+//! are keyed by such an account are the likely culprits.
+
+use super::{field_u128, fmt_account, ss58_prefix, warn, Collected, Decoder};
+use anyhow::Result;
+use std::collections::{BTreeMap as Map, BTreeSet};
+
+struct Candidate {
+	consumers: u128,
+	providers: u128,
+	sufficients: u128,
+	/// Storage maps that are keyed by this account.
+	referenced_by: BTreeSet<String>,
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder) -> Result<()> {
+	let mut candidates = Map::<[u8; 32], Candidate>::new();
+	let mut undecodable = 0;
+
+	for (key, value) in collected.entries("System", "Account") {
+		// The key is the storage prefix, a `Blake2_128` hash and then the account.
+		let Some(account) = key.get(48..80).and_then(|a| <[u8; 32]>::try_from(a).ok()) else {
+			undecodable += 1;
+			continue
+		};
+		let Ok(info) = decoder.decode("System", "Account", value) else {
+			undecodable += 1;
+			continue
+		};
+
+		let balance = field_u128(&info, &["data", "free"]).unwrap_or_default() +
+			field_u128(&info, &["data", "reserved"]).unwrap_or_default();
+		let consumers = field_u128(&info, &["consumers"]).unwrap_or_default();
+		let providers = field_u128(&info, &["providers"]).unwrap_or_default();
+		let sufficients = field_u128(&info, &["sufficients"]).unwrap_or_default();
+
+		if balance == 0 && consumers + providers + sufficients > 0 {
+			let referenced_by = BTreeSet::new();
+			let candidate = Candidate { consumers, providers, sufficients, referenced_by };
+			candidates.insert(account, candidate);
+		}
+	}
+
+	for (account, map) in &collected.account_refs {
+		if let Some(candidate) = candidates.get_mut(account) {
+			candidate.referenced_by.insert(map.to_string());
+		}
+	}
+
+	println!(
+		"Reaping candidates: {} accounts without balance but with references",
+		candidates.len()
+	);
+	if undecodable > 0 {
+		warn(format!("Could not decode {} System::Account entries", undecodable));
+	}
+
+	let prefix = ss58_prefix(decoder.meta());
+	for (account, candidate) in &candidates {
+		let referenced_by = if candidate.referenced_by.is_empty() {
+			"no known maps".into()
+		} else {
+			candidate.referenced_by.iter().cloned().collect::<Vec<_>>().join(", ")
+		};
+		println!(
+			"  {} (consumers: {}, providers: {}, sufficients: {}) referenced by {}",
+			fmt_account(account, prefix),
+			candidate.consumers,
+			candidate.providers,
+			candidate.sufficients,
+			referenced_by
+		);
+	}
+
+	Ok(())
+}
@@ -0,0 +1,243 @@
+//! Which pallets refer to the keys of other pallets.
+//!
+//! A value that contains an account or hash which is the first key of a storage map in another
+//! pallet couples both pallets. Removing a pallet or extracting its state has to consider them.
+
+use super::{account_key_offset, is_account_id, Collected};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use scale_info::TypeDef;
+use scale_value::{Value, ValueDef};
+use serde_json::json;
+use std::{
+	collections::{BTreeMap as Map, BTreeSet},
+	fmt::Write,
+	fs,
+	sync::Arc,
+};
+use subxt::Metadata;
+
+/// Number of edges to print.
+const TOP_EDGES: usize = 20;
+
+/// Accounts and hashes that occur in keys and values, with the storage items they occur in.
+#[derive(Default)]
+pub struct References {
+	/// Accounts and hashes that are the first key of a storage map.
+	pub keys: Map<[u8; 32], BTreeSet<Arc<str>>>,
+	/// Accounts and hashes within values.
+	pub values: Map<[u8; 32], BTreeSet<Arc<str>>>,
+}
+
+impl References {
+	pub fn merge(&mut self, other: References) {
+		for (id, items) in other.keys {
+			self.keys.entry(id).or_default().extend(items);
+		}
+		for (id, items) in other.values {
+			self.values.entry(id).or_default().extend(items);
+		}
+	}
+}
+
+/// Storage maps whose first key is 32 bytes long, as `(pallet, item, offset of the key)`.
+pub fn keyed_items(meta: &Metadata) -> Vec<(String, String, usize)> {
+	let mut items = Vec::new();
+
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			if let Some(offset) = account_key_offset(entry, meta) {
+				items.push((pallet.name().to_string(), entry.name().to_string(), offset));
+			}
+		}
+	}
+
+	items
+}
+
+/// Storage items whose values can contain 32 byte types, as `(pallet, item, value type)`.
+pub fn value_items(meta: &Metadata, id_types: &BTreeSet<u32>) -> Vec<(String, String, u32)> {
+	let mut items = Vec::new();
+
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			let ty = entry.entry_type().value_ty();
+			if contains_id(ty, id_types, meta, &mut BTreeSet::new()) {
+				items.push((pallet.name().to_string(), entry.name().to_string(), ty));
+			}
+		}
+	}
+
+	items
+}
+
+/// The named types that are encoded as 32 bytes, like `AccountId32` and `H256`.
+///
+/// Plain byte arrays are left out, since they are mostly randomness or other opaque data.
+pub fn id_types(meta: &Metadata) -> BTreeSet<u32> {
+	meta.types()
+		.types
+		.iter()
+		.filter(|t| !t.ty.path.segments.is_empty() && is_account_id(t.id, meta))
+		.map(|t| t.id)
+		.collect()
+}
+
+fn contains_id(
+	ty: u32,
+	id_types: &BTreeSet<u32>,
+	meta: &Metadata,
+	seen: &mut BTreeSet<u32>,
+) -> bool {
+	if id_types.contains(&ty) {
+		return true
+	}
+	// Types can be recursive.
+	if !seen.insert(ty) {
+		return false
+	}
+	let Some(resolved) = meta.types().resolve(ty) else { return false };
+	let mut check = |ty: u32| contains_id(ty, id_types, meta, seen);
+
+	match &resolved.type_def {
+		TypeDef::Composite(composite) => composite.fields.iter().any(|f| check(f.ty.id)),
+		TypeDef::Variant(variant) =>
+			variant.variants.iter().flat_map(|v| &v.fields).any(|f| check(f.ty.id)),
+		TypeDef::Sequence(seq) => check(seq.type_param.id),
+		TypeDef::Array(array) => check(array.type_param.id),
+		TypeDef::Tuple(tuple) => tuple.fields.iter().any(|f| check(f.id)),
+		_ => false,
+	}
+}
+
+/// All accounts and hashes within a decoded value.
+pub fn scan(value: &Value<u32>, id_types: &BTreeSet<u32>) -> Vec<[u8; 32]> {
+	let mut found = Vec::new();
+	let mut stack = vec![value];
+
+	while let Some(value) = stack.pop() {
+		if id_types.contains(&value.context) {
+			found.extend(super::value_account(value));
+			continue
+		}
+		match &value.value {
+			ValueDef::Composite(composite) => stack.extend(composite.values()),
+			ValueDef::Variant(variant) => stack.extend(variant.values.values()),
+			_ => {},
+		}
+	}
+
+	found
+}
+
+/// References from the values of one pallet to the keys of another.
+#[derive(Default)]
+struct Edge {
+	/// Number of distinct accounts and hashes.
+	references: usize,
+	/// Storage items with the referencing values.
+	from_items: BTreeSet<Arc<str>>,
+	/// Storage maps with the referenced keys.
+	to_items: BTreeSet<Arc<str>>,
+}
+
+/// The edges of the reference graph, by `(from, to)` pallet.
+fn edges(references: &References) -> Map<(String, String), Edge> {
+	let pallet = |item: &Arc<str>| item.split("::").next().unwrap_or_default().to_string();
+	let mut edges = Map::<(String, String), Edge>::new();
+
+	for (id, from_items) in &references.values {
+		let Some(to_items) = references.keys.get(id) else { continue };
+		let mut counted = BTreeSet::new();
+
+		for (from, to) in from_items.iter().cartesian_product(to_items) {
+			let pallets = (pallet(from), pallet(to));
+			if pallets.0 == pallets.1 {
+				continue
+			}
+			let edge = edges.entry(pallets.clone()).or_default();
+			if counted.insert(pallets) {
+				edge.references += 1;
+			}
+			edge.from_items.insert(from.clone());
+			edge.to_items.insert(to.clone());
+		}
+	}
+
+	edges
+}
+
+pub fn print(collected: &Collected) -> Result<()> {
+	let edges = edges(&collected.references);
+
+	println!("Cross-pallet references:");
+	let top = edges.iter().sorted_by_key(|(_, e)| e.references).rev().take(TOP_EDGES);
+	for ((from, to), edge) in top {
+		println!(
+			"  {} -> {}: {} keys, via {} -> {}",
+			from,
+			to,
+			edge.references,
+			edge.from_items.iter().join(", "),
+			edge.to_items.iter().join(", ")
+		);
+	}
+	if edges.len() > TOP_EDGES {
+		println!("  ... and {} more", edges.len() - TOP_EDGES);
+	}
+
+	Ok(())
+}
+
+/// Parse the path of `--reference-graph`, whose extension selects the format.
+pub fn parse_path(path: &str) -> Result<String, String> {
+	match extension(path) {
+		Some("dot" | "gv" | "json") => Ok(path.to_string()),
+		_ => Err("The reference graph must be written to a .dot or .json file".into()),
+	}
+}
+
+fn extension(path: &str) -> Option<&str> {
+	std::path::Path::new(path).extension()?.to_str()
+}
+
+/// Write the reference graph to a `.dot` or `.json` file.
+pub fn save(path: &str, collected: &Collected) -> Result<()> {
+	let edges = edges(&collected.references);
+
+	let out = match extension(path) {
+		Some("dot" | "gv") => {
+			let mut out = String::from("digraph references {\n");
+			for ((from, to), edge) in &edges {
+				writeln!(
+					out,
+					"\t{:?} -> {:?} [label=\"{}\", weight={}];",
+					from, to, edge.references, edge.references
+				)?;
+			}
+			out.push_str("}\n");
+			out
+		},
+		Some("json") => {
+			let nodes = edges.keys().flat_map(|(from, to)| [from, to]).collect::<BTreeSet<_>>();
+			let edges = edges
+				.iter()
+				.map(|((from, to), edge)| {
+					json!({
+						"from": from,
+						"to": to,
+						"references": edge.references,
+						"from_items": edge.from_items.iter().map(|i| &**i).collect::<Vec<_>>(),
+						"to_items": edge.to_items.iter().map(|i| &**i).collect::<Vec<_>>(),
+					})
+				})
+				.collect::<Vec<_>>();
+			serde_json::to_string_pretty(&json!({ "nodes": nodes, "edges": edges }))?
+		},
+		_ => return Err(anyhow!("Unknown format of the reference graph {}", path)),
+	};
+	fs::write(path, out).map_err(|e| anyhow!("Failed to write {}: {}", path, e))?;
+	log::info!("Wrote the reference graph to {}", path);
+
+	Ok(())
+}
@@ -0,0 +1,207 @@
+//! Simulate retention rules that keep only the newest entries of storage maps.
+//!
+//! Many maps are keyed by an index that only grows, like the era in `Staking::ErasStakersPaged` or
+//! the block number in `System::BlockHash`. A rule keeps the entries of the newest indices and
+//! reports how much the older ones occupy.
+
+use super::{sub_keys::FirstKey, Collected};
+use crate::{fmt_bytes, fmt_percent, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use parity_scale_codec::Decode;
+use scale_info::{TypeDef, TypeDefPrimitive};
+use std::fmt;
+use subxt::Metadata;
+
+/// Block time to convert durations with if the metadata does not tell.
+const DEFAULT_BLOCK_TIME: u64 = 6_000;
+
+/// Keep the entries of the newest indices of the matching storage maps.
+#[derive(Clone)]
+pub struct Rule {
+	/// The rule as it was given.
+	raw: String,
+	pallet: String,
+	/// Name of the storage item, or the start of it if it ends with `*`.
+	item: String,
+	keep: Keep,
+}
+
+#[derive(Clone, Copy)]
+enum Keep {
+	/// Number of indices, eg. eras.
+	Indices(u128),
+	/// Duration in milliseconds, which is converted into a number of blocks.
+	Millis(u64),
+}
+
+impl Rule {
+	fn matches(&self, pallet: &str, item: &str) -> bool {
+		let item_matches = match self.item.strip_suffix('*') {
+			Some(start) => item.starts_with(start),
+			None => item == self.item,
+		};
+
+		pallet.eq_ignore_ascii_case(&self.pallet) && item_matches
+	}
+
+	/// Number of indices to keep.
+	fn keep(&self, block_time: u64) -> u128 {
+		match self.keep {
+			Keep::Indices(indices) => indices,
+			Keep::Millis(millis) => (millis / block_time) as u128,
+		}
+	}
+}
+
+impl fmt::Display for Rule {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.raw)
+	}
+}
+
+/// Parse a rule like `Staking::Eras*=28` or `System::BlockHash=7d`.
+pub fn parse_rule(s: &str) -> Result<Rule, String> {
+	let invalid = || format!("Rule must be like Pallet::Item=28 or Pallet::Item=7d, not {}", s);
+	let (name, keep) = s.split_once('=').ok_or_else(invalid)?;
+	let (pallet, item) = name.split_once("::").ok_or_else(invalid)?;
+
+	let keep = if let Some(days) = keep.strip_suffix('d') {
+		Keep::Millis(days.parse::<u64>().map_err(|_| invalid())? * 86_400_000)
+	} else if let Some(hours) = keep.strip_suffix('h') {
+		Keep::Millis(hours.parse::<u64>().map_err(|_| invalid())? * 3_600_000)
+	} else {
+		Keep::Indices(keep.parse().map_err(|_| invalid())?)
+	};
+
+	Ok(Rule { raw: s.into(), pallet: pallet.into(), item: item.into(), keep })
+}
+
+/// The storage maps that any of the rules apply to as `(pallet, item, first key)`.
+///
+/// Only maps whose first key is an unsigned integer that is not hashed away can be simulated.
+pub fn items(meta: &Metadata, rules: &[Rule]) -> Vec<(String, String, FirstKey)> {
+	let mut items = Vec::new();
+
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			if !rules.iter().any(|r| r.matches(pallet.name(), entry.name())) {
+				continue
+			}
+			let first_key = FirstKey::of(entry, meta)
+				.filter(|k| k.hasher.ends_with_key() && is_index(k.ty, meta));
+			match first_key {
+				Some(first_key) =>
+					items.push((pallet.name().to_string(), entry.name().to_string(), first_key)),
+				None => log::warn!(
+					"Cannot simulate retention of {}::{}, it is not keyed by a readable index",
+					pallet.name(),
+					entry.name()
+				),
+			}
+		}
+	}
+
+	items
+}
+
+/// The index that a storage key starts with after the storage prefix.
+pub fn index(first_key: &FirstKey, key: &[u8], meta: &Metadata) -> Option<u128> {
+	let mut input = key.get(first_key.hasher.len_excluding_key()..)?;
+	scale_value::scale::decode_as_type(&mut input, first_key.ty, meta.types()).ok()?.as_u128()
+}
+
+pub fn print(collected: &Collected, rules: &[Rule], meta: &Metadata, units: Units) -> Result<()> {
+	let block_time = block_time(meta);
+	println!("Retention simulation with a block time of {} ms:", block_time);
+
+	for rule in rules {
+		let keep = rule.keep(block_time);
+		// Freed and total size of each matching map.
+		let mut freed_by_item = Vec::new();
+		let mut freed_entries = 0;
+
+		for (name, indices) in &collected.retention {
+			let Some((pallet, item)) = name.split_once("::") else { continue };
+			let Some(newest) = indices.keys().last() else { continue };
+			if !rule.matches(pallet, item) {
+				continue
+			}
+
+			// Everything below the cutoff is dropped, relative to the newest index in the map.
+			let cutoff = (newest + 1).saturating_sub(keep);
+			let (mut freed, mut total) = (0, 0);
+			for (index, sub_key) in indices {
+				if *index < cutoff {
+					freed += sub_key.size;
+					freed_entries += sub_key.entries;
+				}
+				total += sub_key.size;
+			}
+			freed_by_item.push((name, freed, total));
+		}
+
+		if freed_by_item.is_empty() {
+			println!("  {}: no matching storage maps keyed by an index", rule);
+			continue
+		}
+		let freed = freed_by_item.iter().map(|(_, freed, _)| freed).sum::<usize>();
+		let total = freed_by_item.iter().map(|(_, _, total)| total).sum::<usize>();
+		println!(
+			"  {}: keeps {} indices, frees {} of {} ({}) in {} entries",
+			rule,
+			keep,
+			fmt_bytes(freed, false, units),
+			fmt_bytes(total, false, units),
+			fmt_percent(freed, total),
+			freed_entries
+		);
+		for (name, freed, total) in freed_by_item.iter().sorted_by_key(|(_, f, _)| f).rev() {
+			println!(
+				"    {}: frees {} of {}",
+				name,
+				fmt_bytes(*freed, false, units),
+				fmt_bytes(*total, false, units)
+			);
+		}
+	}
+
+	Ok(())
+}
+
+/// Whether a type is an unsigned integer, possibly wrapped in a newtype.
+pub fn is_index(ty: u32, meta: &Metadata) -> bool {
+	let Some(ty) = meta.types().resolve(ty) else { return false };
+
+	match &ty.type_def {
+		TypeDef::Composite(composite) if composite.fields.len() == 1 =>
+			is_index(composite.fields[0].ty.id, meta),
+		TypeDef::Primitive(primitive) => matches!(
+			primitive,
+			TypeDefPrimitive::U8 |
+				TypeDefPrimitive::U16 |
+				TypeDefPrimitive::U32 |
+				TypeDefPrimitive::U64 |
+				TypeDefPrimitive::U128
+		),
+		_ => false,
+	}
+}
+
+/// The expected block time of the chain in milliseconds.
+pub fn block_time(meta: &Metadata) -> u64 {
+	let constant = |pallet: &str, name: &str| {
+		meta.pallet_by_name(pallet)
+			.and_then(|p| p.constant_by_name(name))
+			.and_then(|c| u64::decode(&mut c.value()).ok())
+			.filter(|value| *value > 0)
+	};
+
+	// Blocks are produced every other minimum period of the timestamp without Babe.
+	constant("Babe", "ExpectedBlockTime")
+		.or_else(|| constant("Timestamp", "MinimumPeriod").map(|period| period * 2))
+		.unwrap_or_else(|| {
+			log::warn!("Block time is unknown, assuming {} ms", DEFAULT_BLOCK_TIME);
+			DEFAULT_BLOCK_TIME
+		})
+}
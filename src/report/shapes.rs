@@ -0,0 +1,209 @@
+//! Guessed shapes of the keys and values of storage that is not in the metadata.
+//!
+//! Helps to find out what an uncategorized pallet or item stores, eg. after a runtime upgrade
+//! removed it from the metadata. Shapes are guessed from the lengths of sampled entries, so eg. an
+//! `u128` and a pair of `u64` look the same. Hashers that keep the key are recognized by the hash.
+
+use super::{Collected, RawEntry};
+use crate::{fmt_bytes, fmt_percent, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use parity_scale_codec::{Compact, Decode};
+use sp_crypto_hashing::{blake2_128, twox_128, twox_64};
+use std::collections::BTreeMap as Map;
+use subxt::Metadata;
+
+/// Number of entries per storage prefix whose shapes are guessed.
+const SAMPLES: usize = 100;
+
+/// Number of storage prefixes to show.
+const TOP_PREFIXES: usize = 20;
+
+/// Number of shapes to show per storage prefix.
+const TOP_SHAPES: usize = 3;
+
+/// Entries under a storage prefix that is not in the metadata.
+#[derive(Default)]
+pub struct Unknown {
+	pub entries: usize,
+	pub size: usize,
+	/// The first entries, whose shapes are guessed.
+	pub samples: Vec<RawEntry>,
+}
+
+impl Unknown {
+	pub fn record(&mut self, key: &[u8], value: &[u8]) {
+		self.entries += 1;
+		self.size += key.len() + value.len();
+		if self.samples.len() < SAMPLES {
+			self.samples.push((key.to_vec(), value.to_vec()));
+		}
+	}
+
+	pub fn merge(&mut self, other: Unknown) {
+		self.entries += other.entries;
+		self.size += other.size;
+		let missing = SAMPLES.saturating_sub(self.samples.len());
+		self.samples.extend(other.samples.into_iter().take(missing));
+	}
+}
+
+/// The storage prefix under which an unknown key is grouped, which is its whole key if shorter.
+pub fn prefix(key: &[u8]) -> &[u8] {
+	&key[..key.len().min(32)]
+}
+
+pub fn print(collected: &Collected, meta: &Metadata, units: Units) -> Result<()> {
+	let pallets = meta
+		.pallets()
+		.map(|p| (twox_128(p.name().as_bytes()).to_vec(), p.name().to_string()))
+		.collect::<Map<_, _>>();
+	let unknown = &collected.unknown;
+	println!("Guessed shapes of the storage that is not in the metadata:");
+	if unknown.is_empty() {
+		println!("  All storage is in the metadata");
+		return Ok(())
+	}
+
+	let largest = unknown.iter().sorted_by_key(|(_, u)| u.size).rev();
+	for (prefix, unknown) in largest.take(TOP_PREFIXES) {
+		println!(
+			"  {}: {} entries ({})",
+			fmt_prefix(prefix, &pallets),
+			unknown.entries,
+			fmt_bytes(unknown.size, false, units)
+		);
+		let keys = unknown.samples.iter().map(|(key, _)| key_shape(&key[prefix.len()..]));
+		println!("    keys: {}", fmt_shapes(keys));
+		let values = unknown.samples.iter().map(|(_, value)| value_shape(value));
+		println!("    values: {}", fmt_shapes(values));
+	}
+	if unknown.len() > TOP_PREFIXES {
+		println!("  ... and {} more", unknown.len() - TOP_PREFIXES);
+	}
+
+	Ok(())
+}
+
+/// Format a storage prefix as `Pallet::0x..` if the pallet is known and readable if possible.
+fn fmt_prefix(prefix: &[u8], pallets: &Map<Vec<u8>, String>) -> String {
+	if prefix.len() < 32 {
+		// Well-known keys like `:code` are readable.
+		return match std::str::from_utf8(prefix) {
+			Ok(key) if key.starts_with(':') => key.into(),
+			_ => format!("0x{}", hex::encode(prefix)),
+		}
+	}
+
+	let (pallet, item) = prefix.split_at(16);
+	match pallets.get(pallet) {
+		Some(pallet) => format!("{}::0x{}", pallet, hex::encode(item)),
+		None => format!("0x{}::0x{}", hex::encode(pallet), hex::encode(item)),
+	}
+}
+
+/// The most common shapes with their share of the samples.
+fn fmt_shapes(shapes: impl Iterator<Item = String>) -> String {
+	let counts = shapes.counts();
+	let total = counts.values().sum();
+	let mut formatted = counts
+		.iter()
+		.sorted_by_key(|(shape, count)| (std::cmp::Reverse(**count), *shape))
+		.take(TOP_SHAPES)
+		.map(|(shape, count)| format!("{} {}", fmt_percent(*count, total), shape))
+		.join(", ");
+	if counts.len() > TOP_SHAPES {
+		formatted += &format!(" and {} other shapes", counts.len() - TOP_SHAPES);
+	}
+	formatted
+}
+
+/// The shape of a key after its storage prefix, as the hashers and the shapes of the keys.
+fn key_shape(mut key: &[u8]) -> String {
+	if key.is_empty() {
+		return "plain value".into()
+	}
+
+	let mut parts = Vec::new();
+	while !key.is_empty() {
+		let Some((hasher, inner, rest)) = concat_hasher(key) else {
+			// Opaque hashers, like `Blake2_128`, or keys that follow them.
+			parts.push(format!("{} bytes", key.len()));
+			break
+		};
+		parts.push(format!("{}({})", hasher, fixed_shape(inner).unwrap_or("bytes")));
+		key = rest;
+	}
+	parts.join(", ")
+}
+
+/// The hasher that keeps the key at the start of `key`, with the key and the rest after it.
+fn concat_hasher(key: &[u8]) -> Option<(&'static str, &[u8], &[u8])> {
+	for (name, hash_len) in [("Blake2_128Concat", 16), ("Twox64Concat", 8)] {
+		let hash = |k: &[u8]| match hash_len {
+			16 => blake2_128(k).to_vec(),
+			_ => twox_64(k).to_vec(),
+		};
+		let Some((hashed, rest)) = key.split_at_checked(hash_len) else { continue };
+		// The length of the key is unknown, so try all of them.
+		for key_len in 1..=rest.len() {
+			let (inner, rest) = rest.split_at(key_len);
+			if hash(inner) == hashed {
+				return Some((name, inner, rest))
+			}
+		}
+	}
+
+	None
+}
+
+/// The shape of a value, guessed from its length and length prefix.
+fn value_shape(value: &[u8]) -> String {
+	if let Some(shape) = fixed_shape(value) {
+		return shape.into()
+	}
+	if let Some(shape) = vec_shape(value) {
+		return shape
+	}
+	// Options start with `1` for `Some`.
+	match value.split_first() {
+		Some((1, inner)) => match fixed_shape(inner) {
+			Some(shape) => format!("Option<{}>", shape),
+			None => format!("{} bytes", value.len()),
+		},
+		_ => format!("{} bytes", value.len()),
+	}
+}
+
+/// The shape of a value of a fixed length that is common for numbers, accounts and hashes.
+fn fixed_shape(bytes: &[u8]) -> Option<&'static str> {
+	Some(match bytes.len() {
+		0 => "empty",
+		1 => "u8, bool or None",
+		2 => "u16",
+		4 => "u32",
+		8 => "u64",
+		16 => "u128 (balance)",
+		32 => "[u8; 32] (account or hash)",
+		_ => return None,
+	})
+}
+
+/// The shape of a vector, whose length prefix fits the length of the value.
+fn vec_shape(value: &[u8]) -> Option<String> {
+	let mut input = value;
+	let len = Compact::<u32>::decode(&mut input).ok()?.0 as usize;
+	match len {
+		0 if input.is_empty() => Some("empty vector".into()),
+		0 => None,
+		_ if input.len() == len => Some("Vec<u8> (blob)".into()),
+		_ if !input.is_empty() && input.len().is_multiple_of(len) => {
+			let element = &input[..input.len() / len];
+			match fixed_shape(element) {
+				Some(shape) => Some(format!("Vec<{}>", shape)),
+				None => Some(format!("Vec of {} byte elements", element.len())),
+			}
+		},
+		_ => None,
+	}
+}
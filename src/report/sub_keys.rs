@@ -0,0 +1,129 @@
+//! Entries of storage maps with multiple keys, broken down by their first key.
+//!
+//! Reveals which first key dominates an item, eg. the era in `Staking::ErasStakersPaged`.
+
+use super::{fmt_account, ss58_prefix, value_account, Collected, Decoder};
+use crate::{fmt_bytes, fmt_percent, Units};
+use anyhow::Result;
+use itertools::Itertools;
+use scale_info::TypeDef;
+use std::collections::BTreeMap as Map;
+use subxt::Metadata;
+use subxt_metadata::{StorageEntryMetadata, StorageEntryType, StorageHasher};
+
+/// Number of first keys to show per storage item.
+const TOP_KEYS: usize = 10;
+
+/// Entries that share a first key.
+#[derive(Default)]
+pub struct SubKey {
+	pub entries: usize,
+	pub size: usize,
+}
+
+/// How the first key of a storage map is encoded.
+#[derive(Clone)]
+pub struct FirstKey {
+	pub hasher: StorageHasher,
+	pub ty: u32,
+}
+
+impl FirstKey {
+	/// The first key of a storage map, or `None` for plain storage values.
+	pub fn of(entry: &StorageEntryMetadata, meta: &Metadata) -> Option<Self> {
+		let StorageEntryType::Map { hashers, key_ty, .. } = entry.entry_type() else { return None };
+		let hasher = *hashers.first()?;
+		if hashers.len() == 1 {
+			return Some(Self { hasher, ty: *key_ty })
+		}
+
+		// Multiple hashers mean that the key type is a tuple of all keys.
+		let TypeDef::Tuple(tuple) = &meta.types().resolve(*key_ty)?.type_def else { return None };
+		Some(Self { hasher, ty: tuple.fields.first()?.id })
+	}
+
+	/// The hashed first key within a storage key that starts after the storage prefix.
+	pub fn split<'a>(&self, key: &'a [u8], meta: &Metadata) -> Option<&'a [u8]> {
+		let hash_len = self.hasher.len_excluding_key();
+		if !self.hasher.ends_with_key() {
+			return key.get(..hash_len)
+		}
+
+		// The length of the key itself is only known after decoding it.
+		let mut input = key.get(hash_len..)?;
+		scale_value::scale::decode_as_type(&mut input, self.ty, meta.types()).ok()?;
+		key.get(..key.len() - input.len())
+	}
+}
+
+/// The storage maps with multiple keys as `(pallet, item, first key)`.
+pub fn items(meta: &Metadata) -> Vec<(String, String, FirstKey)> {
+	let mut items = Vec::new();
+
+	for pallet in meta.pallets() {
+		for entry in pallet.storage().map_or(&[][..], |s| s.entries()) {
+			let StorageEntryType::Map { hashers, .. } = entry.entry_type() else { continue };
+			let (Some(first_key), true) = (FirstKey::of(entry, meta), hashers.len() > 1) else {
+				continue
+			};
+
+			items.push((pallet.name().to_string(), entry.name().to_string(), first_key));
+		}
+	}
+
+	items
+}
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let first_keys = items(meta)
+		.into_iter()
+		.map(|(pallet, item, first_key)| (format!("{}::{}", pallet, item), first_key))
+		.collect::<Map<_, _>>();
+	let item_size =
+		|sub_keys: &Map<Vec<u8>, SubKey>| sub_keys.values().map(|s| s.size).sum::<usize>();
+
+	println!("Storage maps with multiple keys by their first key:");
+	for (name, sub_keys) in collected.sub_keys.iter().sorted_by_key(|(_, s)| item_size(s)).rev() {
+		let size = item_size(sub_keys);
+		println!(
+			"  {}: {} in {} first keys",
+			name,
+			fmt_bytes(size, false, units),
+			sub_keys.len()
+		);
+
+		let Some(first_key) = first_keys.get(&**name) else { continue };
+		for (key, sub_key) in sub_keys.iter().sorted_by_key(|(_, s)| s.size).rev().take(TOP_KEYS) {
+			println!(
+				"    {}: {} entries, {} {}",
+				fmt_key(key, first_key, meta),
+				sub_key.entries,
+				fmt_bytes(sub_key.size, false, units),
+				fmt_percent(sub_key.size, size)
+			);
+		}
+		if sub_keys.len() > TOP_KEYS {
+			println!("    ... and {} more", sub_keys.len() - TOP_KEYS);
+		}
+	}
+
+	Ok(())
+}
+
+/// Format a hashed first key as the key itself if it is part of the hash, or as hex otherwise.
+pub fn fmt_key(key: &[u8], first_key: &FirstKey, meta: &Metadata) -> String {
+	let hex = || format!("0x{}", hex::encode(key));
+	if !first_key.hasher.ends_with_key() {
+		return hex()
+	}
+
+	let input = &mut &key[first_key.hasher.len_excluding_key()..];
+	let Ok(value) = scale_value::scale::decode_as_type(input, first_key.ty, meta.types()) else {
+		return hex()
+	};
+	match value_account(&value) {
+		Some(account) => fmt_account(&account, ss58_prefix(meta)),
+		None => value.to_string(),
+	}
+}
@@ -0,0 +1,105 @@
+//! Whether `frame_system` prunes its per-block state as configured.
+//!
+//! Block hashes are kept for `BlockHashCount` blocks and the events with their topics are cleared
+//! at the start of every block. A chain that keeps more has a silently growing state.
+
+use super::{
+	block_number, retention, storage_entry, sub_keys::FirstKey, warn, Collected, Decoder, RawEntry,
+};
+use crate::{fmt_bytes, Units};
+use anyhow::Result;
+use parity_scale_codec::{Compact, Decode};
+use scale_value::{Composite, Value, ValueDef};
+
+pub fn print(collected: &Collected, decoder: &Decoder, units: Units) -> Result<()> {
+	let meta = decoder.meta();
+	let now = block_number(collected, decoder)?;
+	println!("System retention at block {}:", now);
+	let size = |entries: &[RawEntry]| entries.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>();
+
+	let block_hash_count = meta
+		.pallet_by_name("System")
+		.and_then(|p| p.constant_by_name("BlockHashCount"))
+		.and_then(|c| match c.value().len() {
+			4 => u32::decode(&mut c.value()).ok().map(Into::into),
+			_ => u64::decode(&mut c.value()).ok(),
+		});
+	let block_hash = storage_entry(meta, "System", "BlockHash");
+	if let (Ok(entry), Some(count)) = (block_hash, block_hash_count) {
+		let entries = collected.entries("System", "BlockHash");
+		let first_key = FirstKey::of(entry, meta);
+		// The genesis hash is never pruned.
+		let stale = entries
+			.iter()
+			.filter_map(|(key, _)| retention::index(first_key.as_ref()?, &key[32..], meta))
+			.filter(|block| *block != 0 && block + count as u128 <= now)
+			.count();
+		let expected = now.min(count as u128) + 1;
+
+		println!(
+			"  System::BlockHash: {} hashes ({}), expected at most {} for a BlockHashCount of {}",
+			entries.len(),
+			fmt_bytes(size(entries), false, units),
+			expected,
+			count
+		);
+		if stale > 0 {
+			warn(format!("System::BlockHash keeps {} hashes older than BlockHashCount", stale));
+		}
+	}
+
+	let events = collected.entries("System", "Events");
+	if let Some((_, value)) = events.first() {
+		let num_events = Compact::<u32>::decode(&mut &value[..]).map(|c| c.0).unwrap_or_default();
+		let event_count = collected
+			.entries("System", "EventCount")
+			.first()
+			.and_then(|(_, value)| u32::decode(&mut &value[..]).ok())
+			.unwrap_or_default();
+
+		println!(
+			"  System::Events: {} events ({}), EventCount is {}",
+			num_events,
+			fmt_bytes(size(events), false, units),
+			event_count
+		);
+		if num_events != event_count {
+			warn(format!("System::EventCount is {} instead of {}", event_count, num_events));
+		}
+	}
+
+	if storage_entry(meta, "System", "EventTopics").is_ok() {
+		let entries = collected.entries("System", "EventTopics");
+		// Topics point to the events of the current block, since they are cleared with them.
+		let stale = entries
+			.iter()
+			.filter(|(_, value)| {
+				let decoded = decoder.decode("System", "EventTopics", value);
+				decoded.is_ok_and(|topic| has_past_events(&topic, now))
+			})
+			.count();
+
+		println!(
+			"  System::EventTopics: {} topics ({}), {} of them with events of past blocks",
+			entries.len(),
+			fmt_bytes(size(entries), false, units),
+			stale
+		);
+		if stale > 0 {
+			warn(format!("System::EventTopics keeps {} topics of past blocks", stale));
+		}
+	}
+
+	Ok(())
+}
+
+/// Whether the `(block, event index)` pairs of a topic point to blocks other than `now`.
+fn has_past_events(topic: &Value<u32>, now: u128) -> bool {
+	let ValueDef::Composite(indices) = &topic.value else { return false };
+
+	indices.values().any(|index| match &index.value {
+		ValueDef::Composite(Composite::Unnamed(fields)) =>
+			fields.first().and_then(|b| b.as_u128()).is_some_and(|b| b != now),
+		_ => false,
+	})
+}
@@ -0,0 +1,69 @@
+//! Print a few entries of a storage item to see what is actually stored in it.
+//!
+//! The entries with the smallest hash of their key are taken, which is a uniform sample that is
+//! the same on every run.
+
+use crate::{
+	fmt_bytes, index, next_prefix,
+	report::{self, Decoder},
+	Units,
+};
+use anyhow::{anyhow, Result};
+use sp_crypto_hashing::{blake2_128, twox_128};
+use std::collections::BinaryHeap;
+
+/// Number of characters of a decoded value to show.
+const PREVIEW_LEN: usize = 300;
+
+/// Print `count` entries of `pallet::item` with their decoded key and a preview of their value.
+pub fn print(
+	snapshot: &str,
+	decoder: &Decoder,
+	pallet: &str,
+	item: &str,
+	count: usize,
+	units: Units,
+) -> Result<()> {
+	let meta = decoder.meta();
+	let entry = meta
+		.pallet_by_name(pallet)
+		.and_then(|p| p.storage())
+		.and_then(|s| s.entry_by_name(item))
+		.ok_or_else(|| anyhow!("{}::{} is not in the metadata", pallet, item))?;
+
+	let prefix = [twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat();
+	let end = next_prefix(&prefix);
+	let reader = index::open_range(snapshot, None, prefix, end)?;
+
+	// Keeps the entries with the smallest hashes, the largest one on top.
+	let mut sample = BinaryHeap::new();
+	let mut total = 0;
+	for kv in reader {
+		let (key, (value, _)) = kv?;
+		total += 1;
+		sample.push((blake2_128(&key), key, value));
+		if sample.len() > count {
+			sample.pop();
+		}
+	}
+
+	println!("Sampled {} of {} entries of {}::{}:", sample.len(), total, pallet, item);
+	for (_, key, value) in sample.into_sorted_vec() {
+		let keys = report::decode_keys(entry.entry_type(), &key[32..], meta);
+		let shown = match decoder.decode(pallet, item, &value) {
+			Ok(decoded) => report::preview(&decoded.to_string(), PREVIEW_LEN),
+			Err(e) => format!("0x{} ({})", report::preview(&hex::encode(&value), PREVIEW_LEN), e),
+		};
+
+		println!("  Key:   {}", keys);
+		println!(
+			"  Size:  {} key, {} value",
+			fmt_bytes(key.len(), false, units),
+			fmt_bytes(value.len(), false, units)
+		);
+		println!("  Value: {}", shown);
+		println!();
+	}
+
+	Ok(())
+}
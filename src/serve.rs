@@ -0,0 +1,204 @@
+//! Serve the results of an analysis over HTTP.
+//!
+//! The snapshot is only analyzed once on startup, so that dashboards and bots can query the results
+//! repeatedly. Responses are JSON and each connection answers a single request.
+//!
+//! Only `/grep?address=` reads the snapshot again, since the entries of an account are not kept.
+
+use crate::{
+	provenance::{fmt_timestamp, Provenance},
+	report::{grep, parse_address},
+	unknown_size, Analysis, ItemInfo, PalletInfo,
+};
+use anyhow::Result;
+use itertools::Itertools;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::{
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	net::{TcpListener, TcpStream},
+};
+
+/// A snapshot file with the identity to decrypt it.
+pub type Snapshot = (String, Option<String>);
+
+/// Serve the analysis until the user hits Ctrl-C.
+///
+/// `/grep` searches the `snapshot`, if the state came from one.
+pub async fn run(
+	analysis: Analysis,
+	snapshot: Option<Snapshot>,
+	address: &str,
+	port: u16,
+) -> Result<()> {
+	let listener = TcpListener::bind((address, port)).await?;
+	log::info!("Serving on http://{}", listener.local_addr()?);
+	let analysis = Arc::new(analysis);
+	let snapshot = Arc::new(snapshot);
+
+	loop {
+		let stream = tokio::select! {
+			accepted = listener.accept() => accepted?.0,
+			_ = tokio::signal::ctrl_c() => return Ok(()),
+		};
+		let analysis = Arc::clone(&analysis);
+		let snapshot = Arc::clone(&snapshot);
+
+		tokio::spawn(async move {
+			if let Err(e) = handle_connection(stream, &analysis, snapshot.as_ref().as_ref()).await {
+				log::debug!("Failed to answer request: {}", e);
+			}
+		});
+	}
+}
+
+async fn handle_connection(
+	stream: TcpStream,
+	analysis: &Analysis,
+	snapshot: Option<&Snapshot>,
+) -> Result<()> {
+	let mut stream = BufReader::new(stream);
+	let mut request_line = String::new();
+	stream.read_line(&mut request_line).await?;
+
+	// Skip the headers since no endpoint needs them.
+	let mut header = String::new();
+	while stream.read_line(&mut header).await? > 2 {
+		header.clear();
+	}
+
+	let mut parts = request_line.split_whitespace();
+	let (status, body) = match (parts.next(), parts.next()) {
+		(Some("GET"), Some(target)) => route(target, analysis, snapshot),
+		(Some(_), Some(_)) => ("405 Method Not Allowed", error("Only GET is supported")),
+		_ => ("400 Bad Request", error("Malformed request")),
+	};
+
+	let body = body.to_string();
+	let response = format!(
+		"HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		status,
+		body.len(),
+		body
+	);
+	stream.get_mut().write_all(response.as_bytes()).await?;
+
+	Ok(())
+}
+
+/// Answer a request for the given path and query.
+fn route(target: &str, analysis: &Analysis, snapshot: Option<&Snapshot>) -> (&'static str, Value) {
+	let path = target.split('?').next().unwrap_or_default().trim_end_matches('/');
+	let segments = path.split('/').skip(1).collect::<Vec<_>>();
+
+	match segments.as_slice() {
+		["pallets"] => {
+			let pallets = analysis
+				.found_by_pallet
+				.values()
+				.sorted_by_key(|p| p.size)
+				.rev()
+				.map(pallet_json)
+				.collect::<Vec<_>>();
+			let (unknown_pallets, unknown_items) = unknown_size(&analysis.found_by_pallet);
+			let total = analysis.found_by_pallet.values().map(|p| p.size).sum::<usize>();
+			let body = json!({
+				"provenance": provenance_json(&analysis.provenance),
+				"partial": analysis.stopped.is_some(),
+				"unknown": {
+					"pallets_size": unknown_pallets,
+					"items_size": unknown_items,
+					"ratio": (unknown_pallets + unknown_items) as f64 / total.max(1) as f64,
+				},
+				"pallets": pallets,
+			});
+			("200 OK", body)
+		},
+		["pallets", name, "items"] => {
+			// Pallet names are matched case-insensitive like with `--pallet`.
+			let Some(pallet) =
+				analysis.found_by_pallet.values().find(|p| p.name.eq_ignore_ascii_case(name))
+			else {
+				return ("404 Not Found", error(&format!("Unknown pallet {}", name)))
+			};
+			let items = pallet
+				.items
+				.values()
+				.sorted_by_key(|i| i.key_len + i.value_len)
+				.rev()
+				.map(item_json)
+				.collect::<Vec<_>>();
+			let body = json!({
+				"provenance": provenance_json(&analysis.provenance),
+				"partial": analysis.stopped.is_some(),
+				"items": items,
+			});
+			("200 OK", body)
+		},
+		["grep"] => {
+			let Some(address) = query(target, "address") else {
+				return ("400 Bad Request", error("Missing the address to search for"))
+			};
+			let subject = match parse_address(address) {
+				Ok(subject) => subject,
+				Err(e) => return ("400 Bad Request", error(&e)),
+			};
+			let Some((path, identity)) = snapshot else {
+				return ("501 Not Implemented", error("Only a snapshot file can be searched"))
+			};
+			// Reading the snapshot takes a while, so the other requests move to another thread.
+			let found = tokio::task::block_in_place(|| {
+				grep((path, identity.as_deref()), &subject, &analysis.decoder, false)
+			});
+			match found {
+				Ok(body) => ("200 OK", body),
+				Err(e) => ("500 Internal Server Error", error(&format!("{:#}", e))),
+			}
+		},
+		_ => ("404 Not Found", error(&format!("Unknown endpoint {}", path))),
+	}
+}
+
+/// Value of a parameter in the query of a request target.
+fn query<'a>(target: &'a str, name: &str) -> Option<&'a str> {
+	let (_, query) = target.split_once('?')?;
+	query.split('&').find_map(|param| param.strip_prefix(name)?.strip_prefix('='))
+}
+
+fn provenance_json(provenance: &Provenance) -> Value {
+	json!({
+		"block": provenance.block,
+		"runtime": provenance.runtime.as_ref().map(|(name, _)| name),
+		"spec_version": provenance.runtime.as_ref().map(|(_, version)| version),
+		"pdu_version": provenance.version,
+		"analyzed_at": fmt_timestamp(provenance.analyzed_at),
+	})
+}
+
+fn pallet_json(pallet: &PalletInfo) -> Value {
+	let items = pallet.items.values();
+
+	json!({
+		"name": pallet.name,
+		"size": pallet.size,
+		"keys": items.clone().map(|i| i.num_entries).sum::<usize>(),
+		"key_size": items.clone().map(|i| i.key_len).sum::<usize>(),
+		"value_size": items.map(|i| i.value_len).sum::<usize>(),
+	})
+}
+
+fn item_json(item: &ItemInfo) -> Value {
+	json!({
+		"name": item.name,
+		"size": item.key_len + item.value_len,
+		"keys": item.num_entries,
+		"key_size": item.key_len,
+		"value_size": item.value_len,
+		"empty_values": item.empty_values,
+		"zero_values": item.zero_values,
+	})
+}
+
+fn error(message: &str) -> Value {
+	json!({ "error": message })
+}
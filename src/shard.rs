@@ -0,0 +1,284 @@
+//! Analyze a snapshot in shards, possibly on different machines, and merge their results.
+//!
+//! Each shard analyzes a key range with `--start-key` and `--end-key` and writes its results with
+//! `--json`. `pdu merge-reports` then checks that the ranges fit together and prints the results of
+//! the whole snapshot. Only the sizes are merged, not the reports.
+
+use crate::{
+	merge_found, print_results, print_unknown,
+	report::{LargeEntry, Largest},
+	Analysis, Args, Cli, ItemInfo, PalletInfo, Units, ValueSizes,
+};
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use itertools::Itertools;
+use serde_json::{json, Value};
+use std::{collections::BTreeMap as Map, fs, path::Path, sync::Arc};
+
+/// The results of one shard.
+struct Shard {
+	path: String,
+	network: String,
+	block: Option<u128>,
+	start_key: Option<Vec<u8>>,
+	end_key: Option<Vec<u8>>,
+	partial: bool,
+	found_by_pallet: Map<String, PalletInfo>,
+	/// The largest entries per storage item with `--top`, which are not merged.
+	largest: Map<Arc<str>, Largest>,
+	/// Whether to write the distributions of the value sizes with `--stats`, which are not merged.
+	stats: bool,
+}
+
+/// Write the results of an analysis so that they can be merged with other shards.
+pub fn save(path: &str, analysis: &Analysis, args: &Args) -> Result<()> {
+	let shard = Shard {
+		path: path.into(),
+		network: args.network.clone(),
+		block: analysis.provenance.block,
+		start_key: args.start_key.clone(),
+		end_key: args.end_key.clone(),
+		partial: analysis.stopped.is_some(),
+		found_by_pallet: analysis.found_by_pallet.clone(),
+		largest: analysis.collected.largest.clone(),
+		stats: args.stats,
+	};
+
+	store(&shard)
+}
+
+/// Write the results of an analysis as one file per pallet and an index of them.
+///
+/// The pallet files are like the pallets of [`save`], so that they can be diffed one by one.
+pub fn save_split(dir: &Path, analysis: &Analysis, args: &Args) -> Result<()> {
+	fs::create_dir_all(dir)
+		.map_err(|e| anyhow!("Failed to create directory {}: {}", dir.display(), e))?;
+
+	let mut pallets = Vec::new();
+	for pallet in analysis.found_by_pallet.values() {
+		let file = format!("{}.json", pallet.name);
+		let json = pallet_json(pallet, &analysis.collected.largest, args.stats);
+		write_json(&dir.join(&file), &json)?;
+		pallets.push(json!({ "name": pallet.name, "size": pallet.size, "file": file }));
+	}
+
+	let index = json!({
+		"network": args.network,
+		"block": analysis.provenance.block.map(|b| b.to_string()),
+		"start_key": fmt_key(&args.start_key),
+		"end_key": fmt_key(&args.end_key),
+		"partial": analysis.stopped.is_some(),
+		"pallets": pallets,
+	});
+	write_json(&dir.join("index.json"), &index)?;
+	log::info!("Wrote the results of {} pallets to {}", pallets.len(), dir.display());
+	Ok(())
+}
+
+/// Merge the results of multiple shards and print them like a single analysis.
+pub fn merge(paths: &[String], out: Option<&str>, verbose: bool, units: Units) -> Result<()> {
+	let mut shards = paths.iter().map(|path| load(path)).collect::<Result<Vec<_>>>()?;
+	// Shards without a start key begin at the first key.
+	shards.sort_by(|a, b| a.start_key.cmp(&b.start_key));
+	// Only the shard with `System::Number` knows the block.
+	let block = shards.iter().find_map(|s| s.block);
+	let network = shards[0].network.clone();
+
+	for shard in &shards {
+		if shard.network != network || shard.block.is_some_and(|b| Some(b) != block) {
+			return Err(anyhow!(
+				"{} is of {} at block {:?}, but other shards are of {} at block {:?}",
+				shard.path,
+				shard.network,
+				shard.block,
+				network,
+				block
+			))
+		}
+	}
+	for (before, after) in shards.iter().tuple_windows() {
+		let (end, start) = (&before.end_key, &after.start_key);
+		let (before, after) = (&before.path, &after.path);
+		match (end, start) {
+			(Some(end), Some(start)) if end == start => (),
+			(Some(end), Some(start)) if end < start =>
+				return Err(anyhow!("Keys between {} and {} are in no shard", before, after)),
+			_ => return Err(anyhow!("Shards {} and {} overlap", before, after)),
+		}
+	}
+	if shards[0].start_key.is_some() || shards.last().is_some_and(|s| s.end_key.is_some()) {
+		log::warn!("The shards do not cover all keys");
+	}
+
+	let mut found_by_pallet = Map::new();
+	for shard in &mut shards {
+		merge_found(&mut found_by_pallet, std::mem::take(&mut shard.found_by_pallet));
+	}
+
+	// The merged results can be merged again, eg. by a coordinator per data center.
+	let merged = Shard {
+		path: out.unwrap_or_default().into(),
+		network,
+		block,
+		start_key: shards[0].start_key.clone(),
+		end_key: shards.last().and_then(|s| s.end_key.clone()),
+		partial: shards.iter().any(|s| s.partial),
+		found_by_pallet,
+		largest: Map::new(),
+		stats: false,
+	};
+	if out.is_some() {
+		store(&merged)?;
+	}
+
+	// Parse the arguments like for a single snapshot to get the same defaults.
+	let cli = Cli::parse_from(["pdu", "--network", &merged.network]);
+	let mut args = cli.args.expect("Parsed without a subcommand; qed");
+	args.units = units;
+
+	let block = block.map_or("unknown block".into(), |b| format!("block {}", b));
+	println!("Merged {} shards of a snapshot at {}", shards.len(), block);
+	let partial = merged.partial.then_some("a shard stopped early");
+	// Neither compression nor key groups are kept per shard.
+	let (compressed, groups) = (Default::default(), Default::default());
+	let found = &merged.found_by_pallet;
+	print_results(found, &compressed, &groups, &merged.largest, verbose, partial, &args);
+	print_unknown(&merged.found_by_pallet, units);
+	Ok(())
+}
+
+fn store(shard: &Shard) -> Result<()> {
+	let json = json!({
+		"network": shard.network,
+		// Numbers of 128 bits are stored as strings, since JSON parsers rarely support them.
+		"block": shard.block.map(|b| b.to_string()),
+		"start_key": fmt_key(&shard.start_key),
+		"end_key": fmt_key(&shard.end_key),
+		"partial": shard.partial,
+		"pallets": shard
+			.found_by_pallet
+			.values()
+			.map(|pallet| pallet_json(pallet, &shard.largest, shard.stats))
+			.collect::<Vec<_>>(),
+	});
+
+	fs::write(&shard.path, serde_json::to_string_pretty(&json)?)?;
+	log::info!("Wrote the results to {}", shard.path);
+	Ok(())
+}
+
+fn fmt_key(key: &Option<Vec<u8>>) -> Option<String> {
+	key.as_ref().map(|k| format!("0x{}", hex::encode(k)))
+}
+
+fn write_json(path: &Path, json: &Value) -> Result<()> {
+	// End with a newline, since the files are meant to be kept in git.
+	fs::write(path, serde_json::to_string_pretty(json)? + "\n")
+		.map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+}
+
+fn load(path: &str) -> Result<Shard> {
+	let json: Value = serde_json::from_str(
+		&fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?,
+	)?;
+	let invalid = |field: &str| anyhow!("{} has no valid {}", path, field);
+	let key = |field: &str| -> Result<Option<Vec<u8>>> {
+		match &json[field] {
+			Value::Null => Ok(None),
+			Value::String(key) => Ok(Some(
+				hex::decode(key.trim_start_matches("0x")).map_err(|_| invalid(field))?,
+			)),
+			_ => Err(invalid(field)),
+		}
+	};
+
+	let mut found_by_pallet = Map::new();
+	for pallet in json["pallets"].as_array().ok_or_else(|| invalid("pallets"))? {
+		let name = pallet["name"].as_str().ok_or_else(|| invalid("pallet name"))?;
+		let mut items = Map::new();
+		for item in pallet["items"].as_array().ok_or_else(|| invalid("items"))? {
+			let number = |field: &str| {
+				item[field].as_u64().map(|n| n as usize).ok_or_else(|| invalid(field))
+			};
+			let item = ItemInfo {
+				name: item["name"].as_str().ok_or_else(|| invalid("item name"))?.into(),
+				key_len: number("key_size")?,
+				value_len: number("value_size")?,
+				num_entries: number("keys")?,
+				size_sq: item["size_sq"]
+					.as_str()
+					.and_then(|s| s.parse().ok())
+					.ok_or_else(|| invalid("size_sq"))?,
+				empty_values: number("empty_values")?,
+				zero_values: number("zero_values")?,
+				value_sizes: ValueSizes::default(),
+			};
+			items.insert(item.name.clone(), item);
+		}
+		let size = pallet["size"].as_u64().ok_or_else(|| invalid("pallet size"))? as usize;
+		found_by_pallet.insert(name.into(), PalletInfo { name: name.into(), size, items });
+	}
+
+	Ok(Shard {
+		path: path.into(),
+		network: json["network"].as_str().ok_or_else(|| invalid("network"))?.into(),
+		block: json["block"].as_str().and_then(|b| b.parse().ok()),
+		start_key: key("start_key")?,
+		end_key: key("end_key")?,
+		partial: json["partial"].as_bool().unwrap_or_default(),
+		found_by_pallet,
+		largest: Map::new(),
+		stats: false,
+	})
+}
+
+fn pallet_json(pallet: &PalletInfo, largest: &Map<Arc<str>, Largest>, stats: bool) -> Value {
+	let items = pallet
+		.items
+		.values()
+		.map(|item| {
+			let mut json = json!({
+				"name": item.name,
+				"key_size": item.key_len,
+				"value_size": item.value_len,
+				"keys": item.num_entries,
+				"size_sq": item.size_sq.to_string(),
+				"empty_values": item.empty_values,
+				"zero_values": item.zero_values,
+			});
+			if stats && item.value_sizes.count > 0 {
+				json["value_sizes"] = value_sizes_json(&item.value_sizes);
+			}
+			// Only analyses with `--top` know the largest entries.
+			if let Some(largest) = largest.get(format!("{}::{}", pallet.name, item.name).as_str()) {
+				json["largest"] = largest.entries.iter().map(largest_json).collect();
+			}
+			json
+		})
+		.collect::<Vec<_>>();
+
+	json!({
+		"name": pallet.name,
+		"size": pallet.size,
+		"items": items,
+	})
+}
+
+fn largest_json(entry: &LargeEntry) -> Value {
+	json!({
+		"key": format!("0x{}", hex::encode(&entry.key)),
+		"value_size": entry.value_len,
+		"compressed_size": entry.compressed_len,
+	})
+}
+
+fn value_sizes_json(sizes: &ValueSizes) -> Value {
+	json!({
+		"min": sizes.min,
+		"max": sizes.max,
+		"mean": sizes.mean(),
+		"median": sizes.quantile(0.5),
+		"p95": sizes.quantile(0.95),
+		"p99": sizes.quantile(0.99),
+	})
+}
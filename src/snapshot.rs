@@ -0,0 +1,520 @@
+//! Streaming access to try-runtime-cli snapshots.
+
+use crate::{
+	encryption::{self, Tool},
+	error::{PduError, Result},
+	process::Output,
+	remote::{self, Remote},
+};
+use parity_scale_codec::{Compact, Decode, DecodeAll, Encode, IoReader};
+use sp_crypto_hashing::{twox_128, twox_64};
+use std::{
+	fs::{self, File},
+	io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+	marker::PhantomData,
+	path::{Path, PathBuf},
+};
+use subxt_metadata::StorageHasher;
+
+/// A raw Key-Value pair of a snapshot together with its reference count.
+pub type SnapshotEntry = (Vec<u8>, (Vec<u8>, i32));
+
+/// Reads the entries of a snapshot one by one.
+///
+/// The snapshot is decoded as a stream, so it never needs to fit into memory or be seekable.
+/// Iteration stops after the first error.
+pub struct SnapshotReader<R: Read> {
+	input: IoReader<BufReader<R>>,
+	state_version: u8,
+	num_keys: usize,
+	/// Length of the header before the entries.
+	header_len: u64,
+	read: usize,
+	skip_values: bool,
+	/// Entries before this key are skipped without reading their values.
+	start_key: Option<Vec<u8>>,
+	/// Iteration stops at this key.
+	end_key: Option<Vec<u8>>,
+	/// Whether the end key was reached.
+	past_end: bool,
+	/// Rate of the keys whose values are read, see [`is_sampled`].
+	sample: Option<f64>,
+	/// Skips over the given number of bytes and returns how many there were.
+	skip: fn(&mut BufReader<R>, u64) -> io::Result<u64>,
+}
+
+/// Where the bytes of a snapshot are read from.
+pub enum Input {
+	Stdin(io::Stdin),
+	File(File),
+	/// The output of tools like `age` or `aws`, which only tell at its end whether it was valid.
+	Tools(Output),
+}
+
+impl Input {
+	/// Check that the input was valid after reading what is needed of it.
+	///
+	/// The output of tools is read to the end for that, see [`Output::finish`].
+	pub fn finish(self) -> Result<()> {
+		match self {
+			Input::Stdin(_) | Input::File(_) => Ok(()),
+			Input::Tools(output) => output.finish(),
+		}
+	}
+}
+
+impl Read for Input {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match self {
+			Input::Stdin(stdin) => stdin.read(buf),
+			Input::File(file) => file.read(buf),
+			Input::Tools(output) => output.read(buf),
+		}
+	}
+}
+
+impl Seek for Input {
+	fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+		match self {
+			Input::File(file) => file.seek(pos),
+			Input::Stdin(_) | Input::Tools(_) => Err(io::ErrorKind::Unsupported.into()),
+		}
+	}
+}
+
+impl SnapshotReader<Input> {
+	/// Open a snapshot file or read it from stdin if the path is `-`.
+	///
+	/// Paths that start with `s3://` or `gs://` are downloaded while reading, see [`remote`].
+	pub fn open(path: &str) -> Result<Self> {
+		Self::open_encrypted(path, None)
+	}
+
+	/// Like [`Self::open`], but decrypts snapshots that end with `.age` or `.gpg`.
+	///
+	/// Decrypting with `age` needs the `identity` file of a recipient, `gpg` uses its keyring.
+	pub fn open_encrypted(path: &str, identity: Option<&str>) -> Result<Self> {
+		let input = if path == "-" {
+			log::info!("Loading snapshot from stdin");
+			Input::Stdin(std::io::stdin())
+		} else if Remote::of(path).is_some() {
+			let download = remote::download(path)?;
+			match Tool::of(path) {
+				Some(_) => Input::Tools(encryption::decrypt_output(path, identity, download)?),
+				None => Input::Tools(download),
+			}
+		} else if Tool::of(path).is_some() {
+			Input::Tools(encryption::decrypted(path, identity)?)
+		} else {
+			log::info!("Loading snapshot from file");
+			let file = File::open(path).map_err(|e| {
+				PduError::io(format!("Failed to load snapshot file from {}", path), e)
+			})?;
+			Input::File(file)
+		};
+
+		let is_file = matches!(input, Input::File(_));
+		let mut reader = Self::new(input)?;
+		if is_file {
+			reader.skip = skip_by_seeking;
+		}
+		Ok(reader)
+	}
+
+	/// Continue at the last entry before `key` that `offsets` know, without decoding the entries
+	/// in between. The offsets must be of this snapshot.
+	///
+	/// Only snapshot files can seek, so other inputs just read on.
+	pub fn seek(mut self, offsets: &KeyOffsets, key: &[u8]) -> Result<Self> {
+		let Some((number, offset)) = offsets.before(key) else { return Ok(self) };
+		let is_file = matches!(self.input.0.get_ref(), Input::File(_));
+		if !is_file || number as usize <= self.read || number as usize > self.num_keys {
+			return Ok(self)
+		}
+
+		self.input
+			.0
+			.seek(SeekFrom::Start(offset))
+			.map_err(|e| PduError::io("Failed to seek in the snapshot", e))?;
+		self.read = number as usize;
+		Ok(self)
+	}
+
+	/// Check that the snapshot was valid after reading the entries that are needed.
+	///
+	/// Decrypting or downloading tools only tell at the end of a snapshot whether it was valid, so
+	/// the rest of their output is read. Dropping the reader without this kills them instead.
+	pub fn finish(self) -> Result<()> {
+		self.input.0.into_inner().finish()
+	}
+
+	/// Read everything after the entries, like the storage root and header, and [`Self::finish`].
+	///
+	/// Skips over all entries that were not read yet.
+	pub fn into_trailer(mut self) -> Result<Vec<u8>> {
+		let trailer = self.read_trailer()?;
+		self.finish()?;
+		Ok(trailer)
+	}
+}
+
+impl<R: Read> SnapshotReader<R> {
+	/// Read the header of a snapshot.
+	pub fn new(reader: R) -> Result<Self> {
+		let mut input = IoReader(BufReader::new(reader));
+		let invalid = |e| PduError::SnapshotFormat(format!("Invalid snapshot header: {}", e));
+
+		let snapshot_version = Compact::<u16>::decode(&mut input).map_err(invalid)?;
+		if snapshot_version.0 != 4 {
+			log::warn!("Snapshot version is not 4 but {}", snapshot_version.0);
+		}
+
+		let state_version: u8 = u8::decode(&mut input).map_err(invalid)?;
+		if state_version != 1 {
+			log::warn!("State version is not 1 but {}", state_version);
+		}
+
+		let num_keys = Compact::<u32>::decode(&mut input).map(|l| l.0 as usize).map_err(invalid)?;
+		let header_len = (snapshot_version, state_version, Compact(num_keys as u32)).encoded_size();
+
+		Ok(Self {
+			input,
+			state_version,
+			num_keys,
+			header_len: header_len as u64,
+			read: 0,
+			skip_values: false,
+			start_key: None,
+			end_key: None,
+			past_end: false,
+			sample: None,
+			skip: skip_by_reading,
+		})
+	}
+
+	/// Skip over all values without reading them into memory. They are returned as empty.
+	///
+	/// Snapshot files seek past the values, so that they are not even read from disk.
+	pub fn skip_values(mut self, skip: bool) -> Self {
+		self.skip_values = skip;
+		self
+	}
+
+	/// Only read the values of a uniform sample of the keys with this rate, see [`is_sampled`].
+	///
+	/// The values of the other keys are skipped like with [`Self::skip_values`].
+	pub fn sample(mut self, rate: Option<f64>) -> Self {
+		self.sample = rate;
+		self
+	}
+
+	/// Only return the entries from `start` on and before `end`.
+	///
+	/// The entries of a snapshot are sorted by key, so the ones before `start` are skipped over
+	/// without reading their values and reading stops at `end`.
+	pub fn range(mut self, start: Option<Vec<u8>>, end: Option<Vec<u8>>) -> Self {
+		self.start_key = start;
+		self.end_key = end;
+		self
+	}
+
+	/// Total number of entries in the snapshot.
+	pub fn num_keys(&self) -> usize {
+		self.num_keys
+	}
+
+	/// State version of the trie that the snapshot was taken from.
+	pub fn state_version(&self) -> u8 {
+		self.state_version
+	}
+
+	/// Start to record the offsets of the entries, which must all be read with their values.
+	pub fn key_offsets(&self) -> KeyOffsets {
+		KeyOffsets { keys: Vec::new(), next: (0, self.header_len) }
+	}
+
+	/// Read everything after the entries, like the storage root and header.
+	///
+	/// Skips over all entries that were not read yet.
+	fn read_trailer(&mut self) -> Result<Vec<u8>> {
+		self.skip_values = true;
+		self.past_end = false;
+		self.end_key = None;
+		for entry in &mut *self {
+			entry.map_err(|e| PduError::SnapshotFormat(e.to_string()))?;
+		}
+
+		let mut trailer = Vec::new();
+		self.input
+			.0
+			.read_to_end(&mut trailer)
+			.map_err(|e| PduError::io("Failed to read trailer", e))?;
+		Ok(trailer)
+	}
+
+	/// Decode the next entry, with an empty value if it is skipped.
+	fn decode_entry(&mut self) -> Result<SnapshotEntry, parity_scale_codec::Error> {
+		let key = Vec::<u8>::decode(&mut self.input)?;
+		let before_start = self.start_key.as_ref().is_some_and(|start| key < *start);
+		let unsampled = self.sample.is_some_and(|rate| !is_sampled(&key, rate));
+
+		let value = if self.skip_values || before_start || unsampled {
+			self.skip_value()?;
+			Vec::new()
+		} else {
+			Vec::<u8>::decode(&mut self.input)?
+		};
+		let ref_count = i32::decode(&mut self.input)?;
+
+		Ok((key, (value, ref_count)))
+	}
+
+	fn skip_value(&mut self) -> Result<(), parity_scale_codec::Error> {
+		let value_len = Compact::<u32>::decode(&mut self.input)?.0 as u64;
+		let skipped = (self.skip)(&mut self.input.0, value_len).map_err(|_| "Failed to skip value")?;
+		if skipped != value_len {
+			return Err("Snapshot ended within a value".into())
+		}
+
+		Ok(())
+	}
+}
+
+impl<R: Read> Iterator for SnapshotReader<R> {
+	type Item = Result<SnapshotEntry, parity_scale_codec::Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.read < self.num_keys && !self.past_end {
+			self.read += 1;
+
+			let entry = self.decode_entry();
+			let key = match &entry {
+				Ok((key, _)) => key,
+				Err(_) => {
+					self.read = self.num_keys;
+					return Some(entry)
+				},
+			};
+			if self.start_key.as_ref().is_some_and(|start| key < start) {
+				continue
+			}
+			if self.end_key.as_ref().is_some_and(|end| key >= end) {
+				self.past_end = true;
+				return None
+			}
+
+			return Some(entry)
+		}
+
+		None
+	}
+}
+
+/// Whether a key is part of a uniform sample with the given rate.
+///
+/// Uses the hash of the key instead of a random number to keep runs reproducible.
+pub fn is_sampled(key: &[u8], rate: f64) -> bool {
+	let hash = u64::from_le_bytes(twox_64(key));
+	(hash as f64) < rate * u64::MAX as f64
+}
+
+/// Skip bytes of any input by reading and discarding them.
+fn skip_by_reading<R: Read>(input: &mut BufReader<R>, len: u64) -> io::Result<u64> {
+	io::copy(&mut input.take(len), &mut io::sink())
+}
+
+/// Skip bytes of a file without reading them. A snapshot that ends within them fails to decode the
+/// reference count after the value instead.
+fn skip_by_seeking(input: &mut BufReader<Input>, len: u64) -> io::Result<u64> {
+	input.seek_relative(len as i64)?;
+	Ok(len)
+}
+
+/// Number of entries from one key in [`KeyOffsets`] to the next.
+const KEY_STRIDE: u64 = 1024;
+
+/// The byte offsets of every 1024th entry of a snapshot, sorted by key.
+///
+/// Ranges of the snapshot are then read without decoding the entries before them, see
+/// [`SnapshotReader::seek`]. Recorded with [`SnapshotReader::key_offsets`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Encode, Decode)]
+pub struct KeyOffsets {
+	/// Key, number and byte offset of the recorded entries.
+	keys: Vec<(Vec<u8>, u64, u64)>,
+	/// Number and byte offset of the next entry.
+	next: (u64, u64),
+}
+
+impl KeyOffsets {
+	/// Record the next entry of the snapshot.
+	pub fn record(&mut self, (key, (value, ref_count)): &SnapshotEntry) {
+		let (number, offset) = self.next;
+		if number % KEY_STRIDE == 0 {
+			self.keys.push((key.clone(), number, offset));
+		}
+		let len = key.encoded_size() + value.encoded_size() + ref_count.encoded_size();
+		self.next = (number + 1, offset + len as u64);
+	}
+
+	/// Number and byte offset of the last recorded entry at or before `key`.
+	fn before(&self, key: &[u8]) -> Option<(u64, u64)> {
+		let after = self.keys.partition_point(|(recorded, ..)| recorded.as_slice() <= key);
+		let (_, number, offset) = self.keys.get(after.checked_sub(1)?)?;
+		Some((*number, *offset))
+	}
+}
+
+/// Writes a snapshot entry by entry.
+///
+/// The number of entries precedes them in a snapshot, so they are buffered in a temporary file
+/// next to the snapshot until it is finished.
+pub struct SnapshotWriter {
+	path: PathBuf,
+	buffer_path: PathBuf,
+	buffer: BufWriter<File>,
+	state_version: u8,
+	num_keys: u32,
+}
+
+impl SnapshotWriter {
+	/// Start a snapshot at `path` with the state version of the snapshot it is derived from.
+	pub fn create(path: impl Into<PathBuf>, state_version: u8) -> Result<Self> {
+		let path = path.into();
+		let mut buffer_path = path.clone().into_os_string();
+		buffer_path.push(".part");
+		let buffer_path = PathBuf::from(buffer_path);
+		let buffer =
+			BufWriter::new(File::create(&buffer_path).map_err(|e| write_error(&buffer_path, e))?);
+
+		Ok(Self { path, buffer_path, buffer, state_version, num_keys: 0 })
+	}
+
+	/// Append an entry. Entries are written in the order they are given.
+	pub fn write(&mut self, entry: &SnapshotEntry) -> Result<()> {
+		self.buffer
+			.write_all(&entry.encode())
+			.map_err(|e| write_error(&self.buffer_path, e))?;
+		self.num_keys += 1;
+		Ok(())
+	}
+
+	/// Write the snapshot with the given trailer, as returned by `SnapshotReader::into_trailer`.
+	pub fn finish(mut self, trailer: &[u8]) -> Result<()> {
+		self.buffer.flush().map_err(|e| write_error(&self.buffer_path, e))?;
+		self.assemble(trailer).map_err(|e| write_error(&self.path, e))?;
+		fs::remove_file(&self.buffer_path).map_err(|e| write_error(&self.buffer_path, e))?;
+
+		log::info!("Wrote {} keys to {}", self.num_keys, self.path.display());
+		Ok(())
+	}
+
+	/// Write the header, the buffered entries and the trailer to the snapshot.
+	fn assemble(&self, trailer: &[u8]) -> io::Result<()> {
+		// Snapshot version, state version and number of entries, see `SnapshotReader::new`.
+		let mut out = BufWriter::new(File::create(&self.path)?);
+		out.write_all(&(Compact(4u16), self.state_version, Compact(self.num_keys)).encode())?;
+		io::copy(&mut File::open(&self.buffer_path)?, &mut out)?;
+		out.write_all(trailer)?;
+		out.flush()
+	}
+}
+
+fn write_error(path: &Path, e: io::Error) -> PduError {
+	PduError::io(format!("Failed to write {}", path.display()), e)
+}
+
+/// The smallest key that is larger than all keys with this prefix.
+///
+/// There is none if the prefix is all `0xff`.
+pub fn next_prefix(prefix: &[u8]) -> Option<Vec<u8>> {
+	// Increment the last byte that is not `0xff` and drop the ones after it.
+	let last = prefix.iter().rposition(|b| *b != 0xff)?;
+	let mut next = prefix[..=last].to_vec();
+	next[last] += 1;
+	Some(next)
+}
+
+/// Iterates over a storage map in a snapshot and decodes its keys and values.
+///
+/// Only works for maps with a single key and a hasher that contains the key, like
+/// `Blake2_128Concat`.
+///
+/// The reader is restricted to the keys of the map, so the values before it are skipped and the
+/// iteration stops at its end instead of reading the rest of the snapshot.
+///
+/// ```no_run
+/// use polkadot_du::snapshot::{SnapshotReader, StorageMapIter};
+/// use subxt_metadata::StorageHasher;
+///
+/// // `AccountInfo` with nonce, consumers, providers, sufficients and `AccountData`.
+/// type AccountInfo = (u32, u32, u32, u32, [u128; 4]);
+///
+/// let reader = SnapshotReader::open("polkadot.snap")?;
+/// let accounts = StorageMapIter::<_, [u8; 32], AccountInfo>::new(
+///     reader,
+///     "System",
+///     "Account",
+///     StorageHasher::Blake2_128Concat,
+/// )?;
+///
+/// for account in accounts {
+///     let (who, (nonce, ..)) = account?;
+///     println!("0x{}: nonce {}", hex::encode(who), nonce);
+/// }
+/// # Ok::<(), polkadot_du::PduError>(())
+/// ```
+pub struct StorageMapIter<R: Read, K, V> {
+	entries: SnapshotReader<R>,
+	prefix: Vec<u8>,
+	/// Length of the hash in front of the key.
+	hash_len: usize,
+	_phantom: PhantomData<(K, V)>,
+}
+
+impl<R: Read, K: Decode, V: Decode> StorageMapIter<R, K, V> {
+	pub fn new(
+		entries: SnapshotReader<R>,
+		pallet: &str,
+		item: &str,
+		hasher: StorageHasher,
+	) -> Result<Self> {
+		if !hasher.ends_with_key() {
+			return Err(PduError::Metadata(format!(
+				"Cannot derive keys of {}::{} from hasher {:?}",
+				pallet, item, hasher
+			)))
+		}
+		let prefix = [twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat();
+		let entries = entries.range(Some(prefix.clone()), next_prefix(&prefix));
+
+		Ok(Self { entries, prefix, hash_len: hasher.len_excluding_key(), _phantom: PhantomData })
+	}
+}
+
+impl<R: Read, K: Decode, V: Decode> Iterator for StorageMapIter<R, K, V> {
+	type Item = Result<(K, V)>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (key, (value, _)) = match self.entries.next()? {
+			Ok(entry) => entry,
+			Err(e) => return Some(Err(PduError::SnapshotFormat(e.to_string()))),
+		};
+
+		let Some(mut raw_key) = key.get(self.prefix.len() + self.hash_len..) else {
+			let e = format!("Key 0x{} is too short", hex::encode(&key));
+			return Some(Err(PduError::SnapshotFormat(e)))
+		};
+		let decode_error = |what: &str, source| PduError::Decode {
+			context: format!("Failed to decode {} 0x{}", what, hex::encode(&key)),
+			source,
+		};
+		let decoded =
+			K::decode_all(&mut raw_key).map_err(|e| decode_error("key", e)).and_then(|k| {
+				let v =
+					V::decode_all(&mut &value[..]).map_err(|e| decode_error("value of key", e))?;
+				Ok((k, v))
+			});
+
+		Some(decoded)
+	}
+}
@@ -0,0 +1,85 @@
+//! Sources of raw Key-Value pairs, like snapshot files or CSV dumps.
+//!
+//! The analysis reads from any [`KvSource`], so new sources only need to stream their entries.
+
+use crate::{
+	csv::CsvReader,
+	error::{PduError, Result},
+	snapshot::{Input, SnapshotEntry, SnapshotReader},
+};
+use futures::{stream, Stream};
+use std::iter;
+use tokio::{sync::mpsc::channel, task};
+
+/// Number of entries that blocking sources read ahead of the stream.
+const READ_AHEAD: usize = 64;
+
+/// Streams the entries of a state together with their reference counts.
+pub trait KvSource {
+	/// Number of entries, if the source knows it before streaming them.
+	fn len_hint(&self) -> Option<usize>;
+
+	/// Stream the entries. The stream ends after the first error.
+	///
+	/// Sources that read with blocking IO do so on a blocking thread of the Tokio runtime, so this
+	/// must be called within one.
+	fn stream(self) -> impl Stream<Item = Result<SnapshotEntry>> + Send + 'static;
+}
+
+impl KvSource for SnapshotReader<Input> {
+	fn len_hint(&self) -> Option<usize> {
+		Some(self.num_keys())
+	}
+
+	/// Fails after the last entry if the tools that the snapshot is streamed through fail, see
+	/// [`SnapshotReader::finish`].
+	fn stream(self) -> impl Stream<Item = Result<SnapshotEntry>> + Send + 'static {
+		let mut reader = Some(self);
+		let entries = iter::from_fn(move || match reader.as_mut()?.next() {
+			Some(entry) => Some(entry.map_err(|e| PduError::SnapshotFormat(e.to_string()))),
+			None => reader.take()?.finish().err().map(Err),
+		});
+		blocking(entries)
+	}
+}
+
+impl KvSource for CsvReader {
+	fn len_hint(&self) -> Option<usize> {
+		Some(self.num_keys())
+	}
+
+	fn stream(self) -> impl Stream<Item = Result<SnapshotEntry>> + Send + 'static {
+		blocking(self)
+	}
+}
+
+/// Entries that are in memory already, sorted by key.
+impl KvSource for Vec<SnapshotEntry> {
+	fn len_hint(&self) -> Option<usize> {
+		Some(self.len())
+	}
+
+	fn stream(self) -> impl Stream<Item = Result<SnapshotEntry>> + Send + 'static {
+		stream::iter(self.into_iter().map(Ok))
+	}
+}
+
+/// Stream the entries of a blocking iterator, which is read on a blocking thread.
+///
+/// Reading stops once the stream is dropped.
+fn blocking<I>(entries: I) -> impl Stream<Item = Result<SnapshotEntry>> + Send + 'static
+where
+	I: Iterator<Item = Result<SnapshotEntry>> + Send + 'static,
+{
+	let (tx, mut rx) = channel(READ_AHEAD);
+	task::spawn_blocking(move || {
+		for entry in entries {
+			let failed = entry.is_err();
+			if tx.blocking_send(entry).is_err() || failed {
+				break
+			}
+		}
+	});
+
+	stream::poll_fn(move |cx| rx.poll_recv(cx))
+}
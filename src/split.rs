@@ -0,0 +1,90 @@
+//! Split a snapshot into smaller snapshots, one per pallet or group of pallets.
+//!
+//! Each part keeps the trailer of the original snapshot, so its storage root does not match the
+//! entries anymore. This is fine for analyzing and archiving the parts.
+
+use crate::{build_prefix_lookup, categorize_prefix, CategorizedKey, UNKNOWN};
+use anyhow::{anyhow, Result};
+use polkadot_du::snapshot::{SnapshotReader, SnapshotWriter};
+use std::{
+	collections::{btree_map::Entry, BTreeMap as Map},
+	fs,
+	path::Path,
+};
+use subxt::Metadata;
+
+/// Name of the part with all pallets that are in no group, unless splitting by pallet.
+const REST: &str = "Rest";
+
+/// A named group of pallets that end up in the same part.
+pub type Group = (String, Vec<String>);
+
+/// Parse a group of the form `Name=Pallet,Pallet`.
+pub fn parse_group(s: &str) -> Result<Group, String> {
+	let (name, pallets) = s
+		.split_once('=')
+		.ok_or_else(|| format!("Group must be of the form Name=Pallet,.. not {}", s))?;
+
+	Ok((name.into(), pallets.split(',').map(|p| p.trim().to_string()).collect()))
+}
+
+/// Write the entries of a snapshot into one snapshot per part in `out_dir`.
+pub fn run(
+	snapshot: &str,
+	meta: &Metadata,
+	out_dir: &str,
+	groups: &[Group],
+	by_pallet: bool,
+) -> Result<()> {
+	fs::create_dir_all(out_dir)?;
+	let pallets = meta.pallets().collect::<Vec<_>>();
+	let lookup = build_prefix_lookup(&pallets);
+
+	// Pallet names are matched case-insensitive like with `--pallet`.
+	let mut group_of = Map::new();
+	for (name, pallets) in groups {
+		for pallet in pallets {
+			if group_of.insert(pallet.to_lowercase(), name.clone()).is_some() {
+				return Err(anyhow!("Pallet {} is in multiple groups", pallet))
+			}
+		}
+	}
+
+	let mut reader = SnapshotReader::open(snapshot)?;
+	let state_version = reader.state_version();
+	let mut parts = Map::new();
+
+	for entry in &mut reader {
+		let entry = entry?;
+		let pallet = match categorize_prefix(&entry.0, &lookup) {
+			// Child tries stay with the pallet that owns them.
+			CategorizedKey::Item(pallet, _) |
+			CategorizedKey::Pallet(pallet) |
+			CategorizedKey::Child { pallet, .. } => pallet,
+			CategorizedKey::Unknown => UNKNOWN.into(),
+		};
+		let part = match group_of.get(&pallet.to_lowercase()) {
+			Some(group) => group.clone(),
+			None if by_pallet => pallet,
+			None => REST.into(),
+		};
+
+		let writer = match parts.entry(part) {
+			Entry::Occupied(writer) => writer.into_mut(),
+			Entry::Vacant(part) => {
+				let path = Path::new(out_dir).join(format!("{}.snap", part.key()));
+				part.insert(SnapshotWriter::create(path, state_version)?)
+			},
+		};
+		writer.write(&entry)?;
+	}
+
+	let trailer = reader.into_trailer()?;
+	let num_parts = parts.len();
+	for writer in parts.into_values() {
+		writer.finish(&trailer)?;
+	}
+
+	println!("Split {} into {} snapshots in {}", snapshot, num_parts, out_dir);
+	Ok(())
+}
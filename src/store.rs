@@ -0,0 +1,321 @@
+//! A local store for a history of snapshots that keeps every distinct value only once.
+//!
+//! Consecutive snapshots of a chain share most of their values, so the store saves them by their
+//! hash in one append-only pack. A stored snapshot is a manifest of its keys with the hashes of
+//! their values:
+//!
+//! - `objects.pack`: the distinct values, one after the other.
+//! - `objects.idx`: the hash, offset and length of every value in the pack.
+//! - `snapshots/<name>`: a header and the key, value hash and reference count of every entry.
+
+use crate::{fmt_bytes, Units};
+use anyhow::{anyhow, Result};
+use parity_scale_codec::{Decode, Encode, IoReader};
+use polkadot_du::snapshot::{SnapshotReader, SnapshotWriter};
+use sp_crypto_hashing::blake2_256;
+use std::{
+	collections::HashMap,
+	fs::{self, File, OpenOptions},
+	io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+	path::{Path, PathBuf},
+};
+
+/// Length of an index record: a hash, an offset and a length.
+const INDEX_RECORD_LEN: usize = 32 + 8 + 4;
+
+/// Length of the header of a manifest, see `Header`.
+const HEADER_LEN: usize = 1 + 8 + 8 + 32;
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+	/// Add a snapshot to the store.
+	Add {
+		/// Path to the snapshot file.
+		snapshot: String,
+
+		/// Name to store the snapshot under.
+		///
+		/// Defaults to the file name without extension.
+		#[clap(long)]
+		name: Option<String>,
+	},
+	/// Write a stored snapshot to a file.
+	Extract {
+		/// Name of the stored snapshot.
+		name: String,
+
+		/// Path to write the snapshot to.
+		#[clap(long)]
+		out: String,
+	},
+	/// List the stored snapshots and how much the store saves.
+	List,
+}
+
+pub fn run(dir: &str, command: Command, units: Units) -> Result<()> {
+	match command {
+		Command::Add { snapshot, name } => {
+			let name = match name {
+				Some(name) => name,
+				None => Path::new(&snapshot)
+					.file_stem()
+					.and_then(|s| s.to_str())
+					.ok_or_else(|| anyhow!("Cannot derive a name from {}, use --name", snapshot))?
+					.to_string(),
+			};
+			add(dir, &snapshot, &name, units)
+		},
+		Command::Extract { name, out } => extract(dir, &name, &out),
+		Command::List => list(dir, units),
+	}
+}
+
+/// Summary of a stored snapshot, stored at the start of its manifest.
+///
+/// Encoded with fixed length, so that it can be written after the entries.
+struct Header {
+	state_version: u8,
+	num_keys: u64,
+	/// Size of all values, as if they were not deduplicated.
+	value_bytes: u64,
+	/// Hash of the trailer with the storage root and header.
+	trailer: [u8; 32],
+}
+
+impl Header {
+	fn encode(&self) -> Vec<u8> {
+		(self.state_version, self.num_keys, self.value_bytes, self.trailer).encode()
+	}
+
+	fn decode(input: &mut impl parity_scale_codec::Input) -> Result<Self> {
+		let (state_version, num_keys, value_bytes, trailer) = Decode::decode(input)?;
+		Ok(Self { state_version, num_keys, value_bytes, trailer })
+	}
+}
+
+/// Offset and length of every value in the pack, by its hash.
+type Index = HashMap<[u8; 32], (u64, u32)>;
+
+/// The pack of values with its index.
+struct Objects {
+	index: Index,
+	pack: BufWriter<File>,
+	pack_len: u64,
+	index_file: BufWriter<File>,
+}
+
+impl Objects {
+	/// Open the pack and index in `dir` to add values, creating them if needed.
+	fn open(dir: &Path) -> Result<Self> {
+		fs::create_dir_all(dir.join("snapshots"))?;
+		let append = |name: &str| OpenOptions::new().create(true).append(true).open(dir.join(name));
+		let pack = append("objects.pack")?;
+		let pack_len = pack.metadata()?.len();
+		let index_file = append("objects.idx")?;
+
+		// New records must follow the valid ones, instead of whatever a crash left behind.
+		let (index, valid_len) = read_index(dir, pack_len)?;
+		let index_len = index_file.metadata()?.len();
+		if valid_len < index_len {
+			let dropped = index_len - valid_len;
+			log::warn!("Dropping {} bytes of the index after an interrupted add", dropped);
+			index_file.set_len(valid_len)?;
+		}
+
+		let (pack, index_file) = (BufWriter::new(pack), BufWriter::new(index_file));
+		Ok(Self { index, pack, pack_len, index_file })
+	}
+
+	/// Add a value unless it is already stored and return its hash.
+	fn put(&mut self, value: &[u8]) -> Result<[u8; 32]> {
+		let hash = blake2_256(value);
+		if self.index.contains_key(&hash) {
+			return Ok(hash)
+		}
+
+		let len = u32::try_from(value.len()).map_err(|_| anyhow!("Value is too large to store"))?;
+		self.pack.write_all(value)?;
+		self.index_file.write_all(&hash)?;
+		self.index_file.write_all(&self.pack_len.to_le_bytes())?;
+		self.index_file.write_all(&len.to_le_bytes())?;
+		self.index.insert(hash, (self.pack_len, len));
+		self.pack_len += len as u64;
+		Ok(hash)
+	}
+
+	/// Flush the pack before its index.
+	///
+	/// The buffer of the index may be flushed before the pack anyway, so the index can point past
+	/// the pack after a crash. `Objects::open` drops such records.
+	fn flush(&mut self) -> Result<()> {
+		self.pack.flush()?;
+		self.pack.get_ref().sync_data()?;
+		self.index_file.flush()?;
+		Ok(())
+	}
+}
+
+/// Read the records of the index of a pack with `pack_len` bytes.
+///
+/// A crash can leave a partial record at the end of the index, or records of values that did not
+/// make it into the pack. Returns the records before the first such one, and their length.
+fn read_index(dir: &Path, pack_len: u64) -> Result<(Index, u64)> {
+	let mut index = HashMap::new();
+	let mut valid_len = 0;
+	for record in fs::read(dir.join("objects.idx"))?.chunks_exact(INDEX_RECORD_LEN) {
+		let hash = record[..32].try_into().expect("Record has a hash; qed");
+		let offset = u64::from_le_bytes(record[32..40].try_into().expect("Has offset; qed"));
+		let len = u32::from_le_bytes(record[40..].try_into().expect("Has length; qed"));
+		// Values are appended in the order of their records.
+		if offset + len as u64 > pack_len {
+			break
+		}
+		index.insert(hash, (offset, len));
+		valid_len += INDEX_RECORD_LEN as u64;
+	}
+	Ok((index, valid_len))
+}
+
+/// Reads values from the pack by their hash.
+struct PackReader {
+	index: Index,
+	pack: File,
+}
+
+impl PackReader {
+	/// Open the pack in `dir` without changing it.
+	fn open(dir: &Path) -> Result<Self> {
+		let pack = File::open(dir.join("objects.pack"))
+			.map_err(|e| anyhow!("No store at {}: {}", dir.display(), e))?;
+		let (index, _) = read_index(dir, pack.metadata()?.len())?;
+		Ok(Self { index, pack })
+	}
+
+	/// Read a value and check that it has the hash, since the pack may be corrupt after a crash.
+	fn get(&mut self, hash: &[u8; 32]) -> Result<Vec<u8>> {
+		let (offset, len) = self
+			.index
+			.get(hash)
+			.ok_or_else(|| anyhow!("Value 0x{} is missing in the store", hex::encode(hash)))?;
+		let mut value = vec![0; *len as usize];
+		self.pack.seek(SeekFrom::Start(*offset))?;
+		self.pack.read_exact(&mut value)?;
+		if blake2_256(&value) != *hash {
+			return Err(anyhow!("Value 0x{} is corrupt in the store", hex::encode(hash)))
+		}
+		Ok(value)
+	}
+}
+
+fn manifest_path(dir: &Path, name: &str) -> Result<PathBuf> {
+	let invalid = name.contains(['/', '\\']) || name.starts_with('.') || name.ends_with(".part");
+	if name.is_empty() || invalid {
+		return Err(anyhow!("Invalid name of a stored snapshot: {}", name))
+	}
+	Ok(dir.join("snapshots").join(name))
+}
+
+fn add(dir: &str, snapshot: &str, name: &str, units: Units) -> Result<()> {
+	let dir = Path::new(dir);
+	let mut objects = Objects::open(dir)?;
+	let path = manifest_path(dir, name)?;
+	if path.exists() {
+		return Err(anyhow!("A snapshot named {} is already stored", name))
+	}
+	let pack_len = objects.pack_len;
+
+	let mut reader = SnapshotReader::open(snapshot)?;
+	let mut header = Header {
+		state_version: reader.state_version(),
+		num_keys: 0,
+		value_bytes: 0,
+		trailer: [0; 32],
+	};
+	// The header is written last, once the entries are known.
+	let part = dir.join("snapshots").join(format!("{}.part", name));
+	let mut manifest = BufWriter::new(File::create(&part)?);
+	manifest.write_all(&[0; HEADER_LEN])?;
+
+	for entry in &mut reader {
+		let (key, (value, ref_count)) = entry?;
+		let hash = objects.put(&value)?;
+		manifest.write_all(&(key, hash, ref_count).encode())?;
+		header.num_keys += 1;
+		header.value_bytes += value.len() as u64;
+	}
+	header.trailer = objects.put(&reader.into_trailer()?)?;
+	objects.flush()?;
+
+	let mut manifest = manifest.into_inner().map_err(|e| e.into_error())?;
+	manifest.seek(SeekFrom::Start(0))?;
+	manifest.write_all(&header.encode())?;
+	manifest.sync_all()?;
+	fs::rename(&part, &path)?;
+
+	println!(
+		"Stored {} with {} keys as {}: {} of values, {} of them new",
+		snapshot,
+		header.num_keys,
+		name,
+		fmt_bytes(header.value_bytes as usize, false, units),
+		fmt_bytes((objects.pack_len - pack_len) as usize, false, units)
+	);
+	Ok(())
+}
+
+fn extract(dir: &str, name: &str, out: &str) -> Result<()> {
+	let dir = Path::new(dir);
+	let path = manifest_path(dir, name)?;
+	let file =
+		File::open(&path).map_err(|e| anyhow!("No snapshot named {} is stored: {}", name, e))?;
+	let mut manifest = IoReader(BufReader::new(file));
+	let header = Header::decode(&mut manifest)?;
+
+	let mut pack = PackReader::open(dir)?;
+	let mut writer = SnapshotWriter::create(out, header.state_version)?;
+	for _ in 0..header.num_keys {
+		let (key, hash, ref_count) = <(Vec<u8>, [u8; 32], i32)>::decode(&mut manifest)?;
+		writer.write(&(key, (pack.get(&hash)?, ref_count)))?;
+	}
+	writer.finish(&pack.get(&header.trailer)?)?;
+
+	println!("Extracted {} with {} keys to {}", name, header.num_keys, out);
+	Ok(())
+}
+
+fn list(dir: &str, units: Units) -> Result<()> {
+	let dir = Path::new(dir);
+	let mut names = Vec::new();
+	let entries = fs::read_dir(dir.join("snapshots"))
+		.map_err(|e| anyhow!("No store at {}: {}", dir.display(), e))?;
+	for entry in entries {
+		let entry = entry?;
+		let name = entry.file_name().to_string_lossy().to_string();
+		// Snapshots that are still being added.
+		if name.ends_with(".part") {
+			continue
+		}
+		names.push(name);
+	}
+	names.sort();
+
+	let mut total = 0;
+	for name in &names {
+		let mut header = [0; HEADER_LEN];
+		File::open(dir.join("snapshots").join(name))?.read_exact(&mut header)?;
+		let header = Header::decode(&mut &header[..])?;
+		total += header.value_bytes;
+		let size = fmt_bytes(header.value_bytes as usize, false, units);
+		println!("{}: {} keys, {} of values", name, header.num_keys, size);
+	}
+
+	let pack_len = fs::metadata(dir.join("objects.pack")).map_or(0, |m| m.len());
+	println!(
+		"{} snapshots with {} of values in a pack of {}, {:.1}x deduplicated",
+		names.len(),
+		fmt_bytes(total as usize, false, units),
+		fmt_bytes(pack_len as usize, false, units),
+		total as f64 / pack_len.max(1) as f64
+	);
+	Ok(())
+}
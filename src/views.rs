@@ -0,0 +1,135 @@
+//! Attribute the storage to the runtime APIs and RPC queries that read it.
+//!
+//! Which items a query reads is not part of the metadata, so the views are either the built-in
+//! ones for common queries or given as JSON that maps a view to its items, eg.
+//! `{"Balance lookup": ["System::Account", "Balances::Locks"]}`. Items ending with `*` match all
+//! items with that prefix, like `Staking::ErasStakers*`.
+//!
+//! Large items behind a query make it slow: a lookup of one entry needs a deeper trie path and a
+//! larger proof, and a scan over all entries reads all of them.
+
+use crate::{fmt_bytes, PalletInfo, Units, View};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use std::{collections::BTreeMap as Map, fs};
+
+/// The storage items of each view by its name.
+pub type Views = Map<String, Vec<String>>;
+
+/// Common queries of wallets, explorers and staking dashboards.
+const BUILTIN: &[(&str, &[&str])] = &[
+	("Account balance (system_account)", &["System::Account"]),
+	(
+		"Balance locks and holds",
+		&["Balances::Locks", "Balances::Freezes", "Balances::Holds", "Balances::Reserves"],
+	),
+	("Fee estimation (TransactionPaymentApi)", &["TransactionPayment::NextFeeMultiplier"]),
+	("Staking exposures", &["Staking::ErasStakers*"]),
+	(
+		"Staking ledgers and nominations",
+		&["Staking::Ledger", "Staking::Bonded", "Staking::Nominators"],
+	),
+	(
+		"Nomination pools (NominationPoolsApi)",
+		&[
+			"NominationPools::PoolMembers",
+			"NominationPools::BondedPools",
+			"NominationPools::RewardPools",
+			"NominationPools::SubPoolsStorage",
+		],
+	),
+	("Identity lookup", &["Identity::IdentityOf", "Identity::SuperOf"]),
+	("Proxies and multisigs", &["Proxy::Proxies", "Multisig::Multisigs"]),
+	(
+		"Referenda and votes",
+		&[
+			"Referenda::ReferendumInfoFor",
+			"ConvictionVoting::VotingFor",
+			"ConvictionVoting::ClassLocksFor",
+		],
+	),
+	("Block events (system_events)", &["System::Events", "System::EventTopics"]),
+];
+
+/// Load the views from a JSON file, or the built-in ones for `default`.
+pub fn load(path: &str) -> Result<Views> {
+	if path == "default" {
+		return Ok(BUILTIN
+			.iter()
+			.map(|(view, items)| (view.to_string(), items.iter().map(|i| i.to_string()).collect()))
+			.collect())
+	}
+
+	let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+	let json = json.as_object().ok_or_else(|| anyhow!("{} must map views to items", path))?;
+	let mut views = Views::new();
+	for (view, items) in json {
+		let items = items
+			.as_array()
+			.ok_or_else(|| anyhow!("Items of {} are no list", view))?
+			.iter()
+			.map(|item| {
+				let item = item.as_str().ok_or_else(|| anyhow!("Item of {} is no string", view))?;
+				if !item.contains("::") {
+					return Err(anyhow!(
+						"Item {} of {} is not of the form Pallet::Item",
+						item,
+						view
+					))
+				}
+				Ok(item.to_string())
+			})
+			.collect::<Result<_>>()?;
+		views.insert(view.clone(), items);
+	}
+
+	if views.is_empty() {
+		return Err(anyhow!("No views found in {}", path))
+	}
+	Ok(views)
+}
+
+/// Print the size of the storage items behind each view.
+pub fn print(found_by_pallet: &Map<String, PalletInfo>, views: &Views, view: View, units: Units) {
+	println!("Storage by view:");
+	let mut rows = Vec::new();
+	for (name, patterns) in views {
+		let mut items = Vec::new();
+		for pattern in patterns {
+			let (pallet, item) = pattern.split_once("::").unwrap_or((pattern, ""));
+			let Some(info) = found_by_pallet.get(pallet) else { continue };
+			for info in info.items.values().filter(|i| matches(item, &i.name)) {
+				let size = view.size(info.key_len, info.value_len);
+				items.push((format!("{}::{}", pallet, info.name), size, info.num_entries));
+			}
+		}
+		let size = items.iter().map(|(_, size, _)| size).sum::<usize>();
+		rows.push((name, size, items));
+	}
+
+	for (name, size, items) in rows.into_iter().sorted_by_key(|(_, size, _)| *size).rev() {
+		if items.is_empty() {
+			println!("  {}: no storage in this snapshot", name);
+			continue
+		}
+		let entries = items.iter().map(|(_, _, entries)| entries).sum::<usize>();
+		println!(
+			"  {}: {} in {} entries, {} per entry",
+			name,
+			fmt_bytes(size, false, units),
+			entries,
+			fmt_bytes(size / entries.max(1), false, units)
+		);
+		for (item, size, entries) in items.iter().sorted_by_key(|(_, size, _)| *size).rev() {
+			println!("    {}: {} in {} entries", item, fmt_bytes(*size, false, units), entries);
+		}
+	}
+}
+
+/// Whether an item name matches a pattern like `ErasStakers*`.
+fn matches(pattern: &str, item: &str) -> bool {
+	match pattern.strip_suffix('*') {
+		Some(prefix) => item.starts_with(prefix),
+		None => item == pattern,
+	}
+}
@@ -0,0 +1,326 @@
+//! Golden tests of the output formats, run against the snapshot of `pdu fixture`.
+//!
+//! Run with `UPDATE_GOLDEN=1` to write the current outputs to `tests/golden/` after an intended
+//! change of a format, and review the diff.
+
+use polkadot_du::{fixture, snapshot::SnapshotReader};
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+/// Write the fixture into a directory of its own, so that the tests can run in parallel.
+fn fixture_dir(test: &str) -> PathBuf {
+	let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+	fixture::write(&dir, fixture::NETWORK).unwrap();
+	dir
+}
+
+/// Run `pdu` in `dir` and return its standard output.
+fn pdu(dir: &Path, args: &[&str]) -> String {
+	let output = Command::new(env!("CARGO_BIN_EXE_pdu"))
+		.args(args)
+		.current_dir(dir)
+		.output()
+		.unwrap();
+	assert!(
+		output.status.success(),
+		"pdu {:?} failed: {}",
+		args,
+		String::from_utf8_lossy(&output.stderr)
+	);
+	String::from_utf8(output.stdout).unwrap()
+}
+
+/// Run `pdu` in `dir`, which must fail, and return its standard error.
+fn pdu_fails(dir: &Path, args: &[&str]) -> String {
+	let output = Command::new(env!("CARGO_BIN_EXE_pdu"))
+		.args(args)
+		.current_dir(dir)
+		.output()
+		.unwrap();
+	assert!(!output.status.success(), "pdu {:?} succeeded", args);
+	String::from_utf8(output.stderr).unwrap()
+}
+
+/// Remove what changes between runs: colors, the version of PDU and the time of the analysis.
+fn normalize(output: &str) -> String {
+	let mut normalized = String::new();
+	let mut rest = output;
+	while let Some(start) = rest.find('\x1b') {
+		normalized.push_str(&rest[..start]);
+		rest = rest[start..].split_once('m').map_or("", |(_, after)| after);
+	}
+	normalized.push_str(rest);
+
+	normalized
+		.lines()
+		.map(|line| line.split_once(", analyzed by pdu").map_or(line, |(before, _)| before))
+		.map(|line| line.to_string() + "\n")
+		.collect()
+}
+
+/// Compare an output to its golden file, or update the file with `UPDATE_GOLDEN=1`.
+fn check_golden(name: &str, output: &str) {
+	let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+	let output = normalize(output);
+	if std::env::var_os("UPDATE_GOLDEN").is_some() {
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(&path, &output).unwrap();
+		return
+	}
+
+	let golden = fs::read_to_string(&path).unwrap_or_else(|e| {
+		panic!("Failed to read {}, run with UPDATE_GOLDEN=1: {}", path.display(), e)
+	});
+	assert!(
+		golden == output,
+		"The output differs from {}, run with UPDATE_GOLDEN=1 if that is intended:\n{}",
+		path.display(),
+		output
+	);
+}
+
+#[test]
+fn tree() {
+	let dir = fixture_dir("tree");
+	check_golden("tree.txt", &pdu(&dir, &["--network", "fixture"]));
+}
+
+#[test]
+fn tree_verbose_keys() {
+	let dir = fixture_dir("tree_verbose_keys");
+	let args = ["--network", "fixture", "--verbose", "--view", "keys", "--expand-keys", "1"];
+	check_golden("tree_verbose_keys.txt", &pdu(&dir, &args));
+}
+
+#[test]
+fn tree_top() {
+	let dir = fixture_dir("tree_top");
+	let args = ["--network", "fixture", "--pallet", "Staking", "--top", "2"];
+	check_golden("tree_top.txt", &pdu(&dir, &args));
+}
+
+#[test]
+fn tree_stats() {
+	let dir = fixture_dir("tree_stats");
+	let args = ["--network", "fixture", "--pallet", "Staking", "--stats"];
+	check_golden("tree_stats.txt", &pdu(&dir, &args));
+}
+
+#[test]
+fn tree_read_cost() {
+	let dir = fixture_dir("tree_read_cost");
+	let args = ["--network", "fixture", "--pallet", "Staking", "--read-cost"];
+	check_golden("tree_read_cost.txt", &pdu(&dir, &args));
+}
+
+#[test]
+fn flamegraph() {
+	let dir = fixture_dir("flamegraph");
+	check_golden("flamegraph.txt", &pdu(&dir, &["--network", "fixture", "--output", "flamegraph"]));
+}
+
+#[test]
+fn json() {
+	let dir = fixture_dir("json");
+	pdu(&dir, &["--network", "fixture", "--json", "fixture.json"]);
+	check_golden("fixture.json", &fs::read_to_string(dir.join("fixture.json")).unwrap());
+}
+
+#[test]
+fn openmetrics() {
+	let dir = fixture_dir("openmetrics");
+	pdu(&dir, &["--network", "fixture", "--output", "openmetrics"]);
+	check_golden("fixture.prom", &fs::read_to_string(dir.join("fixture.prom")).unwrap());
+}
+
+/// The entries with an account of the fixture, decoded and as hex.
+#[test]
+fn find() {
+	let dir = fixture_dir("find");
+	let account = format!("0x{}", "01".repeat(32));
+	check_golden("find.txt", &pdu(&dir, &["--network", "fixture", "--find", &account]));
+	let raw = pdu(&dir, &["--network", "fixture", "--find", &account, "--raw"]);
+	check_golden("find_raw.txt", &raw);
+}
+
+/// The entries with any account of a file, written as JSON.
+#[test]
+fn find_json() {
+	let dir = fixture_dir("find_json");
+	let accounts = format!("0x{}\n0x{}\n", "01".repeat(32), "02".repeat(32));
+	fs::write(dir.join("accounts.txt"), accounts).unwrap();
+	let args = ["--find", "Accounts(accounts.txt)", "--found-json", "found.json"];
+	pdu(&dir, &[&["--network", "fixture"][..], &args].concat());
+	check_golden("found.json", &fs::read_to_string(dir.join("found.json")).unwrap());
+}
+
+/// All entries of an item, streamed from the snapshot.
+#[test]
+fn find_prefix() {
+	let dir = fixture_dir("find_prefix");
+	let output = pdu(&dir, &["--network", "fixture", "--find", "Prefix(Balances::Locks)"]);
+	check_golden("find_prefix.txt", &output);
+}
+
+#[test]
+fn split_output() {
+	let dir = fixture_dir("split_output");
+	pdu(&dir, &["--network", "fixture", "--split-output", "split"]);
+	for file in ["index.json", "Staking.json"] {
+		let output = fs::read_to_string(dir.join("split").join(file)).unwrap();
+		check_golden(&format!("split/{}", file), &output);
+	}
+}
+
+/// The second run with `--index` prints the results from the index. Runs without `--index` read
+/// the snapshot pallet by pallet with it, which must not change the results.
+#[test]
+fn index() {
+	let dir = fixture_dir("index");
+	let _ = fs::remove_file(dir.join("fixture.snap.idx"));
+	check_golden("tree.txt", &pdu(&dir, &["--network", "fixture", "--index"]));
+	assert!(dir.join("fixture.snap.idx").exists());
+	check_golden("tree.txt", &pdu(&dir, &["--network", "fixture", "--index"]));
+	check_golden("tree.txt", &pdu(&dir, &["--network", "fixture"]));
+	let args = ["--network", "fixture", "--verbose", "--view", "keys", "--expand-keys", "1"];
+	check_golden("tree_verbose_keys.txt", &pdu(&dir, &args));
+}
+
+/// A broken index is ignored unless it was asked for with `--index`.
+#[test]
+fn broken_index() {
+	let dir = fixture_dir("broken_index");
+	fs::write(dir.join("fixture.snap.idx"), b"PDUIDX").unwrap();
+	check_golden("tree.txt", &pdu(&dir, &["--network", "fixture"]));
+	pdu_fails(&dir, &["--network", "fixture", "--index"]);
+}
+
+/// A CSV dump of the same state gives the same results as the snapshot.
+#[test]
+fn csv_source() {
+	let dir = fixture_dir("csv_source");
+	let csv: String = fixture::entries()
+		.iter()
+		.map(|(key, (value, _))| format!("{},{}\n", hex::encode(key), hex::encode(value)))
+		.collect();
+	fs::write(dir.join("fixture.csv"), csv).unwrap();
+
+	check_golden("tree.txt", &pdu(&dir, &["--network", "fixture", "--source", "csv:fixture.csv"]));
+}
+
+/// Merged snapshots are sorted by key like any other, so that ranges of them can be read.
+#[test]
+fn merge_sorted() {
+	let dir = fixture_dir("merge_sorted");
+	pdu(&dir, &["split", "--network", "fixture", "--out-dir", "parts", "--by-pallet"]);
+	// Not in the order of their keys, whose prefixes are hashes of the pallet names.
+	let parts = ["Staking", "System", "Balances", "Crowdloan", "Unknown"]
+		.map(|p| format!("parts/{}.snap", p));
+	let mut args = vec!["merge", "--out", "merged.snap"];
+	args.extend(parts.iter().map(String::as_str));
+	pdu(&dir, &args);
+
+	let entries = fixture::entries();
+	let merged = dir.join("merged.snap");
+	let reader = SnapshotReader::open(merged.to_str().unwrap()).unwrap();
+	assert_eq!(reader.num_keys(), entries.len());
+	let (start, end) = (entries[10].0.clone(), entries[30].0.clone());
+	let range = reader.range(Some(start), Some(end)).collect::<Result<Vec<_>, _>>().unwrap();
+	assert_eq!(range, entries[10..30]);
+}
+
+/// A misspelled item fails its assertion instead of passing without entries.
+#[test]
+fn check_unknown_item() {
+	let dir = fixture_dir("check_unknown_item");
+	let assertions = ["Balances::Locks", "Balances::Lokcs"]
+		.map(|item| format!("- item: {}\n  max_entries: 10\n", item))
+		.concat();
+	fs::write(dir.join("assertions.yaml"), assertions).unwrap();
+
+	let stderr = pdu_fails(&dir, &["check", "--network", "fixture"]);
+	assert!(stderr.contains("1 of 2 assertions failed"), "{}", stderr);
+}
+
+/// A truncated snapshot fails the analysis instead of reporting the keys before the end.
+#[test]
+fn truncated_snapshot() {
+	let dir = fixture_dir("truncated_snapshot");
+	let snapshot = fs::read(dir.join("fixture.snap")).unwrap();
+	fs::write(dir.join("truncated.snap"), &snapshot[..snapshot.len() - 50]).unwrap();
+
+	let stderr = pdu_fails(&dir, &["--network", "fixture", "--snapshot", "truncated.snap"]);
+	assert!(stderr.contains("Loading failed after"), "{}", stderr);
+}
+
+/// A snapshot whose ciphertext was tampered with fails the analysis, even though `gpg` only notices
+/// at the end of the snapshot, after all entries were decrypted.
+#[test]
+fn tampered_gpg_snapshot() {
+	let dir = fixture_dir("tampered_gpg_snapshot");
+	// A keyring of its own, which must not contain the key of an earlier run.
+	let home = dir.join("gnupg");
+	let _ = fs::remove_dir_all(&home);
+	fs::create_dir_all(&home).unwrap();
+	let gpg = |args: &[&str]| {
+		let output = Command::new("gpg").args(args).env("GNUPGHOME", &home).output();
+		output.is_ok_and(|output| output.status.success())
+	};
+	if !gpg(&["--batch", "--passphrase", "", "--quick-gen-key", "pdu-test", "default", "default"]) {
+		eprintln!("Skipping, gpg is not installed");
+		return
+	}
+	let status = Command::new(env!("CARGO_BIN_EXE_pdu"))
+		.args(["encrypt", "fixture.snap", "--tool", "gpg", "-r", "pdu-test"])
+		.env("GNUPGHOME", &home)
+		.current_dir(&dir)
+		.status()
+		.unwrap();
+	assert!(status.success());
+
+	// The last bytes are the checksum of the plain snapshot, so all entries still decrypt.
+	let mut encrypted = fs::read(dir.join("fixture.snap.gpg")).unwrap();
+	*encrypted.last_mut().unwrap() ^= 1;
+	fs::write(dir.join("tampered.snap.gpg"), encrypted).unwrap();
+
+	let output = Command::new(env!("CARGO_BIN_EXE_pdu"))
+		.args(["--network", "fixture", "--snapshot", "tampered.snap.gpg"])
+		.env("GNUPGHOME", &home)
+		.current_dir(&dir)
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(!output.status.success(), "The tampered snapshot was analyzed");
+	assert!(stderr.contains("gpg failed"), "{}", stderr);
+}
+
+#[tokio::test]
+async fn library() {
+	let dir = fixture_dir("library");
+	let snapshot = dir.join("fixture.snap");
+	let found_by_pallet =
+		polkadot_du::analyze_snapshot(snapshot.to_str().unwrap(), &fixture::metadata())
+			.await
+			.unwrap();
+
+	let size = |(key, (value, _)): &(Vec<u8>, (Vec<u8>, i32))| key.len() + value.len();
+	let total = fixture::entries().iter().map(size).sum::<usize>();
+	assert_eq!(found_by_pallet.values().map(|p| p.size).sum::<usize>(), total);
+	assert_eq!(found_by_pallet["Staking"].items["ErasStakersPaged"].num_entries, 6);
+}
+
+/// The entries of a child trie, as read by `--live`, are accounted to the trie of their root.
+#[tokio::test]
+async fn child_tries() {
+	let mut entries = fixture::entries();
+	entries.extend(fixture::child_entries());
+	entries.sort();
+	let found_by_pallet = polkadot_du::info::analyze(entries, &fixture::metadata()).await.unwrap();
+
+	let child_tries = &found_by_pallet[polkadot_du::child::CHILD_TRIES];
+	assert_eq!(child_tries.items.values().map(|trie| trie.num_entries).collect::<Vec<_>>(), [4]);
+	assert!(child_tries.items.keys().all(|trie| trie.starts_with("Crowdloan 0x")));
+}